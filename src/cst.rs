@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::TokenType;
+
+
+// 无损语法树（CST）：保留全部空白符、换行符与注释，
+// 按先序依次拼接各词法单元的文本即得到原始输入
+
+
+// 叶节点：一个词法单元及其源码文本
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    pub token_type: TokenType,
+    pub text: String,
+    pub index: (usize, usize),
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    // 整个输入
+    Program,
+    // `( ... )`
+    List,
+    // `#( ... )`
+    Vector,
+    // `' start`
+    Quote,
+    // `` ` start``
+    Quasiquote,
+    // `, start`
+    Unquote,
+    // `,@ start`
+    UnquoteSplicing,
+    // `#; start`
+    DatumComment,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+
+impl SyntaxNode {
+    // 节点覆盖的源码文本
+    pub fn text(&self) -> String {
+        self.to_string()
+    }
+
+    // 节点首个词法单元的位置
+    pub fn index(&self) -> Option<(usize, usize)> {
+        self.children.first().and_then(SyntaxElement::index)
+    }
+
+    // 除trivia外的子元素
+    pub fn significant_children(&self) -> impl Iterator<Item = &SyntaxElement> {
+        self.children.iter().filter(|child| !child.is_trivia())
+    }
+}
+
+
+impl SyntaxElement {
+    pub fn index(&self) -> Option<(usize, usize)> {
+        match self {
+            SyntaxElement::Node(node) => node.index(),
+            SyntaxElement::Token(token) => Some(token.index),
+        }
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, SyntaxElement::Token(token) if token.token_type.is_trivia())
+    }
+}
+
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            write!(f, "{}", child)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl fmt::Display for SyntaxElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyntaxElement::Node(node) => write!(f, "{}", node),
+            SyntaxElement::Token(token) => write!(f, "{}", token.text),
+        }
+    }
+}
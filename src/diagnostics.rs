@@ -0,0 +1,182 @@
+use std::fmt::Write;
+
+use crate::{Error, EvalError, ParseError, ScanError};
+#[cfg(test)]
+mod tests;
+
+
+// ANSI转义序列
+const RED_BOLD: &str = "\x1b[1;31m";
+const BLUE_BOLD: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+
+// 一条面向用户的错误信息
+pub struct Diagnostic {
+    pub message: String,
+    // 出错位置；None表示输入末尾
+    pub index: Option<(usize, usize)>,
+    // 标注于脱字符之后的说明
+    pub label: Option<String>,
+}
+
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, index: Option<(usize, usize)>, label: Option<&str>) -> Diagnostic {
+        Diagnostic { message: message.into(), index, label: label.map(String::from) }
+    }
+
+    // 渲染为带源码片段与下划线的文本，origin为源码的来源（如文件名）
+    //
+    // error: unexpected token
+    //  --> main.lisp:3:5
+    //   |
+    // 3 | (if ) x)
+    //   |      ^ expected an expression
+    pub fn render(&self, source: &str, origin: &str, color: bool) -> String {
+        let paint = |style: &'static str| if color { style } else { "" };
+        let reset = paint(RESET);
+
+        let (row, column) = self.index.unwrap_or_else(|| end_of_input(source));
+        let line = source.lines().nth(row).unwrap_or("");
+        let gutter = " ".repeat((row + 1).to_string().len());
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{}error{}{}: {}{}", paint(RED_BOLD), reset, paint(BOLD), self.message, reset);
+        let _ = writeln!(out, "{}{}-->{} {}:{}:{}", gutter, paint(BLUE_BOLD), reset, origin, row + 1, column + 1);
+        let _ = writeln!(out, "{} {}|{}", gutter, paint(BLUE_BOLD), reset);
+        let _ = writeln!(out, "{}{} |{} {}", paint(BLUE_BOLD), row + 1, reset, line);
+
+        // 下划线按字符计数对齐（制表符原样保留以保持对齐）
+        let padding: String = line.chars().take(column).map(|ch| if ch == '\t' { '\t' } else { ' ' }).collect();
+        let width = if self.index.is_some() { token_width(line, column) } else { 1 };
+        let _ = write!(out, "{} {}|{} {}{}{}{}", gutter, paint(BLUE_BOLD), reset, padding, paint(RED_BOLD), "^".repeat(width), reset);
+        if let Some(label) = &self.label {
+            let _ = write!(out, " {}{}{}", paint(RED_BOLD), label, reset);
+        }
+        out.push('\n');
+
+        out
+    }
+}
+
+
+impl From<&ScanError> for Diagnostic {
+    fn from(e: &ScanError) -> Diagnostic {
+        let label = match e {
+            ScanError::InvalidCharacter(_) => "not allowed in mini-lisp source",
+            ScanError::InvalidToken(_) => "not a valid constant, identifier or operator",
+            ScanError::UnterminatedComment(_) => "`#|` is never closed by `|#`",
+            ScanError::InvalidEscape(_) => "unknown or malformed escape",
+            ScanError::UnterminatedString(_) => "missing closing `\"`",
+        };
+        Diagnostic::new(e.message(), Some(e.index()), Some(label))
+    }
+}
+
+
+impl From<&ParseError> for Diagnostic {
+    fn from(e: &ParseError) -> Diagnostic {
+        let label = match e {
+            ParseError::UnexpectedToken(_) | ParseError::UnexpectedEndOfInput => Some("expected an expression"),
+            ParseError::UnclosedList(_) => Some("unclosed delimiter"),
+            ParseError::MisplacedDot(_) => Some("`.` must follow a datum in a list and precede exactly one datum"),
+            ParseError::UnknownScanError => None,
+        };
+        Diagnostic::new(e.message(), e.index(), label)
+    }
+}
+
+
+impl From<&EvalError> for Diagnostic {
+    fn from(e: &EvalError) -> Diagnostic {
+        let label = match e {
+            EvalError::UnboundVariable(..) => Some("not defined in this scope"),
+            EvalError::UninitializedVariable(..) => Some("not yet initialized"),
+            EvalError::NotProcedure(..) => Some("called here"),
+            _ => None,
+        };
+        Diagnostic::new(e.message(), Some(e.index()), label)
+    }
+}
+
+
+impl From<&Error> for Diagnostic {
+    fn from(e: &Error) -> Diagnostic {
+        match e {
+            Error::Scan(e) => Diagnostic::from(e),
+            Error::Parse(e) => Diagnostic::from(e),
+            Error::Eval(e) => Diagnostic::from(e),
+        }
+    }
+}
+
+
+// 输入末尾的位置
+pub(crate) fn end_of_input(source: &str) -> (usize, usize) {
+    match source.lines().enumerate().last() {
+        Some((row, line)) => (row, line.chars().count()),
+        None => (0, 0),
+    }
+}
+
+
+// 从column起的词法单元的字符宽度，用于确定下划线长度
+// 同一行内闭合的列表与字符串整体加下划线，其余情形划至下一个界定符
+pub(crate) fn token_width(line: &str, column: usize) -> usize {
+    let chars: Vec<char> = line.chars().skip(column).collect();
+    // 向量`#( ... )`与列表同样延伸至匹配的`)`
+    if chars.starts_with(&['#', '(']) {
+        return 1 + token_width(line, column + 1);
+    }
+
+    let width = match chars.first() {
+        Some('(') => {
+            let mut depth = 0;
+            let mut in_str = false;
+            let mut pre_escape = false;
+            let mut width = 1;
+            for (i, &ch) in chars.iter().enumerate() {
+                if in_str {
+                    in_str = pre_escape || ch != '"';
+                    pre_escape = !pre_escape && ch == '\\';
+                    continue;
+                }
+                match ch {
+                    '"' => in_str = true,
+                    ';' => break,
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => (),
+                }
+                if depth == 0 {
+                    width = i + 1;
+                    break;
+                }
+            }
+            width
+        },
+
+        Some('"') => {
+            let mut pre_escape = false;
+            let mut width = chars.len();
+            for (i, &ch) in chars.iter().enumerate().skip(1) {
+                if ch == '"' && !pre_escape {
+                    width = i + 1;
+                    break;
+                }
+                pre_escape = !pre_escape && ch == '\\';
+            }
+            width
+        },
+
+        Some(_) => chars.iter()
+            .take_while(|ch| !ch.is_whitespace() && !"()\";".contains(**ch))
+            .count(),
+
+        None => 1,
+    };
+
+    width.max(1)
+}
@@ -0,0 +1,54 @@
+use super::*;
+
+
+#[test]
+fn underlines_the_whole_list_at_the_error() {
+    let source = "(define x 1)\n(if (car x) x)\n";
+    let diagnostic = Diagnostic::from(&EvalError::TypeMismatch(String::from("`car` expected a pair, got 1"), (1, 4)));
+    let expected = concat!(
+        "error: type mismatch: `car` expected a pair, got 1\n",
+        " --> main.lisp:2:5\n",
+        "  |\n",
+        "2 | (if (car x) x)\n",
+        "  |     ^^^^^^^\n",
+    );
+    assert_eq!(diagnostic.render(source, "main.lisp", false), expected);
+}
+
+
+#[test]
+fn labels_follow_the_caret() {
+    let diagnostic = Diagnostic::from(&EvalError::UnboundVariable(String::from("foo"), (0, 3)));
+    let rendered = diagnostic.render("(+ foo 1)", "<repl>", false);
+    assert!(rendered.ends_with("  |    ^^^ not defined in this scope\n"));
+    assert!(rendered.starts_with("error: unbound variable `foo`\n"));
+}
+
+
+#[test]
+fn end_of_input_points_past_the_last_line() {
+    let diagnostic = Diagnostic::from(&ParseError::UnexpectedEndOfInput);
+    let rendered = diagnostic.render("(a\n  (b", "f.lisp", false);
+    assert!(rendered.contains(" --> f.lisp:2:5\n"));
+    assert!(rendered.ends_with("2 |   (b\n  |     ^ expected an expression\n"));
+}
+
+
+#[test]
+fn columns_count_characters_and_keep_tabs() {
+    let diagnostic = Diagnostic::from(&ScanError::InvalidCharacter((0, 4)));
+    let rendered = diagnostic.render("\"é\"\t{", "f.lisp", false);
+    assert!(rendered.ends_with("  |    \t^ not allowed in mini-lisp source\n"));
+}
+
+
+#[test]
+fn wide_gutters_and_color() {
+    let source = "\n".repeat(9) + "(f 1)";
+    let diagnostic = Diagnostic::from(&EvalError::DivisionByZero((9, 0)));
+    let plain = diagnostic.render(&source, "f.lisp", false);
+    assert!(plain.contains("  --> f.lisp:10:1\n   |\n10 | (f 1)\n   | ^^^^^\n"));
+    let colored = diagnostic.render(&source, "f.lisp", true);
+    assert!(colored.starts_with("\x1b[1;31merror\x1b[0m"));
+    assert_ne!(plain, colored);
+}
@@ -0,0 +1,445 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::Rc};
+
+use crate::{Datum, DatumKind, EvalError, ValueType, number::Number};
+use utils::{
+    eval_and, eval_begin, eval_case, eval_cond, eval_define, eval_if, eval_lambda, eval_let, eval_let_star, eval_letrec, eval_or,
+    eval_quasiquote, eval_quote, eval_set, eval_when, quasiquote,
+};
+mod builtins;
+mod print;
+mod utils;
+#[cfg(test)]
+mod tests;
+
+
+// 运行时的值
+#[derive(Clone)]
+pub enum Value {
+    Number(Number),
+    Str(String),
+    Bool(bool),
+    Char(char),
+    Symbol(String),
+    // 空表`()`
+    Nil,
+    // 序对：car与cdr可经set-car!、set-cdr!原地修改，修改对全部引用可见
+    Pair(Rc<RefCell<(Value, Value)>>),
+    // 向量：定长、可原地修改
+    Vector(Rc<RefCell<Vec<Value>>>),
+    Procedure(Rc<Procedure>),
+    Builtin(Builtin),
+    // 无返回值的表达式（如define、display）的结果
+    Void,
+    // letrec中尚未初始化的变量所绑定的标记，引用时报错，不会作为表达式的值出现
+    Unassigned,
+}
+
+
+// 由lambda创建的闭包
+pub struct Procedure {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    // `(lambda args ...)`形式中收集全部实参的形参
+    pub rest: Option<String>,
+    pub body: Vec<Datum>,
+    pub env: Env,
+}
+
+
+// 内建过程：实参已求值，index为调用处的位置
+pub type BuiltinFn = fn(&[Value], (usize, usize)) -> Result<Value, EvalError>;
+
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+}
+
+
+// 词法环境：每个帧保存本层绑定及指向外层的指针
+#[derive(Clone)]
+pub struct Env(Rc<RefCell<Frame>>);
+
+
+struct Frame {
+    vars: HashMap<String, Value>,
+    parent: Option<Env>,
+}
+
+
+impl Env {
+    // 创建已绑定全部内建过程的全局环境
+    pub fn global() -> Env {
+        let env = Env::empty();
+        builtins::install(&env);
+        env
+    }
+
+    pub fn empty() -> Env {
+        Env(Rc::new(RefCell::new(Frame { vars: HashMap::new(), parent: None })))
+    }
+
+    pub fn extend(&self) -> Env {
+        Env(Rc::new(RefCell::new(Frame { vars: HashMap::new(), parent: Some(self.clone()) })))
+    }
+
+    pub fn define(&self, name: &str, value: Value) {
+        self.0.borrow_mut().vars.insert(String::from(name), value);
+    }
+
+    // 修改已有的绑定（自内向外查找）；名字未绑定时返回false
+    pub fn set(&self, name: &str, value: Value) -> bool {
+        let mut frame = self.0.borrow_mut();
+        if let Some(slot) = frame.vars.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &frame.parent {
+            Some(parent) => parent.set(name, value),
+            None => false,
+        }
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        let frame = self.0.borrow();
+        match frame.vars.get(name) {
+            Some(value) => Some(value.clone()),
+            None => frame.parent.as_ref().and_then(|parent| parent.lookup(name)),
+        }
+    }
+
+    // 本层帧中的全部绑定（按名称排序）
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = self.0.borrow().vars.iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+}
+
+
+// 依次求值各顶层表达式，返回最后一个表达式的值
+pub fn eval_program(program: &[Datum], env: &Env) -> Result<Value, EvalError> {
+    let mut result = Value::Void;
+    for datum in program {
+        result = eval(datum, env)?;
+    }
+    Ok(result)
+}
+
+
+pub fn eval(datum: &Datum, env: &Env) -> Result<Value, EvalError> {
+    trampoline(eval_tail(datum, env)?)
+}
+
+
+// 以已求值的实参调用过程，index为调用处的位置
+pub fn apply(procedure: &Value, args: &[Value], index: (usize, usize)) -> Result<Value, EvalError> {
+    trampoline(apply_tail(procedure, args, index)?)
+}
+
+
+// 尾位置上的求值结果：值，或尚待执行的尾调用（过程、实参及调用处的位置）
+// 尾调用交回trampoline()执行，故任意深度的尾递归只占用常数的栈空间
+enum Tail {
+    Value(Value),
+    Call(Value, Vec<Value>, (usize, usize)),
+}
+
+
+fn trampoline(tail: Tail) -> Result<Value, EvalError> {
+    let mut tail = tail;
+    loop {
+        match tail {
+            Tail::Value(value) => return Ok(value),
+            Tail::Call(procedure, args, index) => tail = apply_tail(&procedure, &args, index)?,
+        }
+    }
+}
+
+
+// 求值位于尾位置的表达式：过程调用不立即执行，而是作为Tail::Call返回
+fn eval_tail(datum: &Datum, env: &Env) -> Result<Tail, EvalError> {
+    match &datum.kind {
+        DatumKind::Const(value) => Ok(Tail::Value(Value::from(value.clone()))),
+
+        DatumKind::Symbol(name) => match env.lookup(name) {
+            Some(Value::Unassigned) => Err(EvalError::UninitializedVariable(name.clone(), datum.index)),
+            Some(value) => Ok(Tail::Value(value)),
+            None => Err(EvalError::UnboundVariable(name.clone(), datum.index)),
+        },
+
+        DatumKind::Quote(quoted) => Ok(Tail::Value(Value::from(quoted.as_ref()))),
+
+        // 向量自求值
+        DatumKind::Vector(_) => Ok(Tail::Value(Value::from(datum))),
+
+        DatumKind::Quasiquote(template) => quasiquote(template, 1, env).map(Tail::Value),
+
+        DatumKind::Unquote(_) | DatumKind::UnquoteSplicing(_) => {
+            Err(EvalError::BadSyntax(String::from("unquote outside of quasiquote"), datum.index))
+        },
+
+        DatumKind::DottedList(..) => Err(EvalError::BadSyntax(String::from("improper list cannot be evaluated"), datum.index)),
+
+        DatumKind::List(items) => {
+            let Some(head) = items.first() else {
+                return Err(EvalError::BadSyntax(String::from("empty combination `()`"), datum.index));
+            };
+
+            // 特殊形式
+            if let DatumKind::Symbol(name) = &head.kind {
+                match name.as_str() {
+                    "define" => return eval_define(datum, items, env).map(Tail::Value),
+                    "set!" => return eval_set(datum, items, env).map(Tail::Value),
+                    "if" => return eval_if(datum, items, env),
+                    "lambda" => return eval_lambda(datum, items, env, None).map(Tail::Value),
+                    "quote" => return eval_quote(datum, items).map(Tail::Value),
+                    "quasiquote" => return eval_quasiquote(datum, items, env).map(Tail::Value),
+                    "let" => return eval_let(datum, items, env),
+                    "let*" => return eval_let_star(datum, items, env),
+                    "letrec" | "letrec*" => return eval_letrec(datum, items, env),
+                    "cond" => return eval_cond(datum, items, env),
+                    "case" => return eval_case(datum, items, env),
+                    "and" => return eval_and(items, env),
+                    "or" => return eval_or(items, env),
+                    "when" => return eval_when(datum, items, env, true),
+                    "unless" => return eval_when(datum, items, env, false),
+                    "begin" => return eval_begin(datum, items, env),
+                    "unquote" | "unquote-splicing" => {
+                        return Err(EvalError::BadSyntax(String::from("unquote outside of quasiquote"), datum.index));
+                    },
+                    _ => (),
+                }
+            }
+
+            // 过程调用
+            let procedure = eval(head, env)?;
+            let mut args = Vec::with_capacity(items.len() - 1);
+            for item in &items[1..] {
+                args.push(eval(item, env)?);
+            }
+            // 内建过程不会再求值表达式，直接调用
+            match procedure {
+                Value::Builtin(builtin) => (builtin.func)(&args, datum.index).map(Tail::Value),
+                procedure => Ok(Tail::Call(procedure, args, datum.index)),
+            }
+        },
+    }
+}
+
+
+// 调用过程：过程体的最后一个表达式位于尾位置
+fn apply_tail(procedure: &Value, args: &[Value], index: (usize, usize)) -> Result<Tail, EvalError> {
+    match procedure {
+        Value::Builtin(builtin) => (builtin.func)(args, index).map(Tail::Value),
+
+        Value::Procedure(closure) => {
+            let arity_ok = match closure.rest {
+                Some(_) => args.len() >= closure.params.len(),
+                None => args.len() == closure.params.len(),
+            };
+            if !arity_ok {
+                return Err(EvalError::ArityMismatch(format!(
+                    "`{}` expects {}{} argument(s), got {}",
+                    closure.name.as_deref().unwrap_or("lambda"),
+                    if closure.rest.is_some() { "at least " } else { "" },
+                    closure.params.len(),
+                    args.len(),
+                ), index));
+            }
+
+            let env = closure.env.extend();
+            for (param, arg) in closure.params.iter().zip(args) {
+                env.define(param, arg.clone());
+            }
+            if let Some(rest) = &closure.rest {
+                env.define(rest, Value::list(args[closure.params.len()..].to_vec()));
+            }
+
+            eval_body(&closure.body, &env)
+        },
+
+        other => Err(EvalError::NotProcedure(other.to_string(), index)),
+    }
+}
+
+
+// 依次求值表达式序列，最后一个表达式位于尾位置；序列为空时结果为Void
+fn eval_body(body: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    let Some((last, init)) = body.split_last() else {
+        return Ok(Tail::Value(Value::Void));
+    };
+    for datum in init {
+        eval(datum, env)?;
+    }
+    eval_tail(last, env)
+}
+
+
+impl Value {
+    // 由元素序列构造真列表
+    pub fn list(items: Vec<Value>) -> Value {
+        items.into_iter().rev().fold(Value::Nil, |tail, item| Value::cons(item, tail))
+    }
+
+    pub fn cons(car: Value, cdr: Value) -> Value {
+        Value::Pair(Rc::new(RefCell::new((car, cdr))))
+    }
+
+    pub fn vector(items: Vec<Value>) -> Value {
+        Value::Vector(Rc::new(RefCell::new(items)))
+    }
+
+    // 真列表的各元素；不是真列表（含成环的列表）时返回None
+    pub fn list_items(&self) -> Option<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut value = self.clone();
+        // 每前进两步slow前进一步，二者相遇说明列表成环
+        let mut slow = self.clone();
+        loop {
+            let next = match &value {
+                Value::Nil => return Some(items),
+                Value::Pair(pair) => {
+                    let pair = pair.borrow();
+                    items.push(pair.0.clone());
+                    pair.1.clone()
+                },
+                _ => return None,
+            };
+            value = next;
+
+            if items.len() % 2 == 0 {
+                let next = match &slow {
+                    Value::Pair(pair) => pair.borrow().1.clone(),
+                    _ => Value::Nil,
+                };
+                slow = next;
+                if let (Value::Pair(a), Value::Pair(b)) = (&slow, &value) && Rc::ptr_eq(a, b) {
+                    return None;
+                }
+            }
+        }
+    }
+
+    // `eq?`及`case`所用的等价关系：同一对象或相等的原子值
+    pub fn is_eqv(&self, other: &Value) -> bool {
+        match (self, other) {
+            // 精确数按数值比较；非精确数按其二进制表示比较（区分0.0与-0.0，相同的NaN视为同一）
+            (Value::Number(a), Value::Number(b)) if a.is_exact() && b.is_exact() => a == b,
+            (Value::Number(Number::Float(a)), Value::Number(Number::Float(b))) => a.to_bits() == b.to_bits(),
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Pair(a), Value::Pair(b)) => Rc::ptr_eq(a, b),
+            (Value::Vector(a), Value::Vector(b)) => Rc::ptr_eq(a, b),
+            (Value::Procedure(a), Value::Procedure(b)) => Rc::ptr_eq(a, b),
+            (Value::Builtin(a), Value::Builtin(b)) => a.name == b.name,
+            _ => false,
+        }
+    }
+
+    // 仅`#f`为假
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Bool(false))
+    }
+
+    // display所用的输出形式：字符串不加引号，字符不加`#\`
+    pub fn to_display(&self) -> String {
+        match self {
+            Value::Str(s) => s.clone(),
+            Value::Char(ch) => ch.to_string(),
+            other => other.to_string(),
+        }
+    }
+}
+
+
+impl From<crate::ValueType> for Value {
+    fn from(value: crate::ValueType) -> Value {
+        match value {
+            crate::ValueType::Number(v) => Value::Number(v),
+            crate::ValueType::Str(v) => Value::Str(v),
+            crate::ValueType::Bool(v) => Value::Bool(v),
+            crate::ValueType::Char(v) => Value::Char(v),
+        }
+    }
+}
+
+
+// 被引用的语法树转换为数据
+impl Value {
+    // 把数据转换为语法树（宏展开的结果），各节点均位于index处；过程与成环的结构没有对应的语法树
+    pub fn to_datum(&self, index: (usize, usize)) -> Option<Datum> {
+        self.datum_at(index, &mut HashSet::new())
+    }
+
+    // path为当前正在转换的序对与向量，遇到其中之一说明结构成环
+    fn datum_at(&self, index: (usize, usize), path: &mut HashSet<*const ()>) -> Option<Datum> {
+        let kind = match self {
+            Value::Number(v) => DatumKind::Const(ValueType::Number(v.clone())),
+            Value::Str(v) => DatumKind::Const(ValueType::Str(v.clone())),
+            Value::Bool(v) => DatumKind::Const(ValueType::Bool(*v)),
+            Value::Char(v) => DatumKind::Const(ValueType::Char(*v)),
+            Value::Symbol(name) => DatumKind::Symbol(name.clone()),
+            Value::Nil => DatumKind::List(Vec::new()),
+            Value::Pair(_) => {
+                let mut spine = Vec::new();
+                let mut items = Vec::new();
+                let mut value = self.clone();
+                while let Value::Pair(pair) = &value {
+                    let id = Rc::as_ptr(pair) as *const ();
+                    if !path.insert(id) {
+                        return None;
+                    }
+                    spine.push(id);
+                    let (car, cdr) = pair.borrow().clone();
+                    items.push(car.datum_at(index, path)?);
+                    value = cdr;
+                }
+                let tail = value.datum_at(index, path)?;
+                for id in spine {
+                    path.remove(&id);
+                }
+                match tail.kind {
+                    DatumKind::List(rest) if rest.is_empty() => DatumKind::List(items),
+                    _ => DatumKind::DottedList(items, Box::new(tail)),
+                }
+            },
+            Value::Vector(items) => {
+                let id = Rc::as_ptr(items) as *const ();
+                if !path.insert(id) {
+                    return None;
+                }
+                let items = items.borrow().iter().map(|item| item.datum_at(index, path)).collect::<Option<Vec<Datum>>>()?;
+                path.remove(&id);
+                DatumKind::Vector(items)
+            },
+            Value::Procedure(_) | Value::Builtin(_) | Value::Void | Value::Unassigned => return None,
+        };
+        Some(Datum { kind, index })
+    }
+}
+
+
+impl From<&Datum> for Value {
+    fn from(datum: &Datum) -> Value {
+        match &datum.kind {
+            DatumKind::Const(value) => Value::from(value.clone()),
+            DatumKind::Symbol(name) => Value::Symbol(name.clone()),
+            DatumKind::List(items) => Value::list(items.iter().map(Value::from).collect()),
+            DatumKind::Vector(items) => Value::vector(items.iter().map(Value::from).collect()),
+            DatumKind::DottedList(items, tail) => items.iter().rev()
+                .fold(Value::from(tail.as_ref()), |cdr, item| Value::cons(Value::from(item), cdr)),
+            DatumKind::Quote(quoted) => Value::list(vec![Value::Symbol(String::from("quote")), Value::from(quoted.as_ref())]),
+            DatumKind::Quasiquote(quoted) => Value::list(vec![Value::Symbol(String::from("quasiquote")), Value::from(quoted.as_ref())]),
+            DatumKind::Unquote(quoted) => Value::list(vec![Value::Symbol(String::from("unquote")), Value::from(quoted.as_ref())]),
+            DatumKind::UnquoteSplicing(quoted) => {
+                Value::list(vec![Value::Symbol(String::from("unquote-splicing")), Value::from(quoted.as_ref())])
+            },
+        }
+    }
+}
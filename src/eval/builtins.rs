@@ -0,0 +1,733 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use crate::{EvalError, number::Number};
+use super::{Builtin, BuiltinFn, Env, Value, apply};
+
+
+type Index = (usize, usize);
+
+
+// `make-vector`所能创建的向量长度上限，以免过大的长度耗尽内存
+const MAX_VECTOR_LENGTH: usize = 1 << 24;
+
+
+// 在全局环境中绑定全部内建过程
+pub fn install(env: &Env) {
+    let builtins: [(&'static str, BuiltinFn); 67] = [
+        ("+", add),
+        ("-", sub),
+        ("*", mul),
+        ("/", div),
+        ("<", less_than),
+        (">", greater_than),
+        ("<=", less_eq),
+        (">=", greater_eq),
+        ("=", num_eq),
+        ("list", list),
+        ("cons", cons),
+        ("car", car),
+        ("cdr", cdr),
+        ("set-car!", set_car),
+        ("set-cdr!", set_cdr),
+        ("null?", is_null),
+        ("pair?", is_pair),
+        ("not", not),
+        ("eq?", is_eq),
+        ("display", display),
+        ("newline", newline),
+        ("number?", is_number),
+        ("integer?", is_integer),
+        ("rational?", is_rational),
+        ("exact?", is_exact),
+        ("inexact?", is_inexact),
+        ("exact->inexact", exact_to_inexact),
+        ("inexact->exact", inexact_to_exact),
+        ("numerator", numerator),
+        ("denominator", denominator),
+        ("quotient", quotient),
+        ("remainder", remainder),
+        ("modulo", modulo),
+        ("char?", is_char),
+        ("char->integer", char_to_integer),
+        ("integer->char", integer_to_char),
+        ("char-upcase", char_upcase),
+        ("char-downcase", char_downcase),
+        ("char-alphabetic?", is_char_alphabetic),
+        ("char-numeric?", is_char_numeric),
+        ("char-whitespace?", is_char_whitespace),
+        ("char-upper-case?", is_char_upper_case),
+        ("char-lower-case?", is_char_lower_case),
+        ("char=?", char_eq),
+        ("char<?", char_less_than),
+        ("char>?", char_greater_than),
+        ("char<=?", char_less_eq),
+        ("char>=?", char_greater_eq),
+        ("string?", is_string),
+        ("string-length", string_length),
+        ("string-ref", string_ref),
+        ("substring", substring),
+        ("string-append", string_append),
+        ("string", string),
+        ("string->list", string_to_list),
+        ("list->string", list_to_string),
+        ("vector?", is_vector),
+        ("make-vector", make_vector),
+        ("vector", vector),
+        ("vector-length", vector_length),
+        ("vector-ref", vector_ref),
+        ("vector-set!", vector_set),
+        ("vector->list", vector_to_list),
+        ("list->vector", list_to_vector),
+        ("vector-map", vector_map),
+        ("vector-fill!", vector_fill),
+        ("gensym", gensym),
+    ];
+
+    for (name, func) in builtins {
+        env.define(name, Value::Builtin(Builtin { name, func }));
+    }
+}
+
+
+fn to_num(value: &Value, index: Index) -> Result<&Number, EvalError> {
+    match value {
+        Value::Number(v) => Ok(v),
+        other => Err(EvalError::TypeMismatch(format!("expected a number, got {}", other), index)),
+    }
+}
+
+
+fn expect_arity(name: &str, args: &[Value], expected: usize, index: Index) -> Result<(), EvalError> {
+    if args.len() != expected {
+        return Err(EvalError::ArityMismatch(format!("`{}` expects {} argument(s), got {}", name, expected, args.len()), index));
+    }
+    Ok(())
+}
+
+
+// 精确数运算的结果保持精确（必要时提升为任意精度整数或分数），含非精确数时结果为浮点数
+fn arith(args: &[Value], index: Index, init: Number, op: fn(&Number, &Number) -> Number) -> Result<Value, EvalError> {
+    let mut acc = init;
+    for arg in args {
+        acc = op(&acc, to_num(arg, index)?);
+    }
+    Ok(Value::Number(acc))
+}
+
+
+fn add(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    arith(args, index, Number::Int(0), |a, b| a + b)
+}
+
+
+fn mul(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    arith(args, index, Number::Int(1), |a, b| a * b)
+}
+
+
+// `(- x)`取相反数，`(- x y ...)`依次相减
+fn sub(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    match args {
+        [] => Err(EvalError::ArityMismatch(String::from("`-` expects at least 1 argument, got 0"), index)),
+        [only] => arith(std::slice::from_ref(only), index, Number::Int(0), |a, b| a - b),
+        [first, rest @ ..] => arith(rest, index, to_num(first, index)?.clone(), |a, b| a - b),
+    }
+}
+
+
+// 精确数相除得到精确的分数；精确数除以精确的0时报错
+fn div(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let (mut acc, rest) = match args {
+        [] => return Err(EvalError::ArityMismatch(String::from("`/` expects at least 1 argument, got 0"), index)),
+        [only] => (Number::Int(1), std::slice::from_ref(only)),
+        [first, rest @ ..] => (to_num(first, index)?.clone(), rest),
+    };
+
+    for arg in rest {
+        acc = acc.checked_div(to_num(arg, index)?).ok_or(EvalError::DivisionByZero(index))?;
+    }
+
+    Ok(Value::Number(acc))
+}
+
+
+// 比较运算：相邻实参两两满足关系时为真（与NaN比较时为假）
+fn compare(args: &[Value], index: Index, holds: fn(Ordering) -> bool) -> Result<Value, EvalError> {
+    let nums = args.iter().map(|arg| to_num(arg, index)).collect::<Result<Vec<&Number>, EvalError>>()?;
+    Ok(Value::Bool(nums.windows(2).all(|pair| pair[0].compare(pair[1]).is_some_and(holds))))
+}
+
+
+fn less_than(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    compare(args, index, Ordering::is_lt)
+}
+
+
+fn greater_than(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    compare(args, index, Ordering::is_gt)
+}
+
+
+fn less_eq(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    compare(args, index, Ordering::is_le)
+}
+
+
+fn greater_eq(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    compare(args, index, Ordering::is_ge)
+}
+
+
+fn num_eq(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    compare(args, index, Ordering::is_eq)
+}
+
+
+fn is_number(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("number?", args, 1, index)?;
+    Ok(Value::Bool(matches!(args[0], Value::Number(_))))
+}
+
+
+fn is_integer(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("integer?", args, 1, index)?;
+    Ok(Value::Bool(matches!(&args[0], Value::Number(v) if v.is_integer())))
+}
+
+
+// 有限的实数均为有理数
+fn is_rational(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("rational?", args, 1, index)?;
+    Ok(Value::Bool(matches!(&args[0], Value::Number(v) if v.to_exact().is_some())))
+}
+
+
+fn is_exact(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("exact?", args, 1, index)?;
+    Ok(Value::Bool(to_num(&args[0], index)?.is_exact()))
+}
+
+
+fn is_inexact(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("inexact?", args, 1, index)?;
+    Ok(Value::Bool(!to_num(&args[0], index)?.is_exact()))
+}
+
+
+fn exact_to_inexact(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("exact->inexact", args, 1, index)?;
+    Ok(Value::Number(to_num(&args[0], index)?.to_inexact()))
+}
+
+
+fn inexact_to_exact(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("inexact->exact", args, 1, index)?;
+    let num = to_num(&args[0], index)?;
+    match num.to_exact() {
+        Some(v) => Ok(Value::Number(v)),
+        None => Err(EvalError::TypeMismatch(format!("`inexact->exact` cannot convert {} to an exact number", num), index)),
+    }
+}
+
+
+fn numerator(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("numerator", args, 1, index)?;
+    let num = to_num(&args[0], index)?;
+    match num.numerator() {
+        Some(v) => Ok(Value::Number(v)),
+        None => Err(EvalError::TypeMismatch(format!("`numerator` expected a rational number, got {}", num), index)),
+    }
+}
+
+
+fn denominator(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("denominator", args, 1, index)?;
+    let num = to_num(&args[0], index)?;
+    match num.denominator() {
+        Some(v) => Ok(Value::Number(v)),
+        None => Err(EvalError::TypeMismatch(format!("`denominator` expected a rational number, got {}", num), index)),
+    }
+}
+
+
+// 整数除法：返回向零取整的商与余数
+fn integer_division(name: &str, args: &[Value], index: Index) -> Result<(Number, Number, Number), EvalError> {
+    expect_arity(name, args, 2, index)?;
+    let (a, b) = (to_num(&args[0], index)?, to_num(&args[1], index)?);
+    if !a.is_integer() || !b.is_integer() {
+        return Err(EvalError::TypeMismatch(format!("`{}` expected integers, got {} and {}", name, a, b), index));
+    }
+    match a.div_rem(b) {
+        Some((q, r)) => Ok((q, r, b.clone())),
+        None => Err(EvalError::DivisionByZero(index)),
+    }
+}
+
+
+fn quotient(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let (q, _, _) = integer_division("quotient", args, index)?;
+    Ok(Value::Number(q))
+}
+
+
+// 余数与被除数同号
+fn remainder(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let (_, r, _) = integer_division("remainder", args, index)?;
+    Ok(Value::Number(r))
+}
+
+
+// 模与除数同号
+fn modulo(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let (_, r, b) = integer_division("modulo", args, index)?;
+    let zero = Number::Int(0);
+    let differ = r.compare(&zero).is_some_and(Ordering::is_lt) != b.compare(&zero).is_some_and(Ordering::is_lt);
+    if !r.is_zero() && differ {
+        Ok(Value::Number(&r + &b))
+    } else {
+        Ok(Value::Number(r))
+    }
+}
+
+
+fn list(args: &[Value], _index: Index) -> Result<Value, EvalError> {
+    Ok(Value::list(args.to_vec()))
+}
+
+
+fn cons(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("cons", args, 2, index)?;
+    Ok(Value::cons(args[0].clone(), args[1].clone()))
+}
+
+
+fn car(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("car", args, 1, index)?;
+    match &args[0] {
+        Value::Pair(pair) => Ok(pair.borrow().0.clone()),
+        other => Err(EvalError::TypeMismatch(format!("`car` expected a pair, got {}", other), index)),
+    }
+}
+
+
+fn cdr(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("cdr", args, 1, index)?;
+    match &args[0] {
+        Value::Pair(pair) => Ok(pair.borrow().1.clone()),
+        other => Err(EvalError::TypeMismatch(format!("`cdr` expected a pair, got {}", other), index)),
+    }
+}
+
+
+// 原地修改序对，对该序对的全部引用可见
+fn set_car(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("set-car!", args, 2, index)?;
+    match &args[0] {
+        Value::Pair(pair) => {
+            pair.borrow_mut().0 = args[1].clone();
+            Ok(Value::Void)
+        },
+        other => Err(EvalError::TypeMismatch(format!("`set-car!` expected a pair, got {}", other), index)),
+    }
+}
+
+
+fn set_cdr(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("set-cdr!", args, 2, index)?;
+    match &args[0] {
+        Value::Pair(pair) => {
+            pair.borrow_mut().1 = args[1].clone();
+            Ok(Value::Void)
+        },
+        other => Err(EvalError::TypeMismatch(format!("`set-cdr!` expected a pair, got {}", other), index)),
+    }
+}
+
+
+fn is_null(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("null?", args, 1, index)?;
+    Ok(Value::Bool(matches!(args[0], Value::Nil)))
+}
+
+
+fn is_pair(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("pair?", args, 1, index)?;
+    Ok(Value::Bool(matches!(args[0], Value::Pair(_))))
+}
+
+
+fn not(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("not", args, 1, index)?;
+    Ok(Value::Bool(!args[0].is_truthy()))
+}
+
+
+fn is_eq(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("eq?", args, 2, index)?;
+    Ok(Value::Bool(args[0].is_eqv(&args[1])))
+}
+
+
+fn display(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("display", args, 1, index)?;
+    print!("{}", args[0].to_display());
+    Ok(Value::Void)
+}
+
+
+fn newline(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("newline", args, 0, index)?;
+    println!();
+    Ok(Value::Void)
+}
+
+
+fn to_char(name: &str, value: &Value, index: Index) -> Result<char, EvalError> {
+    match value {
+        Value::Char(ch) => Ok(*ch),
+        other => Err(EvalError::TypeMismatch(format!("`{}` expected a character, got {}", name, other), index)),
+    }
+}
+
+
+fn to_str<'a>(name: &str, value: &'a Value, index: Index) -> Result<&'a str, EvalError> {
+    match value {
+        Value::Str(s) => Ok(s),
+        other => Err(EvalError::TypeMismatch(format!("`{}` expected a string, got {}", name, other), index)),
+    }
+}
+
+
+// 下标须为非负的精确整数
+fn to_position(name: &str, value: &Value, index: Index) -> Result<usize, EvalError> {
+    match value {
+        Value::Number(Number::Int(v)) if *v >= 0 => Ok(*v as usize),
+        other => Err(EvalError::TypeMismatch(format!("`{}` expected a non-negative exact integer, got {}", name, other), index)),
+    }
+}
+
+
+fn is_char(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("char?", args, 1, index)?;
+    Ok(Value::Bool(matches!(args[0], Value::Char(_))))
+}
+
+
+fn char_to_integer(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("char->integer", args, 1, index)?;
+    let ch = to_char("char->integer", &args[0], index)?;
+    Ok(Value::Number(Number::Int(ch as isize)))
+}
+
+
+fn integer_to_char(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("integer->char", args, 1, index)?;
+    match &args[0] {
+        Value::Number(Number::Int(v)) if let Some(ch) = u32::try_from(*v).ok().and_then(char::from_u32) => Ok(Value::Char(ch)),
+        other => Err(EvalError::TypeMismatch(format!("`integer->char` expected a Unicode scalar value, got {}", other), index)),
+    }
+}
+
+
+// 大小写转换仅在结果为单个字符时进行
+fn convert_case(name: &str, args: &[Value], index: Index, convert: fn(&char) -> String) -> Result<Value, EvalError> {
+    expect_arity(name, args, 1, index)?;
+    let ch = to_char(name, &args[0], index)?;
+    let converted = convert(&ch);
+    let mut converted = converted.chars();
+    match (converted.next(), converted.next()) {
+        (Some(single), None) => Ok(Value::Char(single)),
+        _ => Ok(Value::Char(ch)),
+    }
+}
+
+
+fn char_upcase(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    convert_case("char-upcase", args, index, |ch| ch.to_uppercase().collect())
+}
+
+
+fn char_downcase(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    convert_case("char-downcase", args, index, |ch| ch.to_lowercase().collect())
+}
+
+
+fn char_predicate(name: &str, args: &[Value], index: Index, holds: fn(char) -> bool) -> Result<Value, EvalError> {
+    expect_arity(name, args, 1, index)?;
+    Ok(Value::Bool(holds(to_char(name, &args[0], index)?)))
+}
+
+
+fn is_char_alphabetic(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_predicate("char-alphabetic?", args, index, char::is_alphabetic)
+}
+
+
+fn is_char_numeric(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_predicate("char-numeric?", args, index, char::is_numeric)
+}
+
+
+fn is_char_whitespace(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_predicate("char-whitespace?", args, index, char::is_whitespace)
+}
+
+
+fn is_char_upper_case(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_predicate("char-upper-case?", args, index, char::is_uppercase)
+}
+
+
+fn is_char_lower_case(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_predicate("char-lower-case?", args, index, char::is_lowercase)
+}
+
+
+// 字符按码点比较：相邻实参两两满足关系时为真
+fn char_compare(name: &str, args: &[Value], index: Index, holds: fn(Ordering) -> bool) -> Result<Value, EvalError> {
+    let chars = args.iter().map(|arg| to_char(name, arg, index)).collect::<Result<Vec<char>, EvalError>>()?;
+    Ok(Value::Bool(chars.windows(2).all(|pair| holds(pair[0].cmp(&pair[1])))))
+}
+
+
+fn char_eq(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_compare("char=?", args, index, Ordering::is_eq)
+}
+
+
+fn char_less_than(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_compare("char<?", args, index, Ordering::is_lt)
+}
+
+
+fn char_greater_than(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_compare("char>?", args, index, Ordering::is_gt)
+}
+
+
+fn char_less_eq(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_compare("char<=?", args, index, Ordering::is_le)
+}
+
+
+fn char_greater_eq(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    char_compare("char>=?", args, index, Ordering::is_ge)
+}
+
+
+fn is_string(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("string?", args, 1, index)?;
+    Ok(Value::Bool(matches!(args[0], Value::Str(_))))
+}
+
+
+// 字符串的长度与下标均按字符计数
+fn string_length(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("string-length", args, 1, index)?;
+    let s = to_str("string-length", &args[0], index)?;
+    Ok(Value::Number(Number::Int(s.chars().count() as isize)))
+}
+
+
+fn string_ref(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("string-ref", args, 2, index)?;
+    let s = to_str("string-ref", &args[0], index)?;
+    let k = to_position("string-ref", &args[1], index)?;
+    match s.chars().nth(k) {
+        Some(ch) => Ok(Value::Char(ch)),
+        None => Err(EvalError::IndexOutOfRange(format!("index {} for a string of length {}", k, s.chars().count()), index)),
+    }
+}
+
+
+// `(substring s start [end])`：下标区间[start, end)
+fn substring(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    if !(2..=3).contains(&args.len()) {
+        return Err(EvalError::ArityMismatch(format!("`substring` expects 2 or 3 arguments, got {}", args.len()), index));
+    }
+    let s = to_str("substring", &args[0], index)?;
+    let len = s.chars().count();
+    let start = to_position("substring", &args[1], index)?;
+    let end = match args.get(2) {
+        Some(end) => to_position("substring", end, index)?,
+        None => len,
+    };
+    if start > end || end > len {
+        return Err(EvalError::IndexOutOfRange(format!("range {}..{} for a string of length {}", start, end, len), index));
+    }
+    Ok(Value::Str(s.chars().skip(start).take(end - start).collect()))
+}
+
+
+fn string_append(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let parts = args.iter().map(|arg| to_str("string-append", arg, index)).collect::<Result<Vec<&str>, EvalError>>()?;
+    Ok(Value::Str(parts.concat()))
+}
+
+
+// 由若干字符构造字符串
+fn string(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let chars = args.iter().map(|arg| to_char("string", arg, index)).collect::<Result<String, EvalError>>()?;
+    Ok(Value::Str(chars))
+}
+
+
+fn string_to_list(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("string->list", args, 1, index)?;
+    let s = to_str("string->list", &args[0], index)?;
+    Ok(Value::list(s.chars().map(Value::Char).collect()))
+}
+
+
+fn list_to_string(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("list->string", args, 1, index)?;
+    let Some(items) = args[0].list_items() else {
+        return Err(EvalError::TypeMismatch(format!("`list->string` expected a list, got {}", args[0]), index));
+    };
+    string(&items, index)
+}
+
+
+fn to_vector<'a>(name: &str, value: &'a Value, index: Index) -> Result<&'a Rc<RefCell<Vec<Value>>>, EvalError> {
+    match value {
+        Value::Vector(items) => Ok(items),
+        other => Err(EvalError::TypeMismatch(format!("`{}` expected a vector, got {}", name, other), index)),
+    }
+}
+
+
+// 校验向量下标k < len
+fn check_bounds(k: usize, len: usize, index: Index) -> Result<usize, EvalError> {
+    if k < len {
+        Ok(k)
+    } else {
+        Err(EvalError::IndexOutOfRange(format!("index {} for a vector of length {}", k, len), index))
+    }
+}
+
+
+fn is_vector(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("vector?", args, 1, index)?;
+    Ok(Value::Bool(matches!(args[0], Value::Vector(_))))
+}
+
+
+// `(make-vector k [fill])`：未指定fill时各元素为0
+fn make_vector(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    if !(1..=2).contains(&args.len()) {
+        return Err(EvalError::ArityMismatch(format!("`make-vector` expects 1 or 2 arguments, got {}", args.len()), index));
+    }
+    let k = to_position("make-vector", &args[0], index)?;
+    let fill = args.get(1).cloned().unwrap_or(Value::Number(Number::Int(0)));
+    let mut items = Vec::new();
+    if k > MAX_VECTOR_LENGTH || items.try_reserve_exact(k).is_err() {
+        return Err(EvalError::IndexOutOfRange(format!("`make-vector` cannot allocate {} elements (at most {})", k, MAX_VECTOR_LENGTH), index));
+    }
+    items.resize(k, fill);
+    Ok(Value::vector(items))
+}
+
+
+fn vector(args: &[Value], _index: Index) -> Result<Value, EvalError> {
+    Ok(Value::vector(args.to_vec()))
+}
+
+
+fn vector_length(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("vector-length", args, 1, index)?;
+    let items = to_vector("vector-length", &args[0], index)?;
+    Ok(Value::Number(Number::Int(items.borrow().len() as isize)))
+}
+
+
+fn vector_ref(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("vector-ref", args, 2, index)?;
+    let items = to_vector("vector-ref", &args[0], index)?.borrow();
+    let k = check_bounds(to_position("vector-ref", &args[1], index)?, items.len(), index)?;
+    Ok(items[k].clone())
+}
+
+
+fn vector_set(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("vector-set!", args, 3, index)?;
+    let mut items = to_vector("vector-set!", &args[0], index)?.borrow_mut();
+    let k = check_bounds(to_position("vector-set!", &args[1], index)?, items.len(), index)?;
+    items[k] = args[2].clone();
+    Ok(Value::Void)
+}
+
+
+fn vector_to_list(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("vector->list", args, 1, index)?;
+    let items = to_vector("vector->list", &args[0], index)?;
+    Ok(Value::list(items.borrow().clone()))
+}
+
+
+fn list_to_vector(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    expect_arity("list->vector", args, 1, index)?;
+    match args[0].list_items() {
+        Some(items) => Ok(Value::vector(items)),
+        None => Err(EvalError::TypeMismatch(format!("`list->vector` expected a list, got {}", args[0]), index)),
+    }
+}
+
+
+// `(vector-map proc v1 v2 ...)`：结果的长度为最短向量的长度
+fn vector_map(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let [procedure, vectors @ ..] = args else {
+        return Err(EvalError::ArityMismatch(String::from("`vector-map` expects at least 2 arguments, got 0"), index));
+    };
+    if vectors.is_empty() {
+        return Err(EvalError::ArityMismatch(String::from("`vector-map` expects at least 2 arguments, got 1"), index));
+    }
+
+    // 先复制各向量，过程可能修改它们
+    let vectors = vectors.iter()
+        .map(|arg| to_vector("vector-map", arg, index).map(|items| items.borrow().clone()))
+        .collect::<Result<Vec<Vec<Value>>, EvalError>>()?;
+    let len = vectors.iter().map(Vec::len).min().unwrap_or(0);
+
+    let mut results = Vec::with_capacity(len);
+    for k in 0..len {
+        let call_args: Vec<Value> = vectors.iter().map(|items| items[k].clone()).collect();
+        results.push(apply(procedure, &call_args, index)?);
+    }
+    Ok(Value::vector(results))
+}
+
+
+// `(vector-fill! v fill [start [end]])`：填充下标区间[start, end)
+fn vector_fill(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    if !(2..=4).contains(&args.len()) {
+        return Err(EvalError::ArityMismatch(format!("`vector-fill!` expects 2 to 4 arguments, got {}", args.len()), index));
+    }
+    let mut items = to_vector("vector-fill!", &args[0], index)?.borrow_mut();
+    let len = items.len();
+    let start = match args.get(2) {
+        Some(start) => to_position("vector-fill!", start, index)?,
+        None => 0,
+    };
+    let end = match args.get(3) {
+        Some(end) => to_position("vector-fill!", end, index)?,
+        None => len,
+    };
+    if start > end || end > len {
+        return Err(EvalError::IndexOutOfRange(format!("range {}..{} for a vector of length {}", start, end, len), index));
+    }
+    items[start..end].fill(args[1].clone());
+    Ok(Value::Void)
+}
+
+
+// `(gensym [prefix])`：返回与任何已有符号都不同的新符号，前缀可为字符串或符号
+// 名字仿照非驻留符号的写法以`#:`开头，读入器无法产生这样的符号，因而不会与程序中的名字冲突
+fn gensym(args: &[Value], index: Index) -> Result<Value, EvalError> {
+    let prefix = match args {
+        [] => "g",
+        [Value::Str(prefix) | Value::Symbol(prefix)] => prefix.as_str(),
+        [other] => return Err(EvalError::TypeMismatch(format!("`gensym` expected a string or a symbol, got {}", other), index)),
+        _ => return Err(EvalError::ArityMismatch(format!("`gensym` expects 0 or 1 arguments, got {}", args.len()), index)),
+    };
+    Ok(Value::Symbol(format!("#:{}", crate::expand::fresh_name(prefix))))
+}
@@ -0,0 +1,146 @@
+use std::{collections::{HashMap, HashSet}, fmt, rc::Rc};
+
+use super::Value;
+
+
+// write所用的输出形式：字符串加引号
+// 位于环上的序对与向量以数据标签输出：首次出现时写作`#n=`，之后写作`#n#`
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer {
+            cyclic: cyclic_nodes(self),
+            labels: HashMap::new(),
+        };
+        printer.write(f, self)
+    }
+}
+
+
+struct Printer {
+    // 需要标签的结点
+    cyclic: HashSet<usize>,
+    // 已输出过的结点及其标签
+    labels: HashMap<usize, usize>,
+}
+
+
+impl Printer {
+    fn write(&mut self, f: &mut fmt::Formatter<'_>, value: &Value) -> fmt::Result {
+        if let Some(id) = node_id(value) && self.cyclic.contains(&id) {
+            if let Some(label) = self.labels.get(&id) {
+                return write!(f, "#{}#", label);
+            }
+            let label = self.labels.len();
+            self.labels.insert(id, label);
+            write!(f, "#{}=", label)?;
+        }
+
+        match value {
+            Value::Number(v) => write!(f, "{}", v),
+            Value::Str(v) => write!(f, "{:?}", v),
+            Value::Char(v) => write!(f, "{}", crate::ValueType::Char(*v)),
+            Value::Bool(true) => write!(f, "#t"),
+            Value::Bool(false) => write!(f, "#f"),
+            Value::Symbol(name) => write!(f, "{}", name),
+            Value::Nil => write!(f, "()"),
+            Value::Pair(pair) => {
+                let (car, mut tail) = pair.borrow().clone();
+                write!(f, "(")?;
+                self.write(f, &car)?;
+                loop {
+                    match tail {
+                        Value::Nil => break,
+                        // 带标签的尾部需以点对形式输出
+                        Value::Pair(next) if !node_id(&tail).is_some_and(|id| self.cyclic.contains(&id)) => {
+                            let (car, cdr) = next.borrow().clone();
+                            write!(f, " ")?;
+                            self.write(f, &car)?;
+                            tail = cdr;
+                        },
+                        other => {
+                            write!(f, " . ")?;
+                            self.write(f, &other)?;
+                            break;
+                        },
+                    }
+                }
+                write!(f, ")")
+            },
+            Value::Vector(items) => {
+                let items = items.borrow().clone();
+                write!(f, "#(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    self.write(f, item)?;
+                }
+                write!(f, ")")
+            },
+            Value::Procedure(closure) => match &closure.name {
+                Some(name) => write!(f, "#<procedure {}>", name),
+                None => write!(f, "#<procedure>"),
+            },
+            Value::Builtin(builtin) => write!(f, "#<builtin {}>", builtin.name),
+            Value::Void | Value::Unassigned => Ok(()),
+        }
+    }
+}
+
+
+// 可变的复合值以其地址标识
+fn node_id(value: &Value) -> Option<usize> {
+    match value {
+        Value::Pair(pair) => Some(Rc::as_ptr(pair) as *const () as usize),
+        Value::Vector(items) => Some(Rc::as_ptr(items) as *const () as usize),
+        _ => None,
+    }
+}
+
+
+// 深度优先遍历，找出经由自身可达（位于环上）的结点
+// 使用显式的栈，以免长列表耗尽调用栈
+fn cyclic_nodes(value: &Value) -> HashSet<usize> {
+    enum Visit {
+        Enter(Value),
+        Exit(usize),
+    }
+
+    let mut cyclic = HashSet::new();
+    let mut on_path = HashSet::new();
+    let mut done = HashSet::new();
+    let mut stack = vec![Visit::Enter(value.clone())];
+    while let Some(visit) = stack.pop() {
+        let value = match visit {
+            Visit::Exit(id) => {
+                on_path.remove(&id);
+                done.insert(id);
+                continue;
+            },
+            Visit::Enter(value) => value,
+        };
+        let Some(id) = node_id(&value) else {
+            continue;
+        };
+        if on_path.contains(&id) {
+            cyclic.insert(id);
+            continue;
+        }
+        if done.contains(&id) {
+            continue;
+        }
+
+        on_path.insert(id);
+        stack.push(Visit::Exit(id));
+        let children = match &value {
+            Value::Pair(pair) => {
+                let (car, cdr) = pair.borrow().clone();
+                vec![car, cdr]
+            },
+            Value::Vector(items) => items.borrow().clone(),
+            _ => Vec::new(),
+        };
+        stack.extend(children.into_iter().rev().map(Visit::Enter));
+    }
+    cyclic
+}
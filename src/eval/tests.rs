@@ -0,0 +1,314 @@
+use crate::{EvalError, parser::parse, scanner::scan};
+use super::{Env, Value, eval_program};
+
+
+fn try_run(source: &str) -> Result<Value, EvalError> {
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed: {}", source);
+    };
+    let Ok(program) = parse(&tokens, &token_table) else {
+        panic!("parse failed: {}", source);
+    };
+    eval_program(&program, &Env::global())
+}
+
+
+// 求值一段程序，返回最后一个表达式的值的输出形式
+fn run(source: &str) -> String {
+    match try_run(source) {
+        Ok(value) => value.to_string(),
+        Err(_) => panic!("eval failed: {}", source),
+    }
+}
+
+
+#[test]
+fn arithmetic_and_comparison() {
+    assert_eq!(run("(+ 1 2 3)"), "6");
+    assert_eq!(run("(- 10 4 1)"), "5");
+    assert_eq!(run("(* 2 2.5)"), "5.0");
+    assert_eq!(run("(/ 7 2)"), "7/2");
+    assert_eq!(run("(/ 8 2)"), "4");
+    assert_eq!(run("(list (< 1 2 3) (>= 2 3) (= 4 4))"), "(#t #f #t)");
+}
+
+
+#[test]
+fn closures_and_recursion() {
+    let source = "
+        (define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))
+        (define (adder k) (lambda (x) (+ x k)))
+        (define add5 (adder 5))
+        (list (fact 10) (add5 1))";
+    assert_eq!(run(source), "(3628800 6)");
+}
+
+
+#[test]
+fn quote_and_lists() {
+    assert_eq!(run("'(a \"b\" (c))"), "(a \"b\" (c))");
+    assert_eq!(run("(cons 1 (cdr '(x 2 3)))"), "(1 2 3)");
+    assert_eq!(run("(cons 1 2)"), "(1 . 2)");
+    assert_eq!(run("(list (null? '()) (pair? '()) (eq? 'a 'a) (not 0))"), "(#t #f #t #f)");
+}
+
+
+#[test]
+fn reports_errors_at_their_position() {
+    assert!(matches!(try_run("(+ 1 y)"), Err(EvalError::UnboundVariable(name, (0, 5))) if name == "y"));
+    assert!(matches!(try_run("((lambda (x) x))"), Err(EvalError::ArityMismatch(_, (0, 0)))));
+    assert!(matches!(try_run("(car 1)"), Err(EvalError::TypeMismatch(_, (0, 0)))));
+    assert!(matches!(try_run("(1 2)"), Err(EvalError::NotProcedure(_, (0, 0)))));
+    assert!(matches!(try_run("(/ 1 0)"), Err(EvalError::DivisionByZero((0, 0)))));
+}
+
+
+#[test]
+fn dotted_pairs_and_rest_parameters() {
+    assert_eq!(run("'(1 . 2)"), "(1 . 2)");
+    assert_eq!(run("'(1 2 . (3 4))"), "(1 2 3 4)");
+    assert_eq!(run("(cdr '(1 . 2))"), "2");
+    assert_eq!(run("(define (f a . rest) (list a rest)) (f 1 2 3)"), "(1 (2 3))");
+    assert_eq!(run("(define (f a . rest) rest) (f 1)"), "()");
+    assert_eq!(run("((lambda (a b . c) c) 1 2 3)"), "(3)");
+    assert!(matches!(try_run("(define (f a . rest) a) (f)"), Err(EvalError::ArityMismatch(..))));
+    assert!(matches!(try_run("(+ 1 . 2)"), Err(EvalError::BadSyntax(_, (0, 0)))));
+}
+
+
+#[test]
+fn quasiquote() {
+    assert_eq!(run("`(1 ,(+ 1 1) ,@(list 3 4) 5)"), "(1 2 3 4 5)");
+    assert_eq!(run("(define x 'y) `(a . ,x)"), "(a . y)");
+    assert_eq!(run("`(,@'() . tail)"), "tail");
+    assert_eq!(run("(quasiquote (1 (unquote (* 2 3))))"), "(1 6)");
+    // 嵌套的准引用只求值与最外层准引用同级的反引用
+    assert_eq!(run("`(a `(b ,(c ,(+ 1 2))))"), "(a (quasiquote (b (unquote (c 3)))))");
+    assert!(matches!(try_run(",x"), Err(EvalError::BadSyntax(_, (0, 0)))));
+    assert!(matches!(try_run("(unquote x)"), Err(EvalError::BadSyntax(_, (0, 0)))));
+    assert!(try_run("`(1 ,@2 3)").is_err());
+}
+
+
+#[test]
+fn exact_and_inexact_arithmetic() {
+    assert_eq!(run("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1))))) (fact 25)"), "15511210043330985984000000");
+    assert_eq!(run("(+ 1/3 1/6)"), "1/2");
+    assert_eq!(run("(* 2/3 3/2)"), "1");
+    assert_eq!(run("(+ 1/2 0.5)"), "1.0");
+    assert_eq!(run("(list (exact? 1/2) (inexact? 1/2) (exact->inexact 1/8) (inexact->exact 0.25))"), "(#t #f 0.125 1/4)");
+    assert_eq!(run("(list (numerator 6/4) (denominator 6/4) (integer? 4/2) (rational? 0.5))"), "(3 2 #t #t)");
+    assert_eq!(run("(list (quotient -7 2) (remainder -7 2) (modulo -7 2))"), "(-3 -1 1)");
+    assert_eq!(run("(< 1/3 0.34 1/2)"), "#t");
+    assert_eq!(run("(= 1/2 0.5)"), "#t");
+    assert_eq!(run("(> (/ 1.0 0) 1e300)"), "#t");
+    assert!(matches!(try_run("(/ 1 0)"), Err(EvalError::DivisionByZero((0, 0)))));
+    assert!(matches!(try_run("(modulo 1 0)"), Err(EvalError::DivisionByZero(_))));
+}
+
+
+#[test]
+fn chars_and_strings() {
+    assert_eq!(run("(list #\\a (char->integer #\\A) (integer->char 955) (char-upcase #\\ß) (char-downcase #\\Σ))"), "(#\\a 65 #\\λ #\\ß #\\σ)");
+    assert_eq!(run("(list (char-alphabetic? #\\z) (char-numeric? #\\7) (char-whitespace? #\\tab) (char<? #\\a #\\b #\\c))"), "(#t #t #t #t)");
+    assert_eq!(run("(list (string-length \"héllo\") (string-ref \"héllo\" 1) (substring \"héllo\" 1 3))"), "(5 #\\é \"él\")");
+    assert_eq!(run("(string->list \"ab\")"), "(#\\a #\\b)");
+    assert_eq!(run("(list->string (list #\\a #\\space #\\b))"), "\"a b\"");
+    assert_eq!(run("(string-append \"a\" (string #\\b #\\c))"), "\"abc\"");
+    assert_eq!(run("(eq? #\\a #\\a)"), "#t");
+    assert!(matches!(try_run("(string-ref \"abc\" 3)"), Err(EvalError::IndexOutOfRange(_, (0, 0)))));
+    assert!(matches!(try_run("(substring \"abc\" 2 1)"), Err(EvalError::IndexOutOfRange(..))));
+    assert!(matches!(try_run("(integer->char 55296)"), Err(EvalError::TypeMismatch(..))));
+    assert!(matches!(try_run("(char-upcase \"a\")"), Err(EvalError::TypeMismatch(..))));
+}
+
+
+#[test]
+fn vectors() {
+    assert_eq!(run("#(1 (+ 1 1) x)"), "#(1 (+ 1 1) x)");
+    assert_eq!(run("(define v (make-vector 3 0)) (vector-set! v 1 'x) v"), "#(0 x 0)");
+    assert_eq!(run("(list (vector-length (vector 1 2)) (vector-ref #(a b c) 2) (vector? #()) (vector? '()))"), "(2 c #t #f)");
+    assert_eq!(run("(list (vector->list #(1 2)) (list->vector '(1 2)))"), "((1 2) #(1 2))");
+    assert_eq!(run("(vector-map + #(1 2 3) #(10 20))"), "#(11 22)");
+    assert_eq!(run("(define v (vector 1 2 3 4)) (vector-fill! v 0 1 3) v"), "#(1 0 0 4)");
+    // 向量按引用共享
+    assert_eq!(run("(define a (vector 1)) (define b a) (vector-set! b 0 2) (list (vector-ref a 0) (eq? a b) (eq? a (vector 2)))"), "(2 #t #f)");
+}
+
+
+#[test]
+fn vector_bounds() {
+    let out_of_range = |source| matches!(try_run(source), Err(EvalError::IndexOutOfRange(_, (0, 0))));
+    assert!(out_of_range("(vector-ref #(1 2) 2)"));
+    assert!(out_of_range("(vector-ref #() 0)"));
+    assert!(out_of_range("(vector-set! (vector 1) 1 'x)"));
+    assert!(out_of_range("(vector-fill! (vector 1 2) 0 1 3)"));
+    assert!(out_of_range("(vector-fill! (vector 1 2) 0 2 1)"));
+    assert!(matches!(try_run("(vector-ref #(1) -1)"), Err(EvalError::TypeMismatch(..))));
+    assert!(matches!(try_run("(vector-ref #(1) 0.0)"), Err(EvalError::TypeMismatch(..))));
+    assert!(matches!(try_run("(vector-ref '(1) 0)"), Err(EvalError::TypeMismatch(..))));
+    assert_eq!(
+        try_run("(vector-ref #(1 2) 5)").err().map(|e| e.to_string()).as_deref(),
+        Some("index out of range: index 5 for a vector of length 2 at 1:1"),
+    );
+}
+
+
+// 过大的长度报错而不是耗尽内存
+#[test]
+fn make_vector_rejects_oversized_lengths() {
+    assert!(matches!(try_run("(make-vector 100000000)"), Err(EvalError::IndexOutOfRange(_, (0, 0)))));
+    assert!(matches!(try_run("(make-vector 123456789012345678901234567890 0)"), Err(EvalError::TypeMismatch(..))));
+}
+
+
+// 在栈很小的线程中求值：尾调用若未在常量栈空间内求值会导致栈溢出
+fn run_on_small_stack(source: &'static str) -> String {
+    std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(move || run(source))
+        .expect("failed to spawn thread")
+        .join()
+        .expect("evaluation overflowed the stack")
+}
+
+
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    let source = "
+        (define (count n acc) (if (= n 0) acc (count (- n 1) (+ acc 1))))
+        (define (even? n) (if (= n 0) #t (odd? (- n 1))))
+        (define (odd? n) (if (= n 0) #f (even? (- n 1))))
+        (list (count 100000 0) (even? 100000) ((lambda (n) (odd? n)) 100001))";
+    assert_eq!(run_on_small_stack(source), "(100000 #t #t)");
+}
+
+
+// 以下循环均为一百万层的尾调用，耗时较长，需以`--ignored`运行
+
+#[test]
+#[ignore]
+fn self_tail_call_million() {
+    let source = "
+        (define (count n acc) (if (= n 0) acc (count (- n 1) (+ acc 1))))
+        (count 1000000 0)";
+    assert_eq!(run_on_small_stack(source), "1000000");
+}
+
+
+#[test]
+#[ignore]
+fn mutual_tail_calls_million() {
+    let source = "
+        (define (even? n) (if (= n 0) #t (odd? (- n 1))))
+        (define (odd? n) (if (= n 0) #f (even? (- n 1))))
+        (list (even? 1000000) (odd? 1000000))";
+    assert_eq!(run_on_small_stack(source), "(#t #f)");
+}
+
+
+#[test]
+fn binding_forms() {
+    assert_eq!(run("(let ((x 1) (y 2)) (+ x y))"), "3");
+    assert_eq!(run("(define x 10) (let ((x 1) (y x)) y)"), "10");
+    assert_eq!(run("(let* ((x 1) (y (+ x 1))) (* x y))"), "2");
+    assert_eq!(run("(letrec ((even? (lambda (n) (if (= n 0) #t (odd? (- n 1))))) (odd? (lambda (n) (if (= n 0) #f (even? (- n 1)))))) (even? 10))"), "#t");
+    assert_eq!(run("(let loop ((i 0) (acc '())) (if (= i 3) acc (loop (+ i 1) (cons i acc))))"), "(2 1 0)");
+    assert_eq!(run("(let () (define y 5) (* y y))"), "25");
+    assert!(matches!(try_run("(let ((x)) x)"), Err(EvalError::BadSyntax(_, (0, 6)))));
+    assert!(matches!(try_run("(let ((1 2)) 1)"), Err(EvalError::BadSyntax(_, (0, 7)))));
+}
+
+
+#[test]
+fn conditional_forms() {
+    assert_eq!(run("(cond ((> 1 2) 'a) ((< 1 2) 'b) (else 'c))"), "b");
+    assert_eq!(run("(cond ((list 7 8) => car) (else 'none))"), "7");
+    assert_eq!(run("(cond (#f 1) (2))"), "2");
+    assert_eq!(run("(case (* 2 3) ((2 3 5 7) 'prime) ((1 4 6 8 9) 'composite) (else 'other))"), "composite");
+    assert_eq!(run("(case 'x ((a) 1) (else => (lambda (v) v)))"), "x");
+    assert_eq!(run("(case #\\a ((#\\a) 'char) (else 'no))"), "char");
+    assert_eq!(run("(list (and) (and 1 2) (and 1 #f 3) (or) (or #f 2) (or #f #f))"), "(#t 2 #f #f 2 #f)");
+    assert_eq!(run("(list (when (> 2 1) 'a 'b) (unless (< 2 1) 'c))"), "(b c)");
+    assert_eq!(run("(begin (define z 1) (+ z 1))"), "2");
+    assert!(matches!(try_run("(cond (else 1) (#t 2))"), Err(EvalError::BadSyntax(..))));
+}
+
+
+#[test]
+fn tail_calls_through_derived_forms() {
+    let source = "
+        (define (via-cond n) (cond ((= n 0) 'cond) (else (via-cond (- n 1)))))
+        (define (via-and-or n) (or (= n 0) (and (> n 0) (via-and-or (- n 1)))))
+        (define (via-when n) (when (>= n 0) (if (= n 0) 'when (begin (via-when (- n 1))))))
+        (define (via-case n) (case n ((0) 'case) (else (via-case (- n 1)))))
+        (list
+          (via-cond 10000)
+          (via-and-or 10000)
+          (via-when 10000)
+          (via-case 10000)
+          (let loop ((i 0)) (if (< i 10000) (loop (+ i 1)) i)))";
+    assert_eq!(run_on_small_stack(source), "(cond #t when case 10000)");
+}
+
+
+#[test]
+#[ignore]
+fn tail_calls_through_derived_forms_million() {
+    let source = "
+        (define (via-cond n) (cond ((= n 0) 'cond) (else (via-cond (- n 1)))))
+        (define (via-and-or n) (or (= n 0) (and (> n 0) (via-and-or (- n 1)))))
+        (list (via-cond 1000000) (via-and-or 1000000) (let loop ((i 0)) (if (< i 1000000) (loop (+ i 1)) i)))";
+    assert_eq!(run_on_small_stack(source), "(cond #t 1000000)");
+}
+
+
+#[test]
+fn set_changes_the_nearest_binding() {
+    assert_eq!(run("(define x 1) (define (bump!) (set! x (+ x 1))) (bump!) (bump!) x"), "3");
+    assert_eq!(run("(define x 1) (let ((x 10)) (set! x 20)) x"), "1");
+    assert_eq!(run("(define (counter) (let ((n 0)) (lambda () (set! n (+ n 1)) n))) (define c (counter)) (c) (c)"), "2");
+    assert!(matches!(try_run("(set! undefined 1)"), Err(EvalError::UnboundVariable(name, (0, 6))) if name == "undefined"));
+    assert!(matches!(try_run("(set! 1 2)"), Err(EvalError::BadSyntax(..))));
+}
+
+
+// 对序对的修改对全部引用可见
+#[test]
+fn pair_mutation_is_visible_through_every_reference() {
+    assert_eq!(run("(define p (list 1 2 3)) (define q (cdr p)) (set-car! q 'b) p"), "(1 b 3)");
+    assert_eq!(run("(define p (cons 1 2)) (define alias p) (set-cdr! alias '(3)) (list p (eq? p alias))"), "((1 3) #t)");
+    assert_eq!(run("(define v (vector (list 1))) (set-car! (vector-ref v 0) 'x) v"), "#((x))");
+    assert!(matches!(try_run("(set-car! '() 1)"), Err(EvalError::TypeMismatch(..))));
+}
+
+
+#[test]
+fn cyclic_structures_print_with_datum_labels() {
+    assert_eq!(run("(define p (list 1 2)) (set-cdr! (cdr p) p) p"), "#0=(1 2 . #0#)");
+    assert_eq!(run("(define p (list 1)) (set-car! p p) p"), "#0=(#0#)");
+    assert_eq!(run("(define v (vector 1 2)) (vector-set! v 1 v) v"), "#0=#(1 #0#)");
+    assert_eq!(run("(define a (list 'a)) (define b (list a a)) b"), "((a) (a))");
+    assert_eq!(run("(define a (list 1)) (define b (list 2)) (set-cdr! a a) (set-cdr! b b) (list a b)"), "(#0=(1 . #0#) #1=(2 . #1#))");
+    // 成环的列表不是真列表
+    assert!(matches!(try_run("(define p (list 1 2)) (set-cdr! (cdr p) p) (list->vector p)"), Err(EvalError::TypeMismatch(..))));
+}
+
+
+// letrec中在初始化前引用的变量报错，而非得到未指定的值
+#[test]
+fn letrec_reports_uninitialized_references() {
+    let error = try_run("(letrec ((a b) (b 1)) a)").err().expect("expected an error");
+    assert_eq!(error, EvalError::UninitializedVariable(String::from("b"), (0, 12)));
+    assert_eq!(run("(letrec ((f (lambda () b)) (b 1)) (f))"), "1");
+}
+
+
+// 非精确数按数值比较是否同一，但与精确数互不相同
+#[test]
+fn eqv_compares_inexact_numbers_by_value() {
+    assert_eq!(run("(define x 1.5) (eq? x x)"), "#t");
+    assert_eq!(run("(eq? 1.5 1.5)"), "#t");
+    assert_eq!(run("(case 2.0 ((2.0) 'hit) (else 'miss))"), "hit");
+    assert_eq!(run("(list (eq? 2 2.0) (eq? 0.0 -0.0))"), "(#f #f)");
+}
@@ -0,0 +1,480 @@
+use std::rc::Rc;
+
+use crate::{Datum, DatumKind, EvalError};
+use super::{eval, eval_body, eval_tail, Env, Procedure, Tail, Value};
+
+
+// `(define name expr)` 或 `(define (name params...) body...)`
+pub fn eval_define(datum: &Datum, items: &[Datum], env: &Env) -> Result<Value, EvalError> {
+    match items.get(1).map(|target| &target.kind) {
+        Some(DatumKind::Symbol(name)) => {
+            if items.len() != 3 {
+                return Err(EvalError::BadSyntax(String::from("`define` expects a name and exactly one expression"), datum.index));
+            }
+            // 为被定义的lambda记录过程名
+            let value = match lambda_items(&items[2]) {
+                Some(lambda) => eval_lambda(&items[2], lambda, env, Some(name.clone()))?,
+                None => eval(&items[2], env)?,
+            };
+            env.define(name, value);
+            Ok(Value::Void)
+        },
+
+        // `(define (name params...) body...)`或`(define (name params... . rest) body...)`
+        Some(kind @ (DatumKind::List(signature) | DatumKind::DottedList(signature, _))) => {
+            let Some(Datum { kind: DatumKind::Symbol(name), .. }) = signature.first() else {
+                return Err(EvalError::BadSyntax(String::from("`define` expects a procedure name"), items[1].index));
+            };
+            if items.len() < 3 {
+                return Err(EvalError::BadSyntax(String::from("`define` expects a procedure body"), datum.index));
+            }
+            let rest = match kind {
+                DatumKind::DottedList(_, rest) => Some(rest.as_ref()),
+                _ => None,
+            };
+            let (params, rest) = parse_params(&signature[1..], rest)?;
+            let procedure = Procedure {
+                name: Some(name.clone()),
+                params,
+                rest,
+                body: items[2..].to_vec(),
+                env: env.clone(),
+            };
+            env.define(name, Value::Procedure(Rc::new(procedure)));
+            Ok(Value::Void)
+        },
+
+        Some(_) => Err(EvalError::BadSyntax(String::from("`define` expects a name or a procedure signature"), items[1].index)),
+        None => Err(EvalError::BadSyntax(String::from("`define` expects a name"), datum.index)),
+    }
+}
+
+
+// `(set! name expr)`：修改已有的绑定，名字未绑定时报错
+pub fn eval_set(datum: &Datum, items: &[Datum], env: &Env) -> Result<Value, EvalError> {
+    if items.len() != 3 {
+        return Err(EvalError::BadSyntax(String::from("`set!` expects a name and exactly one expression"), datum.index));
+    }
+    let DatumKind::Symbol(name) = &items[1].kind else {
+        return Err(EvalError::BadSyntax(String::from("`set!` expects an identifier"), items[1].index));
+    };
+
+    let value = eval(&items[2], env)?;
+    if env.set(name, value) {
+        Ok(Value::Void)
+    } else {
+        Err(EvalError::UnboundVariable(name.clone(), items[1].index))
+    }
+}
+
+
+// `(if test consequent [alternative])`：两个分支均位于尾位置
+pub fn eval_if(datum: &Datum, items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    if items.len() != 3 && items.len() != 4 {
+        return Err(EvalError::BadSyntax(String::from("`if` expects a test, a consequent and an optional alternative"), datum.index));
+    }
+
+    if eval(&items[1], env)?.is_truthy() {
+        eval_tail(&items[2], env)
+    } else if let Some(alternative) = items.get(3) {
+        eval_tail(alternative, env)
+    } else {
+        Ok(Tail::Value(Value::Void))
+    }
+}
+
+
+// `(lambda (params...) body...)`、`(lambda (params... . rest) body...)` 或 `(lambda args body...)`
+pub fn eval_lambda(datum: &Datum, items: &[Datum], env: &Env, name: Option<String>) -> Result<Value, EvalError> {
+    if items.len() < 3 {
+        return Err(EvalError::BadSyntax(String::from("`lambda` expects a parameter list and a body"), datum.index));
+    }
+
+    let (params, rest) = match &items[1].kind {
+        DatumKind::List(params) => parse_params(params, None)?,
+        DatumKind::DottedList(params, rest) => parse_params(params, Some(rest))?,
+        DatumKind::Symbol(rest) => (Vec::new(), Some(rest.clone())),
+        _ => return Err(EvalError::BadSyntax(String::from("`lambda` expects a parameter list"), items[1].index)),
+    };
+
+    Ok(Value::Procedure(Rc::new(Procedure {
+        name,
+        params,
+        rest,
+        body: items[2..].to_vec(),
+        env: env.clone(),
+    })))
+}
+
+
+// `(quote datum)`
+pub fn eval_quote(datum: &Datum, items: &[Datum]) -> Result<Value, EvalError> {
+    if items.len() != 2 {
+        return Err(EvalError::BadSyntax(String::from("`quote` expects exactly one datum"), datum.index));
+    }
+    Ok(Value::from(&items[1]))
+}
+
+
+// `(quasiquote template)`
+pub fn eval_quasiquote(datum: &Datum, items: &[Datum], env: &Env) -> Result<Value, EvalError> {
+    if items.len() != 2 {
+        return Err(EvalError::BadSyntax(String::from("`quasiquote` expects exactly one template"), datum.index));
+    }
+    quasiquote(&items[1], 1, env)
+}
+
+
+// 展开准引用模板：depth为所在准引用的嵌套层数，仅depth为1的反引用被求值，
+// 更深层的反引用连同其中的模板原样保留（其内的depth相应减1）
+pub fn quasiquote(template: &Datum, depth: usize, env: &Env) -> Result<Value, EvalError> {
+    let wrap = |name: &str, value: Value| Value::list(vec![Value::Symbol(String::from(name)), value]);
+
+    match quasi_form(template) {
+        Some(("unquote", inner)) if depth == 1 => eval(inner, env),
+        Some(("unquote-splicing", _)) if depth == 1 => {
+            Err(EvalError::BadSyntax(String::from("unquote-splicing is only valid inside a list"), template.index))
+        },
+        Some((name @ ("unquote" | "unquote-splicing"), inner)) => Ok(wrap(name, quasiquote(inner, depth - 1, env)?)),
+        Some((name, inner)) => Ok(wrap(name, quasiquote(inner, depth + 1, env)?)),
+
+        None => match &template.kind {
+            DatumKind::List(items) => quasiquote_list(items, None, depth, env),
+            DatumKind::DottedList(items, tail) => quasiquote_list(items, Some(tail), depth, env),
+            DatumKind::Vector(items) => Ok(Value::vector(quasiquote_items(items, depth, env)?)),
+            DatumKind::Quote(quoted) => Ok(wrap("quote", quasiquote(quoted, depth, env)?)),
+            _ => Ok(Value::from(template)),
+        },
+    }
+}
+
+
+// 展开列表模板
+fn quasiquote_list(items: &[Datum], tail: Option<&Datum>, depth: usize, env: &Env) -> Result<Value, EvalError> {
+    let values = quasiquote_items(items, depth, env)?;
+    let tail = match tail {
+        Some(tail) => quasiquote(tail, depth, env)?,
+        None => Value::Nil,
+    };
+    Ok(values.into_iter().rev().fold(tail, |cdr, car| Value::cons(car, cdr)))
+}
+
+
+// 展开列表或向量模板的各元素，depth为1的`,@`的值（须为真列表）拼接入结果
+fn quasiquote_items(items: &[Datum], depth: usize, env: &Env) -> Result<Vec<Value>, EvalError> {
+    let mut values = Vec::new();
+    for item in items {
+        match quasi_form(item) {
+            Some(("unquote-splicing", inner)) if depth == 1 => {
+                let Some(spliced) = eval(inner, env)?.list_items() else {
+                    return Err(EvalError::TypeMismatch(String::from("unquote-splicing expects a list"), item.index));
+                };
+                values.extend(spliced);
+            },
+            _ => values.push(quasiquote(item, depth, env)?),
+        }
+    }
+    Ok(values)
+}
+
+
+// 准引用相关的形式（`` `x``、`,x`、`,@x`或其列表写法`(quasiquote x)`等）的名称及其中的模板
+fn quasi_form(datum: &Datum) -> Option<(&'static str, &Datum)> {
+    match &datum.kind {
+        DatumKind::Quasiquote(inner) => Some(("quasiquote", inner)),
+        DatumKind::Unquote(inner) => Some(("unquote", inner)),
+        DatumKind::UnquoteSplicing(inner) => Some(("unquote-splicing", inner)),
+        DatumKind::List(items) if items.len() == 2 => match &items[0].kind {
+            DatumKind::Symbol(name) => ["quasiquote", "unquote", "unquote-splicing"].into_iter()
+                .find(|form| form == name)
+                .map(|form| (form, &items[1])),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+// `(let ((name init) ...) body...)`或具名let`(let name ((var init) ...) body...)`
+pub fn eval_let(datum: &Datum, items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    if let Some(Datum { kind: DatumKind::Symbol(name), .. }) = items.get(1) {
+        if items.len() < 4 {
+            return Err(EvalError::BadSyntax(String::from("named `let` expects a name, bindings and a body"), datum.index));
+        }
+        let bindings = parse_bindings("let", &items[2], true)?;
+        let mut args = Vec::with_capacity(bindings.len());
+        for (_, init) in &bindings {
+            args.push(eval(init, env)?);
+        }
+
+        // 循环过程绑定于只含其自身的环境中，过程体内可按名字递归调用
+        let loop_env = env.extend();
+        let procedure = Value::Procedure(Rc::new(Procedure {
+            name: Some(name.clone()),
+            params: bindings.into_iter().map(|(var, _)| var).collect(),
+            rest: None,
+            body: items[3..].to_vec(),
+            env: loop_env.clone(),
+        }));
+        loop_env.define(name, procedure.clone());
+        return Ok(Tail::Call(procedure, args, datum.index));
+    }
+
+    if items.len() < 3 {
+        return Err(EvalError::BadSyntax(String::from("`let` expects bindings and a body"), datum.index));
+    }
+    let bindings = parse_bindings("let", &items[1], true)?;
+    let let_env = env.extend();
+    for (name, init) in &bindings {
+        let_env.define(name, eval(init, env)?);
+    }
+    eval_body(&items[2..], &let_env)
+}
+
+
+// `(let* ((name init) ...) body...)`：每个init均可引用其前的绑定
+pub fn eval_let_star(datum: &Datum, items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    if items.len() < 3 {
+        return Err(EvalError::BadSyntax(String::from("`let*` expects bindings and a body"), datum.index));
+    }
+    let mut let_env = env.clone();
+    for (name, init) in parse_bindings("let*", &items[1], false)? {
+        let value = eval(init, &let_env)?;
+        let_env = let_env.extend();
+        let_env.define(&name, value);
+    }
+    eval_body(&items[2..], &let_env)
+}
+
+
+// `(letrec ((name init) ...) body...)`：各init在含全部绑定的环境中依次求值
+pub fn eval_letrec(datum: &Datum, items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    let form = symbol_name(&items[0]).unwrap_or("letrec");
+    if items.len() < 3 {
+        return Err(EvalError::BadSyntax(format!("`{}` expects bindings and a body", form), datum.index));
+    }
+    let bindings = parse_bindings(form, &items[1], true)?;
+    let letrec_env = env.extend();
+    for (name, _) in &bindings {
+        letrec_env.define(name, Value::Unassigned);
+    }
+    for (name, init) in &bindings {
+        // 为被绑定的lambda记录过程名
+        let value = match lambda_items(init) {
+            Some(lambda) => eval_lambda(init, lambda, &letrec_env, Some(name.clone()))?,
+            None => eval(init, &letrec_env)?,
+        };
+        letrec_env.define(name, value);
+    }
+    eval_body(&items[2..], &letrec_env)
+}
+
+
+// `(cond (test expr...) ... (else expr...))`，子句亦可为`(test)`或`(test => receiver)`
+pub fn eval_cond(datum: &Datum, items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    let clauses = check_clauses("cond", datum, &items[1..])?;
+
+    for (clause, test, body) in clauses {
+        if symbol_name(test) == Some("else") {
+            return eval_body(body, env);
+        }
+        let value = eval(test, env)?;
+        if value.is_truthy() {
+            return clause_tail(clause, body, value, env);
+        }
+    }
+    Ok(Tail::Value(Value::Void))
+}
+
+
+// `(case key ((datum...) expr...) ... (else expr...))`，各数据与key按eqv?比较
+pub fn eval_case(datum: &Datum, items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    let Some(key) = items.get(1) else {
+        return Err(EvalError::BadSyntax(String::from("`case` expects a key and at least one clause"), datum.index));
+    };
+    let clauses = check_clauses("case", datum, &items[2..])?;
+    for (_, data, _) in &clauses {
+        if symbol_name(data) != Some("else") && !matches!(data.kind, DatumKind::List(_)) {
+            return Err(EvalError::BadSyntax(String::from("`case` clause must start with a list of data or `else`"), data.index));
+        }
+    }
+
+    let key = eval(key, env)?;
+    for (clause, data, body) in clauses {
+        let matched = match &data.kind {
+            DatumKind::List(data) => data.iter().any(|datum| key.is_eqv(&Value::from(datum))),
+            _ => true,
+        };
+        if matched {
+            return clause_tail(clause, body, key, env);
+        }
+    }
+    Ok(Tail::Value(Value::Void))
+}
+
+
+// `(and expr...)`：返回首个为假的值或最后一个值，`(and)`为#t
+pub fn eval_and(items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    let Some((last, init)) = items[1..].split_last() else {
+        return Ok(Tail::Value(Value::Bool(true)));
+    };
+    for item in init {
+        let value = eval(item, env)?;
+        if !value.is_truthy() {
+            return Ok(Tail::Value(value));
+        }
+    }
+    eval_tail(last, env)
+}
+
+
+// `(or expr...)`：返回首个为真的值或最后一个值，`(or)`为#f
+pub fn eval_or(items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    let Some((last, init)) = items[1..].split_last() else {
+        return Ok(Tail::Value(Value::Bool(false)));
+    };
+    for item in init {
+        let value = eval(item, env)?;
+        if value.is_truthy() {
+            return Ok(Tail::Value(value));
+        }
+    }
+    eval_tail(last, env)
+}
+
+
+// `(when test body...)`与`(unless test body...)`；expected为执行body所需的test的真假
+pub fn eval_when(datum: &Datum, items: &[Datum], env: &Env, expected: bool) -> Result<Tail, EvalError> {
+    if items.len() < 3 {
+        let form = if expected { "when" } else { "unless" };
+        return Err(EvalError::BadSyntax(format!("`{}` expects a test and a body", form), datum.index));
+    }
+    if eval(&items[1], env)?.is_truthy() == expected {
+        eval_body(&items[2..], env)
+    } else {
+        Ok(Tail::Value(Value::Void))
+    }
+}
+
+
+// `(begin expr...)`：在当前环境中依次求值，其中的define作用于当前环境
+pub fn eval_begin(datum: &Datum, items: &[Datum], env: &Env) -> Result<Tail, EvalError> {
+    if items.len() < 2 {
+        return Err(EvalError::BadSyntax(String::from("`begin` expects at least one expression"), datum.index));
+    }
+    eval_body(&items[1..], env)
+}
+
+
+// 校验绑定表`((name init) ...)`；distinct为真时名字须互不相同
+fn parse_bindings<'a>(form: &str, bindings: &'a Datum, distinct: bool) -> Result<Vec<(String, &'a Datum)>, EvalError> {
+    let DatumKind::List(items) = &bindings.kind else {
+        return Err(EvalError::BadSyntax(format!("`{}` expects a list of bindings", form), bindings.index));
+    };
+
+    let mut parsed: Vec<(String, &Datum)> = Vec::with_capacity(items.len());
+    for binding in items {
+        let (name, init) = match &binding.kind {
+            DatumKind::List(pair) if pair.len() == 2 => match &pair[0].kind {
+                DatumKind::Symbol(name) => (name, &pair[1]),
+                _ => return Err(EvalError::BadSyntax(format!("`{}` binding name must be an identifier", form), pair[0].index)),
+            },
+            _ => return Err(EvalError::BadSyntax(format!("`{}` binding must have the form `(name expr)`", form), binding.index)),
+        };
+        if distinct && parsed.iter().any(|(bound, _)| bound == name) {
+            return Err(EvalError::BadSyntax(format!("duplicate binding `{}` in `{}`", name, form), binding.index));
+        }
+        parsed.push((name.clone(), init));
+    }
+    Ok(parsed)
+}
+
+
+// cond、case的子句（整个子句、首个元素、其余元素）
+type Clause<'a> = (&'a Datum, &'a Datum, &'a [Datum]);
+
+
+// 校验子句的形状：须为非空列表，`else`子句须位于最后且含表达式，`=>`其后须恰有一个表达式
+fn check_clauses<'a>(form: &str, datum: &Datum, clauses: &'a [Datum]) -> Result<Vec<Clause<'a>>, EvalError> {
+    if clauses.is_empty() {
+        return Err(EvalError::BadSyntax(format!("`{}` expects at least one clause", form), datum.index));
+    }
+
+    let mut checked = Vec::with_capacity(clauses.len());
+    for (i, clause) in clauses.iter().enumerate() {
+        let DatumKind::List(parts) = &clause.kind else {
+            return Err(EvalError::BadSyntax(format!("`{}` clause must be a non-empty list", form), clause.index));
+        };
+        let Some((head, body)) = parts.split_first() else {
+            return Err(EvalError::BadSyntax(format!("`{}` clause must be a non-empty list", form), clause.index));
+        };
+
+        if symbol_name(head) == Some("else") {
+            if i + 1 != clauses.len() {
+                return Err(EvalError::BadSyntax(format!("`else` must be the last clause of `{}`", form), clause.index));
+            }
+            if body.is_empty() {
+                return Err(EvalError::BadSyntax(String::from("`else` clause expects at least one expression"), clause.index));
+            }
+        }
+        // case的子句与cond的else子句须含表达式
+        if form == "case" && body.is_empty() {
+            return Err(EvalError::BadSyntax(String::from("`case` clause expects at least one expression"), clause.index));
+        }
+        if body.first().and_then(symbol_name) == Some("=>") && body.len() != 2 {
+            return Err(EvalError::BadSyntax(String::from("`=>` must be followed by exactly one expression"), body[0].index));
+        }
+        checked.push((clause, head, body));
+    }
+    Ok(checked)
+}
+
+
+// 被选中的子句：`(test)`的值为test的值，`(... => receiver)`以该值调用receiver，其余依次求值各表达式
+fn clause_tail(clause: &Datum, body: &[Datum], value: Value, env: &Env) -> Result<Tail, EvalError> {
+    match body {
+        [] => Ok(Tail::Value(value)),
+        [arrow, receiver] if symbol_name(arrow) == Some("=>") => Ok(Tail::Call(eval(receiver, env)?, vec![value], clause.index)),
+        _ => eval_body(body, env),
+    }
+}
+
+
+fn symbol_name(datum: &Datum) -> Option<&str> {
+    match &datum.kind {
+        DatumKind::Symbol(name) => Some(name),
+        _ => None,
+    }
+}
+
+
+// 若datum为lambda表达式，返回其各组成部分
+fn lambda_items(datum: &Datum) -> Option<&[Datum]> {
+    match &datum.kind {
+        DatumKind::List(items) => match items.first() {
+            Some(Datum { kind: DatumKind::Symbol(head), .. }) if head == "lambda" => Some(items),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+// 校验形参表：形参须为互不相同的标识符
+fn parse_params(params: &[Datum], rest: Option<&Datum>) -> Result<(Vec<String>, Option<String>), EvalError> {
+    let mut names: Vec<String> = Vec::new();
+
+    for param in params.iter().chain(rest) {
+        match &param.kind {
+            DatumKind::Symbol(name) if !names.contains(name) => names.push(name.clone()),
+            DatumKind::Symbol(name) => return Err(EvalError::BadSyntax(format!("duplicate parameter `{}`", name), param.index)),
+            _ => return Err(EvalError::BadSyntax(String::from("parameter must be an identifier"), param.index)),
+        }
+    }
+
+    let rest = match rest {
+        Some(_) => names.pop(),
+        None => None,
+    };
+    Ok((names, rest))
+}
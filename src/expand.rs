@@ -0,0 +1,442 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, slice, sync::atomic::{AtomicUsize, Ordering}};
+
+use crate::{Datum, DatumKind, EvalError, eval::{Env, Procedure, Value, apply, eval}};
+use rules::SyntaxRules;
+
+mod rules;
+#[cfg(test)]
+mod tests;
+
+
+// 宏展开：在语法分析之后、求值之前把宏的使用替换为展开结果
+// 展开后的程序只含求值器识别的特殊形式，`define-syntax`、`let-syntax`与`define-macro`不再出现
+
+
+// 同一表达式连续展开的次数上限，超过时视为展开不终止
+const MAX_EXPANSIONS: usize = 10_000;
+
+
+// 生成新名字所用的计数器
+static FRESH: AtomicUsize = AtomicUsize::new(0);
+
+
+// 语法环境中绑定的宏
+enum Macro {
+    // `syntax-rules`定义的卫生宏
+    Rules(SyntaxRules),
+    // `define-macro`定义的宏：以未求值的实参（作为数据）调用变换过程，其返回值即展开结果
+    Procedure { name: String, transformer: Value },
+}
+
+
+// 语法环境：名字 -> 宏；局部变量遮蔽同名的宏时记为None
+#[derive(Clone)]
+pub struct MacroEnv(Rc<RefCell<MacroFrame>>);
+
+
+struct MacroFrame {
+    names: HashMap<String, Option<Rc<Macro>>>,
+    parent: Option<MacroEnv>,
+    // `define-macro`的变换过程求值所在的环境：即程序求值所用的全局环境，变换过程可调用此前定义的过程
+    env: Env,
+}
+
+
+impl MacroEnv {
+    pub fn global(env: &Env) -> MacroEnv {
+        MacroEnv(Rc::new(RefCell::new(MacroFrame { names: HashMap::new(), parent: None, env: env.clone() })))
+    }
+
+    pub fn extend(&self) -> MacroEnv {
+        let env = self.0.borrow().env.clone();
+        MacroEnv(Rc::new(RefCell::new(MacroFrame { names: HashMap::new(), parent: Some(self.clone()), env })))
+    }
+
+    fn define(&self, name: &str, transformer: Macro) {
+        self.0.borrow_mut().names.insert(String::from(name), Some(Rc::new(transformer)));
+    }
+
+    // 在本层绑定变量name，遮蔽外层的同名宏
+    fn shadow(&self, name: &str) {
+        if self.lookup(name).is_some() {
+            self.0.borrow_mut().names.insert(String::from(name), None);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Rc<Macro>> {
+        let frame = self.0.borrow();
+        match frame.names.get(name) {
+            Some(transformer) => transformer.clone(),
+            None => frame.parent.as_ref().and_then(|parent| parent.lookup(name)),
+        }
+    }
+}
+
+
+impl Macro {
+    fn expand(&self, form: &Datum) -> Result<Datum, EvalError> {
+        let (name, transformer) = match self {
+            Macro::Rules(rules) => return rules.expand(form),
+            Macro::Procedure { name, transformer } => (name, transformer),
+        };
+        let DatumKind::List(items) = &form.kind else {
+            return Err(EvalError::BadSyntax(format!("invalid use of macro `{}`", name), form.index));
+        };
+
+        let args: Vec<Value> = items[1..].iter().map(Value::from).collect();
+        // 变换过程可能定义于别处（如交互式解释器之前的输入），出错时报告在宏的使用处
+        let expansion = apply(transformer, &args, form.index)
+            .map_err(|e| EvalError::BadSyntax(format!("in expansion of macro `{}`: {}", name, e.message()), form.index))?;
+        expansion.to_datum(form.index)
+            .ok_or_else(|| EvalError::BadSyntax(format!("macro `{}` expanded into `{}`, which is not a valid form", name, expansion), form.index))
+    }
+}
+
+
+// 依次展开各顶层表达式；顶层的宏定义作用于其后的全部表达式
+// 过程定义没有副作用，展开后即求值，供其后的变换过程调用
+pub fn expand_program(program: &[Datum], env: &MacroEnv) -> Result<Vec<Datum>, EvalError> {
+    let runtime = env.0.borrow().env.clone();
+    let mut expanded = Vec::with_capacity(program.len());
+    for form in program {
+        for datum in expand_body(slice::from_ref(form), env)? {
+            if defines_procedure(&datum) {
+                eval(&datum, &runtime)?;
+            }
+            expanded.push(datum);
+        }
+    }
+    Ok(expanded)
+}
+
+
+// 逐个展开并求值顶层表达式：每个表达式展开时，其前的定义均已求值，变换过程可以调用
+pub fn expand_and_eval(program: &[Datum], env: &MacroEnv) -> Result<Value, EvalError> {
+    let runtime = env.0.borrow().env.clone();
+    let mut result = Value::Void;
+    for form in program {
+        for datum in expand_body(slice::from_ref(form), env)? {
+            result = eval(&datum, &runtime)?;
+        }
+    }
+    Ok(result)
+}
+
+
+// 由name派生的新名字；`.`不能出现在标识符中，因而不会与源程序中的名字冲突
+pub fn fresh_name(name: &str) -> String {
+    format!("{}.{}", name, FRESH.fetch_add(1, Ordering::Relaxed) + 1)
+}
+
+
+// 展开顶层或过程体中的表达式序列：其中可出现宏定义，`begin`中的定义作用于同一层
+fn expand_body(forms: &[Datum], env: &MacroEnv) -> Result<Vec<Datum>, EvalError> {
+    let mut expanded = Vec::with_capacity(forms.len());
+    for form in forms {
+        let form = expand_head(form, env)?;
+        let DatumKind::List(items) = &form.kind else {
+            expanded.push(expand_expr(&form, env)?);
+            continue;
+        };
+
+        match form_name(&form) {
+            Some("define-syntax") => define_syntax(&form, items, env)?,
+            Some("define-macro") => define_macro(&form, items, env)?,
+            Some("begin") => {
+                let body = expand_body(&items[1..], env)?;
+                // 只含宏定义的`begin`不留下任何表达式
+                if body.is_empty() && items.len() > 1 {
+                    continue;
+                }
+                expanded.push(list(&form, items[..1].iter().cloned().chain(body).collect()));
+            },
+            Some("define") => {
+                let name = match items.get(1).map(|target| &target.kind) {
+                    Some(DatumKind::Symbol(name)) => Some(name),
+                    Some(DatumKind::List(signature) | DatumKind::DottedList(signature, _)) => match signature.first().map(|name| &name.kind) {
+                        Some(DatumKind::Symbol(name)) => Some(name),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(name) = name {
+                    env.shadow(name);
+                }
+                expanded.push(expand_expr(&form, env)?);
+            },
+            _ => expanded.push(expand_expr(&form, env)?),
+        }
+    }
+    Ok(expanded)
+}
+
+
+fn expand_expr(datum: &Datum, env: &MacroEnv) -> Result<Datum, EvalError> {
+    let datum = expand_head(datum, env)?;
+    match &datum.kind {
+        DatumKind::Symbol(name) if env.lookup(name).is_some() => {
+            Err(EvalError::BadSyntax(format!("macro `{}` cannot be used as a variable", name), datum.index))
+        },
+        DatumKind::Quasiquote(template) => Ok(Datum {
+            kind: DatumKind::Quasiquote(Box::new(expand_quasiquote(template, env, 1)?)),
+            index: datum.index,
+        }),
+        DatumKind::List(items) if !items.is_empty() => expand_form(&datum, items, env),
+        _ => Ok(datum),
+    }
+}
+
+
+// 反复展开表达式开头的宏，直至其不再是宏的使用
+fn expand_head(datum: &Datum, env: &MacroEnv) -> Result<Datum, EvalError> {
+    let mut datum = datum.clone();
+    for _ in 0..MAX_EXPANSIONS {
+        match form_name(&datum).and_then(|name| env.lookup(name)) {
+            Some(transformer) => datum = transformer.expand(&datum)?,
+            None => return Ok(datum),
+        }
+    }
+    Err(EvalError::BadSyntax(format!("macro expansion did not terminate after {} steps", MAX_EXPANSIONS), datum.index))
+}
+
+
+// 按特殊形式的结构展开其中的表达式；结构不合法的形式原样保留，由求值器报告错误
+fn expand_form(datum: &Datum, items: &[Datum], env: &MacroEnv) -> Result<Datum, EvalError> {
+    match form_name(datum) {
+        Some("quote") => Ok(datum.clone()),
+        Some("quasiquote") if items.len() == 2 => Ok(list(datum, vec![items[0].clone(), expand_quasiquote(&items[1], env, 1)?])),
+        Some("lambda") if items.len() >= 3 => {
+            let scope = env.extend();
+            shadow_params(&items[1], &scope);
+            Ok(list(datum, items[..2].iter().cloned().chain(expand_body(&items[2..], &scope)?).collect()))
+        },
+        Some("define") if items.len() >= 3 => match &items[1].kind {
+            DatumKind::List(signature) | DatumKind::DottedList(signature, _) if !signature.is_empty() => {
+                let scope = env.extend();
+                shadow_params(&items[1], &scope);
+                Ok(list(datum, items[..2].iter().cloned().chain(expand_body(&items[2..], &scope)?).collect()))
+            },
+            _ => Ok(list(datum, items[..2].iter().cloned().chain(expand_each(&items[2..], env)?).collect())),
+        },
+        Some(form @ ("let" | "let*" | "letrec" | "letrec*")) => expand_let(form, datum, items, env),
+        Some("cond") => {
+            let mut expanded = vec![items[0].clone()];
+            for clause in &items[1..] {
+                expanded.push(match &clause.kind {
+                    DatumKind::List(parts) => list(clause, expand_each(parts, env)?),
+                    _ => clause.clone(),
+                });
+            }
+            Ok(list(datum, expanded))
+        },
+        // 各子句的数据表不是表达式
+        Some("case") if items.len() >= 2 => {
+            let mut expanded = vec![items[0].clone(), expand_expr(&items[1], env)?];
+            for clause in &items[2..] {
+                expanded.push(match &clause.kind {
+                    DatumKind::List(parts) if !parts.is_empty() => {
+                        list(clause, parts[..1].iter().cloned().chain(expand_each(&parts[1..], env)?).collect())
+                    },
+                    _ => clause.clone(),
+                });
+            }
+            Ok(list(datum, expanded))
+        },
+        Some("let-syntax") => expand_let_syntax(datum, items, env),
+        Some(form @ ("define-syntax" | "define-macro")) => {
+            Err(EvalError::BadSyntax(format!("`{}` is only allowed at the top level or at the start of a body", form), datum.index))
+        },
+        Some("syntax-rules") => Err(EvalError::BadSyntax(String::from("`syntax-rules` is only allowed in a macro definition"), datum.index)),
+        _ => Ok(list(datum, expand_each(items, env)?)),
+    }
+}
+
+
+fn expand_each(datums: &[Datum], env: &MacroEnv) -> Result<Vec<Datum>, EvalError> {
+    datums.iter().map(|datum| expand_expr(datum, env)).collect()
+}
+
+
+// `(let [name] ((var init) ...) body ...)`及`let*`、`letrec`、`letrec*`
+fn expand_let(form: &str, datum: &Datum, items: &[Datum], env: &MacroEnv) -> Result<Datum, EvalError> {
+    let named = form == "let" && matches!(items.get(1).map(|name| &name.kind), Some(DatumKind::Symbol(_)));
+    let start = if named { 2 } else { 1 };
+    let Some(Datum { kind: DatumKind::List(bindings), index }) = items.get(start) else {
+        return Ok(datum.clone());
+    };
+    let mut parsed = Vec::with_capacity(bindings.len());
+    for binding in bindings {
+        match &binding.kind {
+            DatumKind::List(pair) if pair.len() == 2 && matches!(pair[0].kind, DatumKind::Symbol(_)) => parsed.push((binding, &pair[0], &pair[1])),
+            _ => return Ok(datum.clone()),
+        }
+    }
+    if items.len() <= start + 1 {
+        return Ok(datum.clone());
+    }
+
+    let scope = env.extend();
+    let shadow = |name: &Datum| if let DatumKind::Symbol(name) = &name.kind {
+        scope.shadow(name);
+    };
+    if form == "letrec" || form == "letrec*" {
+        parsed.iter().for_each(|(_, name, _)| shadow(name));
+    }
+    let mut expanded = Vec::with_capacity(parsed.len());
+    for (binding, name, init) in parsed {
+        // let的初值位于外层作用域；let*的初值可见此前的变量
+        let init = expand_expr(init, if form == "let" { env } else { &scope })?;
+        expanded.push(list(binding, vec![name.clone(), init]));
+        shadow(name);
+    }
+    if named {
+        shadow(&items[1]);
+    }
+
+    let bindings = Datum { kind: DatumKind::List(expanded), index: *index };
+    let body = expand_body(&items[start + 1..], &scope)?;
+    Ok(list(datum, items[..start].iter().cloned().chain([bindings]).chain(body).collect()))
+}
+
+
+// `(define-syntax name (syntax-rules ...))`
+fn define_syntax(datum: &Datum, items: &[Datum], env: &MacroEnv) -> Result<(), EvalError> {
+    if items.len() != 3 {
+        return Err(EvalError::BadSyntax(String::from("`define-syntax` expects a name and a transformer"), datum.index));
+    }
+    let DatumKind::Symbol(name) = &items[1].kind else {
+        return Err(EvalError::BadSyntax(String::from("`define-syntax` expects an identifier"), items[1].index));
+    };
+    env.define(name, Macro::Rules(SyntaxRules::new(name, &items[2])?));
+    Ok(())
+}
+
+
+// `(define-macro (name . params) body ...)`：变换过程在展开时求值，其中用到的宏先行展开
+fn define_macro(datum: &Datum, items: &[Datum], env: &MacroEnv) -> Result<(), EvalError> {
+    let (name, params) = match items.get(1).map(|signature| &signature.kind) {
+        Some(DatumKind::List(signature)) if let Some(DatumKind::Symbol(name)) = signature.first().map(|name| &name.kind) => {
+            (name, DatumKind::List(signature[1..].to_vec()))
+        },
+        Some(DatumKind::DottedList(signature, rest)) if let Some(DatumKind::Symbol(name)) = signature.first().map(|name| &name.kind) => {
+            let params = if signature.len() == 1 { rest.kind.clone() } else { DatumKind::DottedList(signature[1..].to_vec(), rest.clone()) };
+            (name, params)
+        },
+        _ => return Err(EvalError::BadSyntax(String::from("`define-macro` expects a signature `(name . params)`"), items.get(1).map_or(datum.index, |signature| signature.index))),
+    };
+    if items.len() < 3 {
+        return Err(EvalError::BadSyntax(String::from("`define-macro` expects a body"), datum.index));
+    }
+
+    let head = Datum { kind: DatumKind::Symbol(String::from("lambda")), index: items[0].index };
+    let params = Datum { kind: params, index: items[1].index };
+    let lambda = expand_expr(&list(datum, [head, params].into_iter().chain(items[2..].iter().cloned()).collect()), env)?;
+    // 以宏名命名变换过程，使调用出错时的信息指明是哪个宏
+    let transformer = match eval(&lambda, &env.0.borrow().env)? {
+        Value::Procedure(procedure) => Value::Procedure(Rc::new(Procedure {
+            name: Some(name.clone()),
+            params: procedure.params.clone(),
+            rest: procedure.rest.clone(),
+            body: procedure.body.clone(),
+            env: procedure.env.clone(),
+        })),
+        other => other,
+    };
+    env.define(name, Macro::Procedure { name: name.clone(), transformer });
+    Ok(())
+}
+
+
+// `(let-syntax ((name (syntax-rules ...)) ...) body ...)`：展开为`(let () body ...)`
+fn expand_let_syntax(datum: &Datum, items: &[Datum], env: &MacroEnv) -> Result<Datum, EvalError> {
+    let Some(Datum { kind: DatumKind::List(bindings), index }) = items.get(1) else {
+        return Err(EvalError::BadSyntax(String::from("`let-syntax` expects a list of bindings"), items.get(1).map_or(datum.index, |bindings| bindings.index)));
+    };
+    if items.len() < 3 {
+        return Err(EvalError::BadSyntax(String::from("`let-syntax` expects a body"), datum.index));
+    }
+
+    let scope = env.extend();
+    for binding in bindings {
+        match &binding.kind {
+            DatumKind::List(pair) if pair.len() == 2 => match &pair[0].kind {
+                DatumKind::Symbol(name) => scope.define(name, Macro::Rules(SyntaxRules::new(name, &pair[1])?)),
+                _ => return Err(EvalError::BadSyntax(String::from("`let-syntax` binding name must be an identifier"), pair[0].index)),
+            },
+            _ => return Err(EvalError::BadSyntax(String::from("`let-syntax` binding must have the form `(name transformer)`"), binding.index)),
+        }
+    }
+
+    let head = Datum { kind: DatumKind::Symbol(String::from("let")), index: items[0].index };
+    let bindings = Datum { kind: DatumKind::List(Vec::new()), index: *index };
+    let body = expand_body(&items[2..], &scope)?;
+    Ok(list(datum, [head, bindings].into_iter().chain(body).collect()))
+}
+
+
+// 准引用模板中只有（最外层的）反引用部分是表达式
+fn expand_quasiquote(template: &Datum, env: &MacroEnv, depth: usize) -> Result<Datum, EvalError> {
+    let kind = match &template.kind {
+        DatumKind::Unquote(inner) if depth == 1 => DatumKind::Unquote(Box::new(expand_expr(inner, env)?)),
+        DatumKind::UnquoteSplicing(inner) if depth == 1 => DatumKind::UnquoteSplicing(Box::new(expand_expr(inner, env)?)),
+        DatumKind::Unquote(inner) => DatumKind::Unquote(Box::new(expand_quasiquote(inner, env, depth - 1)?)),
+        DatumKind::UnquoteSplicing(inner) => DatumKind::UnquoteSplicing(Box::new(expand_quasiquote(inner, env, depth - 1)?)),
+        DatumKind::Quasiquote(inner) => DatumKind::Quasiquote(Box::new(expand_quasiquote(inner, env, depth + 1)?)),
+        DatumKind::Quote(inner) => DatumKind::Quote(Box::new(expand_quasiquote(inner, env, depth)?)),
+        DatumKind::List(items) => DatumKind::List(items.iter().map(|item| expand_quasiquote(item, env, depth)).collect::<Result<_, _>>()?),
+        DatumKind::Vector(items) => DatumKind::Vector(items.iter().map(|item| expand_quasiquote(item, env, depth)).collect::<Result<_, _>>()?),
+        DatumKind::DottedList(items, tail) => DatumKind::DottedList(
+            items.iter().map(|item| expand_quasiquote(item, env, depth)).collect::<Result<_, _>>()?,
+            Box::new(expand_quasiquote(tail, env, depth)?),
+        ),
+        _ => return Ok(template.clone()),
+    };
+    Ok(Datum { kind, index: template.index })
+}
+
+
+// 形参表中的变量遮蔽同名的宏；`define`的形参表以过程名开头
+fn shadow_params(params: &Datum, scope: &MacroEnv) {
+    let (items, tail) = match &params.kind {
+        DatumKind::Symbol(name) => return scope.shadow(name),
+        DatumKind::List(items) => (items.as_slice(), None),
+        DatumKind::DottedList(items, tail) => (items.as_slice(), Some(tail.as_ref())),
+        _ => return,
+    };
+    for param in items.iter().chain(tail) {
+        if let DatumKind::Symbol(name) = &param.kind {
+            scope.shadow(name);
+        }
+    }
+}
+
+
+// `(define (name . params) body ...)`或`(define name (lambda ...))`
+fn defines_procedure(datum: &Datum) -> bool {
+    let DatumKind::List(items) = &datum.kind else {
+        return false;
+    };
+    match (form_name(datum), items.get(1).map(|target| &target.kind), items.get(2)) {
+        (Some("define"), Some(DatumKind::List(_) | DatumKind::DottedList(..)), _) => true,
+        (Some("define"), Some(DatumKind::Symbol(_)), Some(value)) => items.len() == 3 && form_name(value) == Some("lambda"),
+        _ => false,
+    }
+}
+
+
+// 以符号开头的列表的首个符号
+fn form_name(datum: &Datum) -> Option<&str> {
+    match &datum.kind {
+        DatumKind::List(items) => match items.first().map(|head| &head.kind) {
+            Some(DatumKind::Symbol(name)) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+fn list(datum: &Datum, items: Vec<Datum>) -> Datum {
+    Datum { kind: DatumKind::List(items), index: datum.index }
+}
+
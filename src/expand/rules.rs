@@ -0,0 +1,462 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Datum, DatumKind, EvalError};
+use super::fresh_name;
+
+
+// `syntax-rules`定义的宏：依次尝试各条规则，以首个匹配的模式所对应的模板展开
+pub struct SyntaxRules {
+    name: String,
+    ellipsis: String,
+    literals: Vec<String>,
+    // (模式, 模板)
+    rules: Vec<(Datum, Datum)>,
+}
+
+
+// 模式变量匹配到的内容：位于省略号之下的变量匹配一个序列
+#[derive(Clone)]
+enum Match {
+    One(Datum),
+    Many(Vec<Match>),
+}
+
+
+type Bindings = HashMap<String, Match>;
+
+
+impl SyntaxRules {
+    // `(syntax-rules [ellipsis] (literal ...) (pattern template) ...)`
+    pub fn new(name: &str, spec: &Datum) -> Result<SyntaxRules, EvalError> {
+        let items = match &spec.kind {
+            DatumKind::List(items) if matches!(items.first().map(|head| &head.kind), Some(DatumKind::Symbol(head)) if head == "syntax-rules") => items,
+            _ => return Err(EvalError::BadSyntax(format!("the transformer of `{}` must be a `syntax-rules` form", name), spec.index)),
+        };
+
+        // 自定义的省略号
+        let (ellipsis, rest) = match items.get(1).map(|ellipsis| &ellipsis.kind) {
+            Some(DatumKind::Symbol(ellipsis)) => (ellipsis.clone(), &items[2..]),
+            _ => (String::from("..."), &items[1..]),
+        };
+        let Some(Datum { kind: DatumKind::List(literals), .. }) = rest.first() else {
+            return Err(EvalError::BadSyntax(String::from("`syntax-rules` expects a list of literals"), rest.first().map_or(spec.index, |literals| literals.index)));
+        };
+        let literals = literals.iter().map(|literal| match &literal.kind {
+            DatumKind::Symbol(literal) => Ok(literal.clone()),
+            _ => Err(EvalError::BadSyntax(String::from("`syntax-rules` literal must be an identifier"), literal.index)),
+        }).collect::<Result<Vec<String>, EvalError>>()?;
+
+        let mut transformer = SyntaxRules { name: String::from(name), ellipsis, literals, rules: Vec::new() };
+        for rule in &rest[1..] {
+            let (pattern, template) = match &rule.kind {
+                DatumKind::List(pair) if pair.len() == 2 => (&pair[0], &pair[1]),
+                _ => return Err(EvalError::BadSyntax(String::from("`syntax-rules` rule must have the form `(pattern template)`"), rule.index)),
+            };
+            // 模式的首个元素对应宏关键字，不参与匹配
+            let (items, tail) = match &pattern.kind {
+                DatumKind::List(items) if !items.is_empty() => (items, None),
+                DatumKind::DottedList(items, tail) => (items, Some(tail.as_ref())),
+                _ => return Err(EvalError::BadSyntax(String::from("`syntax-rules` pattern must be a list headed by the macro keyword"), pattern.index)),
+            };
+            let mut vars = Vec::new();
+            transformer.check_sequence(&items[1..], tail, &mut vars)?;
+            transformer.rules.push((pattern.clone(), template.clone()));
+        }
+        Ok(transformer)
+    }
+
+    // 以首个匹配的规则展开form；展开结果中来自模板的部分均位于form处
+    pub fn expand(&self, form: &Datum) -> Result<Datum, EvalError> {
+        let DatumKind::List(items) = &form.kind else {
+            return Err(EvalError::BadSyntax(format!("invalid use of macro `{}`", self.name), form.index));
+        };
+
+        for (pattern, template) in &self.rules {
+            let (pattern_items, pattern_tail) = list_view(pattern).unwrap_or_default();
+            let mut bindings = Bindings::new();
+            if self.match_sequence(&pattern_items[1..], pattern_tail.as_ref(), &items[1..], None, form.index, &mut bindings) {
+                let mut vars = Vec::new();
+                let _ = self.check_sequence(&pattern_items[1..], pattern_tail.as_ref(), &mut vars);
+                let vars = vars.into_iter().collect();
+                let template = self.rename(template, &HashMap::new(), &vars);
+                return self.instantiate(&template, &bindings, true, form.index);
+            }
+        }
+        Err(EvalError::BadSyntax(format!("no rule of macro `{}` matches this form", self.name), form.index))
+    }
+
+    fn is_ellipsis(&self, datum: &Datum) -> bool {
+        matches!(&datum.kind, DatumKind::Symbol(name) if *name == self.ellipsis && !self.literals.contains(name))
+    }
+
+    fn is_pattern_var(&self, name: &str) -> bool {
+        name != "_" && name != self.ellipsis && !self.literals.iter().any(|literal| literal == name)
+    }
+
+    // 校验模式：省略号须紧随一个子模式且每层至多一个，模式变量互不相同
+    fn check_pattern(&self, pattern: &Datum, vars: &mut Vec<String>) -> Result<(), EvalError> {
+        match &pattern.kind {
+            _ if self.is_ellipsis(pattern) => Err(EvalError::BadSyntax(String::from("misplaced ellipsis in `syntax-rules` pattern"), pattern.index)),
+            DatumKind::Symbol(name) if self.is_pattern_var(name) => {
+                if vars.contains(name) {
+                    return Err(EvalError::BadSyntax(format!("duplicate pattern variable `{}`", name), pattern.index));
+                }
+                vars.push(name.clone());
+                Ok(())
+            },
+            DatumKind::Vector(items) => self.check_sequence(items, None, vars),
+            _ => match list_view(pattern) {
+                Some((items, tail)) => self.check_sequence(&items, tail.as_ref(), vars),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn check_sequence(&self, items: &[Datum], tail: Option<&Datum>, vars: &mut Vec<String>) -> Result<(), EvalError> {
+        let mut seen = false;
+        for (i, item) in items.iter().enumerate() {
+            if self.is_ellipsis(item) {
+                if i == 0 || seen {
+                    return Err(EvalError::BadSyntax(String::from("misplaced ellipsis in `syntax-rules` pattern"), item.index));
+                }
+                seen = true;
+            } else {
+                self.check_pattern(item, vars)?;
+            }
+        }
+        match tail {
+            Some(tail) => self.check_pattern(tail, vars),
+            None => Ok(()),
+        }
+    }
+
+    fn pattern_vars(&self, pattern: &Datum) -> Vec<String> {
+        let mut vars = Vec::new();
+        // 规则已在定义时校验
+        let _ = self.check_pattern(pattern, &mut vars);
+        vars
+    }
+
+    fn match_datum(&self, pattern: &Datum, input: &Datum, bindings: &mut Bindings) -> bool {
+        match &pattern.kind {
+            DatumKind::Symbol(name) if name == "_" => true,
+            DatumKind::Symbol(name) if !self.is_pattern_var(name) => matches!(&input.kind, DatumKind::Symbol(symbol) if symbol == name),
+            DatumKind::Symbol(name) => {
+                bindings.insert(name.clone(), Match::One(input.clone()));
+                true
+            },
+            DatumKind::Const(value) => matches!(&input.kind, DatumKind::Const(other) if value == other),
+            DatumKind::Vector(items) => match &input.kind {
+                DatumKind::Vector(inputs) => self.match_sequence(items, None, inputs, None, input.index, bindings),
+                _ => false,
+            },
+            _ => match (list_view(pattern), list_view(input)) {
+                (Some((items, tail)), Some((inputs, input_tail))) => {
+                    self.match_sequence(&items, tail.as_ref(), &inputs, input_tail.as_ref(), input.index, bindings)
+                },
+                _ => false,
+            },
+        }
+    }
+
+    // 以`(items ... . tail)`匹配`(inputs ... . input_tail)`；index为输入所在的位置
+    fn match_sequence(&self, items: &[Datum], tail: Option<&Datum>, inputs: &[Datum], input_tail: Option<&Datum>, index: (usize, usize), bindings: &mut Bindings) -> bool {
+        let Some(position) = items.iter().position(|item| self.is_ellipsis(item)) else {
+            if inputs.len() < items.len() || !items.iter().zip(inputs).all(|(item, input)| self.match_datum(item, input, bindings)) {
+                return false;
+            }
+            let rest = &inputs[items.len()..];
+            return match tail {
+                None => rest.is_empty() && input_tail.is_none(),
+                Some(tail) => self.match_datum(tail, &join(rest.to_vec(), input_tail.cloned(), index), bindings),
+            };
+        };
+
+        // `(before ... repeated <ellipsis> after ... . tail)`
+        let (before, repeated, after) = (&items[..position - 1], &items[position - 1], &items[position + 1..]);
+        if inputs.len() < before.len() + after.len() {
+            return false;
+        }
+        let count = inputs.len() - before.len() - after.len();
+        if !before.iter().zip(inputs).all(|(item, input)| self.match_datum(item, input, bindings)) {
+            return false;
+        }
+
+        let mut matches = Vec::with_capacity(count);
+        for input in &inputs[before.len()..before.len() + count] {
+            let mut inner = Bindings::new();
+            if !self.match_datum(repeated, input, &mut inner) {
+                return false;
+            }
+            matches.push(inner);
+        }
+        for var in self.pattern_vars(repeated) {
+            let sequence = matches.iter().filter_map(|inner| inner.get(&var).cloned()).collect();
+            bindings.insert(var, Match::Many(sequence));
+        }
+
+        if !after.iter().zip(&inputs[before.len() + count..]).all(|(item, input)| self.match_datum(item, input, bindings)) {
+            return false;
+        }
+        match (tail, input_tail) {
+            (None, input_tail) => input_tail.is_none(),
+            (Some(tail), Some(input_tail)) => self.match_datum(tail, input_tail, bindings),
+            (Some(tail), None) => self.match_datum(tail, &Datum { kind: DatumKind::List(Vec::new()), index }, bindings),
+        }
+    }
+
+    // 以匹配结果填充模板；ellipsis为假时（位于`(... ...)`之内）省略号按普通符号处理
+    fn instantiate(&self, template: &Datum, bindings: &Bindings, ellipsis: bool, index: (usize, usize)) -> Result<Datum, EvalError> {
+        let kind = match &template.kind {
+            DatumKind::Symbol(name) => match bindings.get(name) {
+                Some(Match::One(datum)) => return Ok(datum.clone()),
+                Some(Match::Many(_)) => {
+                    return Err(EvalError::BadSyntax(format!("pattern variable `{}` must be followed by an ellipsis in the template of `{}`", name, self.name), index));
+                },
+                None => DatumKind::Symbol(name.clone()),
+            },
+            // `(... template)`：template中的省略号不再表示重复
+            DatumKind::List(items) if ellipsis && items.len() == 2 && self.is_ellipsis(&items[0]) => {
+                return self.instantiate(&items[1], bindings, false, index);
+            },
+            DatumKind::List(items) => DatumKind::List(self.instantiate_sequence(items, bindings, ellipsis, index)?),
+            DatumKind::Vector(items) => DatumKind::Vector(self.instantiate_sequence(items, bindings, ellipsis, index)?),
+            DatumKind::DottedList(items, tail) => {
+                let items = self.instantiate_sequence(items, bindings, ellipsis, index)?;
+                let tail = self.instantiate(tail, bindings, ellipsis, index)?;
+                return Ok(join(items, Some(tail), index));
+            },
+            DatumKind::Quote(inner) => DatumKind::Quote(Box::new(self.instantiate(inner, bindings, ellipsis, index)?)),
+            DatumKind::Quasiquote(inner) => DatumKind::Quasiquote(Box::new(self.instantiate(inner, bindings, ellipsis, index)?)),
+            DatumKind::Unquote(inner) => DatumKind::Unquote(Box::new(self.instantiate(inner, bindings, ellipsis, index)?)),
+            DatumKind::UnquoteSplicing(inner) => DatumKind::UnquoteSplicing(Box::new(self.instantiate(inner, bindings, ellipsis, index)?)),
+            DatumKind::Const(value) => DatumKind::Const(value.clone()),
+        };
+        Ok(Datum { kind, index })
+    }
+
+    // 其后跟有n个省略号的元素展开为n层重复
+    fn instantiate_sequence(&self, items: &[Datum], bindings: &Bindings, ellipsis: bool, index: (usize, usize)) -> Result<Vec<Datum>, EvalError> {
+        let mut result = Vec::with_capacity(items.len());
+        let mut i = 0;
+        while i < items.len() {
+            let mut depth = 0;
+            while ellipsis && items.get(i + depth + 1).is_some_and(|item| self.is_ellipsis(item)) {
+                depth += 1;
+            }
+            if depth == 0 {
+                result.push(self.instantiate(&items[i], bindings, ellipsis, index)?);
+            } else {
+                self.repeat(&items[i], bindings, depth, &mut result, index)?;
+            }
+            i += depth + 1;
+        }
+        Ok(result)
+    }
+
+    fn repeat(&self, template: &Datum, bindings: &Bindings, depth: usize, result: &mut Vec<Datum>, index: (usize, usize)) -> Result<(), EvalError> {
+        // 驱动重复的是模板中匹配了序列的模式变量
+        let mut symbols = Vec::new();
+        collect_symbols(template, &mut symbols);
+        let mut sequences: Vec<(&String, &Vec<Match>)> = Vec::new();
+        for symbol in symbols {
+            if let Some((name, Match::Many(sequence))) = bindings.get_key_value(symbol) && !sequences.iter().any(|(seen, _)| *seen == name) {
+                sequences.push((name, sequence));
+            }
+        }
+
+        let Some(count) = sequences.first().map(|(_, sequence)| sequence.len()) else {
+            return Err(EvalError::BadSyntax(format!("ellipsis in the template of `{}` follows no repeated pattern variable", self.name), index));
+        };
+        if sequences.iter().any(|(_, sequence)| sequence.len() != count) {
+            return Err(EvalError::BadSyntax(format!("pattern variables under one ellipsis matched different numbers of forms in `{}`", self.name), index));
+        }
+
+        for i in 0..count {
+            let mut inner = bindings.clone();
+            for (name, sequence) in &sequences {
+                inner.insert((*name).clone(), sequence[i].clone());
+            }
+            if depth == 1 {
+                result.push(self.instantiate(template, &inner, true, index)?);
+            } else {
+                self.repeat(template, &inner, depth - 1, result, index)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 卫生性：模板引入的局部绑定（`lambda`、`define`的形参及let族的变量）在每次展开时改用新名字，
+    // 以免捕获使用处传入的同名变量；被引用的数据及模式变量保持不变
+    fn rename(&self, template: &Datum, renames: &HashMap<String, String>, vars: &HashSet<String>) -> Datum {
+        let kind = match &template.kind {
+            DatumKind::Symbol(name) => match renames.get(name) {
+                Some(renamed) => DatumKind::Symbol(renamed.clone()),
+                None => return template.clone(),
+            },
+            DatumKind::List(items) if items.len() == 2 && self.is_ellipsis(&items[0]) => return template.clone(),
+            DatumKind::List(items) => {
+                let mut scope = renames.clone();
+                for binder in self.binders(items, vars) {
+                    let renamed = fresh_name(&binder);
+                    scope.insert(binder, renamed);
+                }
+                // let与let*的初始化表达式不在新变量的作用域内，由rename_bindings单独处理
+                let bindings = match (items.first().map(|head| &head.kind), items.get(1).map(|second| &second.kind)) {
+                    (Some(DatumKind::Symbol(head)), _) if vars.contains(head) => None,
+                    (Some(DatumKind::Symbol(head)), Some(DatumKind::Symbol(_))) if head == "let" => Some((2, false)),
+                    (Some(DatumKind::Symbol(head)), Some(_)) if head == "let" || head == "let*" => Some((1, head == "let*")),
+                    _ => None,
+                };
+                DatumKind::List(items.iter().enumerate().map(|(i, item)| match bindings {
+                    Some((position, sequential)) if i == position => self.rename_bindings(item, renames, &scope, vars, sequential),
+                    _ => self.rename(item, &scope, vars),
+                }).collect())
+            },
+            // 向量字面量是自求值的数据，其中的符号不是变量引用
+            DatumKind::Vector(_) => return template.clone(),
+            DatumKind::DottedList(items, tail) => DatumKind::DottedList(
+                items.iter().map(|item| self.rename(item, renames, vars)).collect(),
+                Box::new(self.rename(tail, renames, vars)),
+            ),
+            DatumKind::Quasiquote(inner) => DatumKind::Quasiquote(Box::new(self.rename_unquoted(inner, renames, vars, 1))),
+            _ => return template.clone(),
+        };
+        Datum { kind, index: template.index }
+    }
+
+    // `((name init) ...)`：变量名按scope改名；初始化表达式按外层的renames改名，
+    // sequential为真（let*）时还能看到其前各变量的新名字
+    fn rename_bindings(&self, bindings: &Datum, renames: &HashMap<String, String>, scope: &HashMap<String, String>, vars: &HashSet<String>, sequential: bool) -> Datum {
+        let DatumKind::List(items) = &bindings.kind else {
+            return self.rename(bindings, renames, vars);
+        };
+        let mut outer = renames.clone();
+        let items = items.iter().map(|binding| {
+            let DatumKind::List(pair) = &binding.kind else {
+                return self.rename(binding, &outer, vars);
+            };
+            let Some((name, inits)) = pair.split_first() else {
+                return binding.clone();
+            };
+            let renamed = [self.rename(name, scope, vars)].into_iter()
+                .chain(inits.iter().map(|init| self.rename(init, &outer, vars)))
+                .collect();
+            if let DatumKind::Symbol(name) = &name.kind && sequential && let Some(fresh) = scope.get(name) {
+                outer.insert(name.clone(), fresh.clone());
+            }
+            Datum { kind: DatumKind::List(renamed), index: binding.index }
+        }).collect();
+        Datum { kind: DatumKind::List(items), index: bindings.index }
+    }
+
+    fn rename_unquoted(&self, template: &Datum, renames: &HashMap<String, String>, vars: &HashSet<String>, depth: usize) -> Datum {
+        let kind = match &template.kind {
+            DatumKind::Unquote(inner) if depth == 1 => DatumKind::Unquote(Box::new(self.rename(inner, renames, vars))),
+            DatumKind::UnquoteSplicing(inner) if depth == 1 => DatumKind::UnquoteSplicing(Box::new(self.rename(inner, renames, vars))),
+            DatumKind::Unquote(inner) => DatumKind::Unquote(Box::new(self.rename_unquoted(inner, renames, vars, depth - 1))),
+            DatumKind::UnquoteSplicing(inner) => DatumKind::UnquoteSplicing(Box::new(self.rename_unquoted(inner, renames, vars, depth - 1))),
+            DatumKind::Quasiquote(inner) => DatumKind::Quasiquote(Box::new(self.rename_unquoted(inner, renames, vars, depth + 1))),
+            DatumKind::Quote(inner) => DatumKind::Quote(Box::new(self.rename_unquoted(inner, renames, vars, depth))),
+            DatumKind::List(items) => DatumKind::List(items.iter().map(|item| self.rename_unquoted(item, renames, vars, depth)).collect()),
+            DatumKind::Vector(items) => DatumKind::Vector(items.iter().map(|item| self.rename_unquoted(item, renames, vars, depth)).collect()),
+            DatumKind::DottedList(items, tail) => DatumKind::DottedList(
+                items.iter().map(|item| self.rename_unquoted(item, renames, vars, depth)).collect(),
+                Box::new(self.rename_unquoted(tail, renames, vars, depth)),
+            ),
+            _ => return template.clone(),
+        };
+        Datum { kind, index: template.index }
+    }
+
+    // 模板中的绑定形式所引入的名字（不含模式变量）；`define`定义的名字本身不改名
+    fn binders(&self, items: &[Datum], vars: &HashSet<String>) -> Vec<String> {
+        let mut binders = Vec::new();
+        let Some(DatumKind::Symbol(head)) = items.first().map(|head| &head.kind) else {
+            return binders;
+        };
+        if vars.contains(head) {
+            return binders;
+        }
+
+        let names: Vec<&Datum> = match (head.as_str(), items.get(1).map(|second| &second.kind)) {
+            ("lambda", Some(DatumKind::Symbol(_))) => vec![&items[1]],
+            ("lambda", Some(DatumKind::List(params))) => params.iter().collect(),
+            ("lambda", Some(DatumKind::DottedList(params, rest))) => params.iter().chain([rest.as_ref()]).collect(),
+            ("define", Some(DatumKind::List(signature))) => signature.iter().skip(1).collect(),
+            ("define", Some(DatumKind::DottedList(signature, rest))) => signature.iter().skip(1).chain([rest.as_ref()]).collect(),
+            ("let", Some(DatumKind::Symbol(_))) => [&items[1]].into_iter().chain(items.get(2).map(binding_names).unwrap_or_default()).collect(),
+            ("let" | "let*" | "letrec" | "letrec*", Some(_)) => binding_names(&items[1]),
+            _ => Vec::new(),
+        };
+        for name in names {
+            if let DatumKind::Symbol(name) = &name.kind && !vars.contains(name) && *name != self.ellipsis {
+                binders.push(name.clone());
+            }
+        }
+        binders
+    }
+}
+
+
+// 把`'x`等前缀形式视为`(quote x)`，返回列表的元素及（非正规列表的）末尾
+fn list_view(datum: &Datum) -> Option<(Vec<Datum>, Option<Datum>)> {
+    let (head, inner) = match &datum.kind {
+        DatumKind::List(items) => return Some((items.clone(), None)),
+        DatumKind::DottedList(items, tail) => return Some((items.clone(), Some(tail.as_ref().clone()))),
+        DatumKind::Quote(inner) => ("quote", inner),
+        DatumKind::Quasiquote(inner) => ("quasiquote", inner),
+        DatumKind::Unquote(inner) => ("unquote", inner),
+        DatumKind::UnquoteSplicing(inner) => ("unquote-splicing", inner),
+        _ => return None,
+    };
+    Some((vec![Datum { kind: DatumKind::Symbol(String::from(head)), index: datum.index }, inner.as_ref().clone()], None))
+}
+
+
+// 由元素与末尾组成列表；末尾为列表时合并为一个列表
+fn join(mut items: Vec<Datum>, tail: Option<Datum>, index: (usize, usize)) -> Datum {
+    let index = items.first().map_or(index, |first| first.index);
+    let kind = match tail.map(|tail| (tail.kind, tail.index)) {
+        None => DatumKind::List(items),
+        Some((DatumKind::List(rest), _)) => {
+            items.extend(rest);
+            DatumKind::List(items)
+        },
+        Some((DatumKind::DottedList(rest, tail), _)) => {
+            items.extend(rest);
+            DatumKind::DottedList(items, tail)
+        },
+        Some((kind, tail_index)) if items.is_empty() => return Datum { kind, index: tail_index },
+        Some((kind, tail_index)) => DatumKind::DottedList(items, Box::new(Datum { kind, index: tail_index })),
+    };
+    Datum { kind, index }
+}
+
+
+// `((name init) ...)`中的各个name
+fn binding_names(bindings: &Datum) -> Vec<&Datum> {
+    match &bindings.kind {
+        DatumKind::List(bindings) => bindings.iter().filter_map(|binding| match &binding.kind {
+            DatumKind::List(pair) => pair.first(),
+            _ => None,
+        }).collect(),
+        _ => Vec::new(),
+    }
+}
+
+
+fn collect_symbols<'a>(datum: &'a Datum, symbols: &mut Vec<&'a String>) {
+    match &datum.kind {
+        DatumKind::Symbol(name) => symbols.push(name),
+        DatumKind::List(items) | DatumKind::Vector(items) => items.iter().for_each(|item| collect_symbols(item, symbols)),
+        DatumKind::DottedList(items, tail) => {
+            items.iter().for_each(|item| collect_symbols(item, symbols));
+            collect_symbols(tail, symbols);
+        },
+        DatumKind::Quote(inner) | DatumKind::Quasiquote(inner) | DatumKind::Unquote(inner) | DatumKind::UnquoteSplicing(inner) => {
+            collect_symbols(inner, symbols);
+        },
+        DatumKind::Const(_) => (),
+    }
+}
@@ -0,0 +1,229 @@
+use crate::{Datum, EvalError, eval::{Env, eval_program}, parser::parse, scanner::scan};
+use super::{MacroEnv, expand_and_eval, expand_program};
+
+
+fn read(source: &str) -> Vec<Datum> {
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed: {}", source);
+    };
+    let Ok(program) = parse(&tokens, &token_table) else {
+        panic!("parse failed: {}", source);
+    };
+    program
+}
+
+
+fn try_expand(source: &str) -> Result<Vec<String>, EvalError> {
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed: {}", source);
+    };
+    let Ok(program) = parse(&tokens, &token_table) else {
+        panic!("parse failed: {}", source);
+    };
+    let expanded = expand_program(&program, &MacroEnv::global(&Env::global()))?;
+    Ok(expanded.iter().map(|datum| datum.to_string()).collect())
+}
+
+
+// 展开并求值一段程序，返回最后一个表达式的值的输出形式
+fn run(source: &str) -> String {
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed: {}", source);
+    };
+    let Ok(program) = parse(&tokens, &token_table) else {
+        panic!("parse failed: {}", source);
+    };
+    let Ok(expanded) = expand_program(&program, &MacroEnv::global(&Env::global())) else {
+        panic!("expand failed: {}", source);
+    };
+    match eval_program(&expanded, &Env::global()) {
+        Ok(value) => value.to_string(),
+        Err(_) => panic!("eval failed: {}", source),
+    }
+}
+
+
+#[test]
+fn ellipsis_matches_sequences() {
+    let my_list = "(define-syntax my-list (syntax-rules () ((_ x ...) (list x ...))))";
+    assert_eq!(try_expand(&format!("{} (my-list 1 (+ 1 1) 3) (my-list)", my_list)).ok(), Some(vec![
+        String::from("(list 1 (+ 1 1) 3)"),
+        String::from("(list)"),
+    ]));
+
+    // 嵌套的省略号及省略号之后的模式
+    let my_let = "
+        (define-syntax my-let
+          (syntax-rules ()
+            ((_ ((name value) ...) body ... last) ((lambda (name ...) body ... last) value ...))))";
+    assert_eq!(run(&format!("{} (my-let ((a 1) (b 2)) (display a) (+ a b))", my_let)), "3");
+    let flatten = "(define-syntax flat (syntax-rules () ((_ (x ...) ...) '(x ... ...))))";
+    assert_eq!(run(&format!("{} (flat (1 2) () (3))", flatten)), "(1 2 3)");
+
+    // 自定义省略号
+    let custom = "(define-syntax tuple (syntax-rules etc () ((_ x etc) (vector x etc))))";
+    assert_eq!(run(&format!("{} (tuple 1 2)", custom)), "#(1 2)");
+}
+
+
+#[test]
+fn literals_must_match_exactly() {
+    let with = "
+        (define-syntax with
+          (syntax-rules (in from)
+            ((_ x in lst body) (let ((x (car lst))) body))
+            ((_ x from lst body) (list 'from lst))))";
+    assert_eq!(run(&format!("{} (with y in '(3 2 1) (* y y))", with)), "9");
+    assert_eq!(run(&format!("{} (with y from '(1) y)", with)), "(from (1))");
+    let error = try_expand(&format!("{}\n(with y over '(1) y)", with)).err().map(|e| e.to_string());
+    assert_eq!(error.as_deref(), Some("bad syntax: no rule of macro `with` matches this form at 6:1"));
+}
+
+
+#[test]
+fn templates_keep_patterns_apart() {
+    let swap = "(define-syntax swap! (syntax-rules () ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))";
+    // 模板中引入的tmp不会捕获使用处的同名变量
+    assert_eq!(run(&format!("{} (define tmp 1) (define y 2) (swap! tmp y) (list tmp y)", swap)), "(2 1)");
+
+    // 模板中的自由名字指向宏定义处的绑定，不受使用处的局部变量影响
+    let my_or = "(define-syntax my-or (syntax-rules () ((_ a b) (let ((t a)) (if t t b)))))";
+    assert_eq!(run(&format!("{} (let ((t 5)) (my-or #f t))", my_or)), "5");
+}
+
+
+// 模板中let、let*及命名let的初始化表达式引用的是外层的同名变量
+#[test]
+fn renamed_binders_do_not_cover_their_inits() {
+    let shadow = "(define-syntax shadow (syntax-rules () ((_ v body) (let ((x v)) (let ((x (+ x 1))) (* x body))))))";
+    assert_eq!(run(&format!("{} (shadow 2 10)", shadow)), "30");
+
+    let sequential = "(define-syntax seq (syntax-rules () ((_ v) (let ((x v)) (let* ((x (+ x 1)) (y (* x 10))) (list x y))))))";
+    assert_eq!(run(&format!("{} (seq 1)", sequential)), "(2 20)");
+
+    let named = "(define-syntax down (syntax-rules () ((_ v) (let ((n v)) (let loop ((n n) (acc '())) (if (= n 0) acc (loop (- n 1) (cons n acc))))))))";
+    assert_eq!(run(&format!("{} (down 3)", named)), "(1 2 3)");
+}
+
+
+// 向量字面量中的符号是数据，不随绑定改名；准引用的向量中插入的仍是改名后的变量
+#[test]
+fn vector_templates_keep_their_symbols() {
+    let source = "(define-syntax vec (syntax-rules () ((_ e) (let ((x e)) (list #(x) `#(,x))))))";
+    assert_eq!(run(&format!("{} (vec 1)", source)), "(#(x) #(1))");
+}
+
+
+#[test]
+fn macros_are_scoped() {
+    let source = "
+        (define (f) 'procedure)
+        (list
+          (let-syntax ((f (syntax-rules () ((_) 'macro)))) (f))
+          (f)
+          (let ((f (lambda () 'shadowed))) (f)))";
+    assert_eq!(run(source), "(macro procedure shadowed)");
+
+    let recursive = "
+        (define-syntax my-and
+          (syntax-rules ()
+            ((_) #t)
+            ((_ e) e)
+            ((_ e r ...) (if e (my-and r ...) #f))))
+        (list (my-and) (my-and 1 2 3) (my-and 1 #f 3))";
+    assert_eq!(run(recursive), "(#t 3 #f)");
+}
+
+
+#[test]
+fn reports_malformed_macros_and_uses() {
+    let bad = |source: &str| matches!(try_expand(source), Err(EvalError::BadSyntax(..)));
+    assert!(bad("(define-syntax m (lambda (x) x))"));
+    assert!(bad("(define-syntax m (syntax-rules (1) ((_) 1)))"));
+    assert!(bad("(define-syntax m (syntax-rules () ((_ x ... y ...) 1)))"));
+    assert!(bad("(define-syntax m (syntax-rules () ((_ x) (x ...)))) (m 1)"));
+    assert!(bad("(define-syntax m (syntax-rules () ((_ a) a))) (m)"));
+    assert!(bad("(define-syntax loop (syntax-rules () ((_) (loop)))) (loop)"));
+}
+
+
+#[test]
+fn define_macro_transforms_unevaluated_arguments() {
+    let source = "
+        (define-macro (my-unless test . body) `(if ,test #f (begin ,@body)))
+        (list (my-unless #f 1 2) (my-unless #t (car '())))";
+    assert_eq!(run(source), "(2 #f)");
+    assert_eq!(
+        try_expand("(define-macro (swap a b) (list b a)) (swap 1 display)").ok(),
+        Some(vec![String::from("(display 1)")]),
+    );
+    // 变换过程体可以是任意表达式
+    let source = "
+        (define-macro (count-args . args) (let loop ((l args) (n 0)) (if (null? l) n (loop (cdr l) (+ n 1)))))
+        (count-args a b c)";
+    assert_eq!(run(source), "3");
+}
+
+
+#[test]
+fn gensym_names_do_not_capture() {
+    let source = "
+        (define-macro (my-or2 a b)
+          (let ((t (gensym)))
+            `(let ((,t ,a)) (if ,t ,t ,b))))
+        (define t 5)
+        (my-or2 #f t)";
+    assert_eq!(run(source), "5");
+    assert_eq!(run("(eq? (gensym) (gensym))"), "#f");
+}
+
+
+// gensym生成的符号无法由读入器写出
+#[test]
+fn gensym_names_are_unreadable() {
+    assert!(run("(gensym 'x)").starts_with("#:x."));
+}
+
+
+#[test]
+fn reports_invalid_macro_expansions() {
+    let bad = |source: &str| matches!(try_expand(source), Err(EvalError::BadSyntax(..)));
+    assert!(bad("(define-macro (m) car) (m)"));
+    assert!(bad("(display (define-macro (m) 1))"));
+    let error = try_expand("(define-macro (m x) (car x)) (m 1)").err().map(|e| e.message());
+    assert_eq!(error.as_deref(), Some("bad syntax: in expansion of macro `m`: type mismatch: `car` expected a pair, got 1"));
+}
+
+
+const HELPER: &str = "(define (helper x) (list 'quote x)) (define-macro (m x) (helper x))";
+
+
+#[test]
+fn transformer_calls_earlier_definition() {
+    let env = MacroEnv::global(&Env::global());
+    let Ok(result) = expand_and_eval(&read(&format!("{} (m (1 2))", HELPER)), &env) else {
+        panic!("eval failed");
+    };
+    assert_eq!(result.to_string(), "(1 2)");
+}
+
+
+// 交互式解释器中各次输入分别展开，共享同一环境
+#[test]
+fn transformer_calls_definition_from_earlier_input() {
+    let env = MacroEnv::global(&Env::global());
+    assert!(expand_and_eval(&read(HELPER), &env).is_ok());
+    let Ok(result) = expand_and_eval(&read("(m 1)"), &env) else {
+        panic!("eval failed");
+    };
+    assert_eq!(result.to_string(), "1");
+}
+
+
+#[test]
+fn expand_sees_procedure_definitions() {
+    let Ok(program) = expand_program(&read(&format!("{} (m 1)", HELPER)), &MacroEnv::global(&Env::global())) else {
+        panic!("expand failed");
+    };
+    assert_eq!(program.last().map(|datum| datum.to_string()).as_deref(), Some("(quote 1)"));
+}
@@ -0,0 +1,372 @@
+use crate::{Error, TokenType, cst::{NodeKind, SyntaxElement, SyntaxNode, SyntaxToken}, scanner::scan_lossless, parser::parse_lossless};
+#[cfg(test)]
+mod tests;
+
+
+// 以函数体方式缩进的特殊形式及其首行保留的参数个数（其余参数缩进2列）
+const BODY_FORMS: [(&str, usize); 17] = [
+    ("define", 1),
+    ("lambda", 1),
+    ("if", 1),
+    ("let", 1),
+    ("let*", 1),
+    ("letrec", 1),
+    ("letrec*", 1),
+    ("when", 1),
+    ("unless", 1),
+    ("begin", 0),
+    ("case", 1),
+    ("do", 2),
+    ("define-syntax", 1),
+    ("let-syntax", 1),
+    ("letrec-syntax", 1),
+    ("syntax-rules", 1),
+    ("define-macro", 1),
+];
+
+
+pub struct FormatOptions {
+    // 期望的最大行宽（按字符计数）
+    pub width: usize,
+}
+
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions { width: 80 }
+    }
+}
+
+
+// 格式化源码：
+// (1) 能在行宽内放下且不含注释的表达式写在一行内
+// (2) 否则define、lambda、if等特殊形式的函数体缩进2列，其余表达式的参数与第一个参数对齐
+// (3) 注释与（至多一行）空行原样保留，行尾注释仍留在行尾
+pub fn format(input: &str, options: &FormatOptions) -> Result<String, Error> {
+    let (tokens, token_table) = scan_lossless(input)?;
+    let program = parse_lossless(input, &tokens, &token_table)?;
+    Ok(Formatter { width: options.width }.program(&program))
+}
+
+
+// 列表内容按行组织后的单元
+enum Piece<'a> {
+    Form(&'a SyntaxElement),
+    // 注释；bool表示其与前一单元位于同一行
+    Comment(&'a SyntaxToken, bool),
+    BlankLine,
+}
+
+
+struct Formatter {
+    width: usize,
+}
+
+
+impl Formatter {
+    fn program(&self, program: &SyntaxNode) -> String {
+        let mut out = String::new();
+
+        for piece in pieces(&program.children) {
+            match piece {
+                Piece::Form(element) => {
+                    if !out.is_empty() {
+                        out.push('\n');
+                    }
+                    out.push_str(&self.render(element, 0, false));
+                },
+                Piece::Comment(comment, true) if !out.is_empty() => {
+                    out.push(' ');
+                    out.push_str(&comment.text);
+                },
+                Piece::Comment(comment, _) => {
+                    if !out.is_empty() {
+                        out.push('\n');
+                    }
+                    out.push_str(&comment.text);
+                },
+                Piece::BlankLine => out.push('\n'),
+            }
+        }
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    // 将element排版于第column列起，换行后的各行带有完整缩进；quoted表示element为被引用的数据
+    fn render(&self, element: &SyntaxElement, column: usize, quoted: bool) -> String {
+        let node = match element {
+            SyntaxElement::Token(token) => return token.text.clone(),
+            SyntaxElement::Node(node) => node,
+        };
+
+        if let Some(flat) = flat(element) && column + flat.chars().count() <= self.width && !multi_body(node, quoted) {
+            return flat;
+        }
+
+        match node.kind {
+            NodeKind::List if quoted => self.render_data(node, column),
+            NodeKind::List => self.render_list(node, column),
+            // 向量的元素总是数据
+            NodeKind::Vector => self.render_data(node, column),
+
+            // 前缀与其后表达式之间含注释时保留原文
+            _ => {
+                if node.children.iter().any(is_comment) {
+                    return node.text();
+                }
+                // 准引用中被反引用的部分仍为代码
+                let quoted = match node.kind {
+                    NodeKind::Quote | NodeKind::Quasiquote => true,
+                    NodeKind::Unquote | NodeKind::UnquoteSplicing => false,
+                    _ => quoted,
+                };
+                let mut out = String::new();
+                for (i, child) in node.significant_children().enumerate() {
+                    if i > 1 {
+                        out.push(' ');
+                    }
+                    let start = end_column(&out, column);
+                    out.push_str(&self.render(child, start, quoted));
+                }
+                out
+            },
+        }
+    }
+
+    fn render_list(&self, node: &SyntaxNode, column: usize) -> String {
+        // 去除首尾的括号
+        let inner = &node.children[1..node.children.len() - 1];
+        let pieces = pieces(inner);
+
+        // 首行可容纳的表达式个数及后续各行的缩进
+        let (slots, indent) = match pieces.first() {
+            Some(Piece::Form(SyntaxElement::Token(head))) if is_symbol(head) => match body_args(&head.text, &pieces) {
+                Some(n) => (1 + n, column + 2),
+                None => (2, column + 2 + head.text.chars().count()),
+            },
+            _ => (1, column + 1),
+        };
+
+        let mut out = String::from("(");
+        let mut on_first_line = true;
+        let mut placed = 0;
+        let mut ends_with_line_comment = false;
+        let mut after_dot = false;
+
+        for piece in pieces {
+            match piece {
+                Piece::Form(element) => {
+                    // `.`与其后的元素写在同一行
+                    if after_dot {
+                        out.push(' ');
+                    } else if on_first_line && placed < slots {
+                        if placed > 0 {
+                            out.push(' ');
+                        }
+                        placed += 1;
+                    } else {
+                        on_first_line = false;
+                        out.push('\n');
+                        out.push_str(&" ".repeat(indent));
+                    }
+                    let start = end_column(&out, column);
+                    out.push_str(&self.render(element, start, false));
+                    ends_with_line_comment = false;
+                    after_dot = is_dot(element);
+                },
+                Piece::Comment(comment, trailing) => {
+                    ends_with_line_comment = push_comment(&mut out, comment, trailing, indent);
+                    on_first_line = false;
+                    after_dot = false;
+                },
+                Piece::BlankLine => {
+                    out.push('\n');
+                    on_first_line = false;
+                },
+            }
+        }
+
+        close(out, ends_with_line_comment, indent)
+    }
+
+    // 被引用的数据列表或向量：各元素与第一个元素对齐，一行内尽量多放
+    fn render_data(&self, node: &SyntaxNode, column: usize) -> String {
+        let inner = &node.children[1..node.children.len() - 1];
+        let open = if node.kind == NodeKind::Vector { "#(" } else { "(" };
+        let indent = column + open.len();
+
+        let mut out = String::from(open);
+        // 当前行能否继续追加元素
+        let mut open_line = true;
+        let mut ends_with_line_comment = false;
+        let mut after_dot = false;
+
+        for piece in pieces(inner) {
+            match piece {
+                Piece::Form(element) => {
+                    let start = end_column(&out, column);
+                    let fits = flat(element).is_some_and(|flat| start + 1 + flat.chars().count() <= self.width);
+                    if out == open {
+                        // 首个元素紧随`(`或`#(`
+                    } else if after_dot || (open_line && fits) {
+                        out.push(' ');
+                    } else {
+                        out.push('\n');
+                        out.push_str(&" ".repeat(indent));
+                    }
+                    let start = end_column(&out, column);
+                    let rendered = self.render(element, start, true);
+                    open_line = !rendered.contains('\n');
+                    out.push_str(&rendered);
+                    ends_with_line_comment = false;
+                    after_dot = is_dot(element);
+                },
+                Piece::Comment(comment, trailing) => {
+                    ends_with_line_comment = push_comment(&mut out, comment, trailing, indent);
+                    open_line = false;
+                    after_dot = false;
+                },
+                Piece::BlankLine => {
+                    out.push('\n');
+                    open_line = false;
+                },
+            }
+        }
+
+        close(out, ends_with_line_comment, indent)
+    }
+}
+
+
+// 追加注释：行尾注释接在当前行后，其余注释另起一行；返回其是否为行注释
+fn push_comment(out: &mut String, comment: &SyntaxToken, trailing: bool, indent: usize) -> bool {
+    if trailing {
+        if out != "(" && out != "#(" {
+            out.push(' ');
+        }
+    } else {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent));
+    }
+    out.push_str(&comment.text);
+    comment.token_type == TokenType::LineComment
+}
+
+
+// 追加`)`；行注释之后的`)`须另起一行
+fn close(mut out: String, ends_with_line_comment: bool, indent: usize) -> String {
+    if ends_with_line_comment {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent));
+    }
+    out.push(')');
+    out
+}
+
+
+// 将子元素按行组织：丢弃空白符，记录注释是否位于行尾，连续空行合并为一行
+fn pieces(children: &[SyntaxElement]) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut newlines = 0;
+
+    for child in children {
+        match child {
+            SyntaxElement::Token(token) if token.token_type == TokenType::Newline => newlines += 1,
+            SyntaxElement::Token(token) if token.token_type == TokenType::Whitespace => (),
+            _ => {
+                if newlines >= 2 && !pieces.is_empty() {
+                    pieces.push(Piece::BlankLine);
+                }
+                match child {
+                    SyntaxElement::Token(token) if token.token_type.is_trivia() => pieces.push(Piece::Comment(token, newlines == 0)),
+                    _ => pieces.push(Piece::Form(child)),
+                }
+                newlines = 0;
+            },
+        }
+    }
+
+    pieces
+}
+
+
+// 单行形式；含注释或跨行的词法单元（如多行字符串）时返回None
+fn flat(element: &SyntaxElement) -> Option<String> {
+    match element {
+        SyntaxElement::Token(token) => {
+            if token.text.contains('\n') {
+                None
+            } else {
+                Some(token.text.clone())
+            }
+        },
+        SyntaxElement::Node(node) => {
+            if node.children.iter().any(is_comment) {
+                return None;
+            }
+            let parts = node.significant_children().map(flat).collect::<Option<Vec<String>>>()?;
+            match node.kind {
+                NodeKind::List => Some(format!("({})", parts[1..parts.len() - 1].join(" "))),
+                NodeKind::Vector => Some(format!("#({})", parts[1..parts.len() - 1].join(" "))),
+                // 前缀紧接其后的部分；表达式之前可有数据注释，以空格分隔
+                _ => Some(format!("{}{}", parts[0], parts[1..].join(" "))),
+            }
+        },
+    }
+}
+
+
+// 特殊形式在首行保留的参数个数；具名let（`(let name ((var init) ...) body...)`）保留2个
+fn body_args(head: &str, pieces: &[Piece]) -> Option<usize> {
+    let n = BODY_FORMS.iter().find(|(name, _)| *name == head).map(|(_, n)| *n)?;
+    if head == "let" && let Some(Piece::Form(SyntaxElement::Token(name))) = pieces.get(1) && is_symbol(name) {
+        return Some(2);
+    }
+    Some(n)
+}
+
+
+// 函数体含多个表达式的特殊形式（`if`的两个分支不算函数体），总是分行书写
+fn multi_body(node: &SyntaxNode, quoted: bool) -> bool {
+    if quoted || node.kind != NodeKind::List {
+        return false;
+    }
+    let inner = &node.children[1..node.children.len() - 1];
+    let pieces = pieces(inner);
+    match pieces.first() {
+        Some(Piece::Form(SyntaxElement::Token(head))) if is_symbol(head) && head.text != "if" => match body_args(&head.text, &pieces) {
+            Some(n) => pieces.iter().filter(|piece| matches!(piece, Piece::Form(_))).count() > n + 2,
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+
+fn is_symbol(token: &SyntaxToken) -> bool {
+    !matches!(token.token_type, TokenType::Const | TokenType::LParen | TokenType::VectorParen | TokenType::RParen | TokenType::Dot)
+        && !token.token_type.is_trivia()
+}
+
+
+fn is_dot(element: &SyntaxElement) -> bool {
+    matches!(element, SyntaxElement::Token(token) if token.token_type == TokenType::Dot)
+}
+
+
+fn is_comment(element: &SyntaxElement) -> bool {
+    matches!(element, SyntaxElement::Token(token) if matches!(token.token_type, TokenType::LineComment | TokenType::BlockComment))
+}
+
+
+// out排版于第column列起时，其末尾所在的列
+fn end_column(out: &str, column: usize) -> usize {
+    match out.rfind('\n') {
+        Some(i) => out[i + 1..].chars().count(),
+        None => column + out.chars().count(),
+    }
+}
+
@@ -0,0 +1,77 @@
+use crate::{Error, ParseError};
+use super::{FormatOptions, format};
+
+
+fn fmt(input: &str, width: usize) -> String {
+    format(input, &FormatOptions { width }).expect("format failed")
+}
+
+
+#[test]
+fn short_forms_collapse_onto_one_line() {
+    assert_eq!(fmt("(define   x\n   (+ 1\n 2))\n'(a   b)", 80), "(define x (+ 1 2))\n'(a b)\n");
+}
+
+
+// 特殊形式的函数体缩进2列，普通调用的参数与第一个参数对齐
+#[test]
+fn long_forms_indent_bodies_and_align_arguments() {
+    let input = "(define (f x) (let ((y (* x x)) (z (+ x 1))) (begin (display y) (newline) (list x y z 'long-symbol-name \"a string\"))))";
+    let expected = "\
+(define (f x)
+  (let ((y (* x x)) (z (+ x 1)))
+    (begin
+      (display y)
+      (newline)
+      (list x
+            y
+            z
+            'long-symbol-name
+            \"a string\"))))
+";
+    assert_eq!(fmt(input, 40), expected);
+    assert_eq!(fmt(expected, 40), expected);
+}
+
+
+#[test]
+fn comments_and_blank_lines_are_kept() {
+    let input = "; header\n\n\n(define x 1) ; trailing\n(f #| inner |# x)\n";
+    assert_eq!(fmt(input, 80), "; header\n\n(define x 1) ; trailing\n(f #| inner |#\n   x)\n");
+}
+
+
+#[test]
+fn syntax_errors_are_returned() {
+    assert_eq!(format("(a", &FormatOptions::default()), Err(Error::Parse(ParseError::UnclosedList((0, 0)))));
+}
+
+
+#[test]
+fn dots_stay_with_their_tail() {
+    assert_eq!(fmt("(a   .\n b)", 80), "(a . b)\n");
+    let input = "(define (log-all level . messages) (for-each display (cons level messages)))";
+    let expected = "\
+(define (log-all level
+                 . messages)
+  (for-each display
+            (cons level
+                  messages)))
+";
+    assert_eq!(fmt(input, 30), expected);
+}
+
+
+// 过长的`if`：条件与`if`同行，两个分支按函数体缩进
+#[test]
+fn long_if() {
+    let input = "(define (classify n) (if (and (number? n) (> n 1000000) (< n 2000000)) (string-append \"between one and two million\" \" indeed\") (string-append \"something else entirely, \" \"not in range\")))\n(if a b c)\n";
+    let expected = "\
+(define (classify n)
+  (if (and (number? n) (> n 1000000) (< n 2000000))
+    (string-append \"between one and two million\" \" indeed\")
+    (string-append \"something else entirely, \" \"not in range\")))
+(if a b c)
+";
+    assert_eq!(fmt(input, 80), expected);
+}
@@ -0,0 +1,62 @@
+use super::Json;
+
+
+#[test]
+fn prints_compact_json_in_insertion_order() {
+    let document = Json::object([
+        ("b", Json::Int(-3)),
+        ("a", Json::Array(vec![Json::Null, Json::Bool(true), Json::Float(2.5), Json::Float(1.0)])),
+        ("s", Json::str("q\"\\\n\t\u{1}é")),
+        ("o", Json::Object(Vec::new())),
+    ]);
+    assert_eq!(document.to_string(), r#"{"b":-3,"a":[null,true,2.5,1.0],"s":"q\"\\\n\t\u0001é","o":{}}"#);
+}
+
+
+// JSON不能表示的浮点数按Scheme写法输出为字符串
+#[test]
+fn prints_non_finite_floats_as_strings() {
+    let document = Json::Array(vec![Json::Float(f64::INFINITY), Json::Float(f64::NEG_INFINITY), Json::Float(f64::NAN)]);
+    assert_eq!(document.to_string(), r#"["+inf.0","-inf.0","+nan.0"]"#);
+}
+
+
+#[test]
+fn prints_the_same_structure_as_an_sexpr() {
+    let document = Json::object([
+        ("version", Json::Int(1)),
+        ("items", Json::Array(vec![Json::Bool(false), Json::Null, Json::str("a b")])),
+        ("empty", Json::Array(Vec::new())),
+    ]);
+    assert_eq!(document.to_sexpr(), r#"(version 1 items (#f () "a b") empty ())"#);
+}
+
+
+#[test]
+fn parses_json_text() {
+    let Ok(value) = Json::parse(r#" {"id": 7, "ok": [true, null, -1.5e2, "a\"\u00e9\ud83d\ude00"], "nested": {}} "#) else {
+        panic!("parse failed");
+    };
+    assert_eq!(value.get("id").and_then(Json::as_i64), Some(7));
+    let Some([Json::Bool(true), Json::Null, Json::Float(v), Json::Str(s)]) = value.get("ok").and_then(Json::as_array) else {
+        panic!("unexpected array: {}", value);
+    };
+    assert_eq!(*v, -150.0);
+    assert_eq!(s, "a\"é😀");
+    assert_eq!(value.get("nested"), Some(&Json::Object(Vec::new())));
+    assert_eq!(value.get("missing"), None);
+
+    // 输出的文本可被重新解析
+    assert_eq!(Json::parse(&value.to_string()).ok(), Some(value));
+}
+
+
+#[test]
+fn rejects_malformed_json_with_its_offset() {
+    let error = |text: &str| Json::parse(text).err().map(|e| e.to_string());
+    assert_eq!(error("[1, 2"), Some(String::from("invalid JSON at byte 5: expected `,` or `]`")));
+    assert_eq!(error("{\"a\" 1}"), Some(String::from("invalid JSON at byte 5: expected `:`")));
+    assert_eq!(error("\"\\ud83d\""), Some(String::from("invalid JSON at byte 7: unpaired surrogate")));
+    assert_eq!(error("1 2"), Some(String::from("invalid JSON at byte 2: trailing characters")));
+    assert!(error("").is_some());
+}
@@ -1,5 +1,21 @@
+use std::{error, fmt};
+
+use number::Number;
+
 pub mod scanner;
 pub mod parser;
+pub mod eval;
+pub mod expand;
+pub mod repl;
+pub mod diagnostics;
+pub mod json;
+pub mod report;
+pub mod cst;
+pub mod format;
+pub mod lsp;
+pub mod number;
+#[cfg(test)]
+mod tests;
 
 
 pub struct TokenUnit {
@@ -26,6 +42,11 @@ pub enum TokenType {
     Quote,
     QuoteMark,
 
+    // 准引用`` ` ``、反引用`,`及拼接反引用`,@`
+    QuasiquoteMark,
+    UnquoteMark,
+    UnquoteSplicingMark,
+
     // 算术运算符
     PlusOp,
     MulOp,
@@ -42,35 +63,395 @@ pub enum TokenType {
     // 界定符（不含双引号）
     LParen,
     RParen,
+    // 向量的左界定符`#(`
+    VectorParen,
+
+    // 点对记号`.`
+    Dot,
+
+    // 数据注释`#;`：忽略其后的一个表达式
+    DatumComment,
+
+    // trivia（仅由无损词法分析输出）
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
 }
 
 
-#[derive(Debug)]
+impl TokenType {
+    // 关键字与运算符的字面形式（Id、Const及界定符无固定字面形式）
+    pub fn lexeme(&self) -> Option<&'static str> {
+        match self {
+            TokenType::Define => Some("define"),
+            TokenType::If => Some("if"),
+            TokenType::List => Some("list"),
+            TokenType::Cons => Some("cons"),
+            TokenType::Lambda => Some("lambda"),
+            TokenType::Display => Some("display"),
+            TokenType::Quote => Some("quote"),
+            TokenType::QuoteMark => Some("'"),
+            TokenType::QuasiquoteMark => Some("`"),
+            TokenType::UnquoteMark => Some(","),
+            TokenType::UnquoteSplicingMark => Some(",@"),
+            TokenType::PlusOp => Some("+"),
+            TokenType::MulOp => Some("*"),
+            TokenType::MinusOp => Some("-"),
+            TokenType::DivOp => Some("/"),
+            TokenType::LessThan => Some("<"),
+            TokenType::GreaterThan => Some(">"),
+            TokenType::LessEq => Some("<="),
+            TokenType::GreaterEq => Some(">="),
+            TokenType::Eq => Some("="),
+            TokenType::LParen => Some("("),
+            TokenType::RParen => Some(")"),
+            TokenType::VectorParen => Some("#("),
+            TokenType::Dot => Some("."),
+            TokenType::DatumComment => Some("#;"),
+            TokenType::Id | TokenType::Const => None,
+            TokenType::Whitespace | TokenType::Newline | TokenType::LineComment | TokenType::BlockComment => None,
+        }
+    }
+
+    // 是否为作用于其后一个表达式的前缀记号：`'`、`` ` ``、`,`、`,@`
+    pub fn is_prefix(&self) -> bool {
+        matches!(self, TokenType::QuoteMark | TokenType::QuasiquoteMark | TokenType::UnquoteMark | TokenType::UnquoteSplicingMark)
+    }
+
+    // 是否为左界定符：`(`或`#(`
+    pub fn is_open(&self) -> bool {
+        matches!(self, TokenType::LParen | TokenType::VectorParen)
+    }
+
+    // 是否为不影响语义的空白符、换行符或注释
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenType::Whitespace | TokenType::Newline | TokenType::LineComment | TokenType::BlockComment)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TableItem {
     pub index: (usize, usize),
+    // 词法单元的字符数
+    pub len: usize,
     pub value: Option<ValueType>,
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ValueType {
-    Int(isize),
-    Float(f64),
+    Number(Number),
     Str(String),
     Bool(bool),
+    Char(char),
 }
 
 
+// 具名字符`#\name`
+pub const CHAR_NAMES: [(&str, char); 9] = [
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+    ("newline", '\n'),
+    ("null", '\0'),
+    ("return", '\r'),
+    ("space", ' '),
+    ("tab", '\t'),
+];
+
+
+// 语法树节点：index为该节点首个词法单元的(row, column)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Datum {
+    pub kind: DatumKind,
+    pub index: (usize, usize),
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatumKind {
+    // 常量
+    Const(ValueType),
+    // 标识符、特殊形式关键字及运算符
+    Symbol(String),
+    // `(start ...)`
+    List(Vec<Datum>),
+    // 非正规列表`(start ... . start)`：至少一个元素及末尾的cdr
+    DottedList(Vec<Datum>, Box<Datum>),
+    // `#(start ...)`
+    Vector(Vec<Datum>),
+    // `'start`
+    Quote(Box<Datum>),
+    // `` `start``
+    Quasiquote(Box<Datum>),
+    // `,start`
+    Unquote(Box<Datum>),
+    // `,@start`
+    UnquoteSplicing(Box<Datum>),
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ScanError {
     // 不会出现在Lisp中的字符
     InvalidCharacter((usize, usize)),
     // 不符合词法规则的串
     InvalidToken((usize, usize)),
+    // 未闭合的块注释（位置为`#|`所在处）
+    UnterminatedComment((usize, usize)),
+    // 字符串中不合法的转义序列（位置为`\`所在处）
+    InvalidEscape((usize, usize)),
+    // 未闭合的字符串（位置为起始双引号所在处）
+    UnterminatedString((usize, usize)),
 }
 
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ParseError {
     UnexpectedToken((usize, usize)),
     UnexpectedEndOfInput,
+    // 输入结束时仍未闭合的`(`
+    UnclosedList((usize, usize)),
+    // 位置不合法的`.`：须位于列表的至少一个元素之后，且其后恰有一个元素
+    MisplacedDot((usize, usize)),
     UnknownScanError,
 }
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    // 未绑定的标识符
+    UnboundVariable(String, (usize, usize)),
+    // 在初始化之前引用letrec绑定的变量
+    UninitializedVariable(String, (usize, usize)),
+    // 特殊形式的结构不合法
+    BadSyntax(String, (usize, usize)),
+    // 实参类型不符
+    TypeMismatch(String, (usize, usize)),
+    // 实参个数不符
+    ArityMismatch(String, (usize, usize)),
+    // 调用的对象不是过程
+    NotProcedure(String, (usize, usize)),
+    // 除数为零
+    DivisionByZero((usize, usize)),
+    // 下标越界
+    IndexOutOfRange(String, (usize, usize)),
+}
+
+
+// 词法、语法及求值阶段错误的统一包装
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Scan(ScanError),
+    Parse(ParseError),
+    Eval(EvalError),
+}
+
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Number(v) => write!(f, "{}", v),
+            ValueType::Str(v) => write!(f, "{:?}", v),
+            ValueType::Bool(true) => write!(f, "#t"),
+            ValueType::Bool(false) => write!(f, "#f"),
+            // 具名字符输出其名字，其余不可见字符输出码点
+            ValueType::Char(ch) => match CHAR_NAMES.iter().find(|(_, named)| named == ch) {
+                Some((name, _)) => write!(f, "#\\{}", name),
+                None if ch.is_control() || ch.is_whitespace() => write!(f, "#\\x{:x}", *ch as u32),
+                None => write!(f, "#\\{}", ch),
+            },
+        }
+    }
+}
+
+
+// 以S表达式形式输出语法树
+impl fmt::Display for Datum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            DatumKind::Const(value) => write!(f, "{}", value),
+            DatumKind::Symbol(name) => write!(f, "{}", name),
+            DatumKind::List(items) | DatumKind::Vector(items) => {
+                write!(f, "{}", if matches!(self.kind, DatumKind::Vector(_)) { "#(" } else { "(" })?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            },
+            DatumKind::DottedList(items, tail) => {
+                write!(f, "(")?;
+                for item in items {
+                    write!(f, "{} ", item)?;
+                }
+                write!(f, ". {})", tail)
+            },
+            DatumKind::Quote(datum) => write!(f, "'{}", datum),
+            DatumKind::Quasiquote(datum) => write!(f, "`{}", datum),
+            DatumKind::Unquote(datum) => write!(f, ",{}", datum),
+            DatumKind::UnquoteSplicing(datum) => write!(f, ",@{}", datum),
+        }
+    }
+}
+
+
+impl ScanError {
+    pub fn index(&self) -> (usize, usize) {
+        match self {
+            ScanError::InvalidCharacter(index)
+            | ScanError::InvalidToken(index)
+            | ScanError::UnterminatedComment(index)
+            | ScanError::InvalidEscape(index)
+            | ScanError::UnterminatedString(index) => *index,
+        }
+    }
+
+    // 不含位置的错误描述
+    pub fn message(&self) -> String {
+        let message = match self {
+            ScanError::InvalidCharacter(_) => "invalid character",
+            ScanError::InvalidToken(_) => "invalid token",
+            ScanError::UnterminatedComment(_) => "unterminated block comment",
+            ScanError::InvalidEscape(_) => "invalid escape sequence in string",
+            ScanError::UnterminatedString(_) => "unterminated string literal",
+        };
+        String::from(message)
+    }
+}
+
+
+impl ParseError {
+    // UnexpectedEndOfInput与UnknownScanError没有确定的位置
+    pub fn index(&self) -> Option<(usize, usize)> {
+        match self {
+            ParseError::UnexpectedToken(index) | ParseError::UnclosedList(index) | ParseError::MisplacedDot(index) => Some(*index),
+            ParseError::UnexpectedEndOfInput | ParseError::UnknownScanError => None,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::UnexpectedToken(_) => String::from("unexpected token"),
+            ParseError::UnexpectedEndOfInput => String::from("unexpected end of input"),
+            ParseError::UnclosedList((row, column)) => format!("expected `)` to close `(` opened at {}:{}", row + 1, column + 1),
+            ParseError::MisplacedDot(_) => String::from("misplaced `.`"),
+            ParseError::UnknownScanError => String::from("token table is inconsistent with the token sequence"),
+        }
+    }
+}
+
+
+impl EvalError {
+    pub fn index(&self) -> (usize, usize) {
+        match self {
+            EvalError::UnboundVariable(_, index)
+            | EvalError::UninitializedVariable(_, index)
+            | EvalError::BadSyntax(_, index)
+            | EvalError::TypeMismatch(_, index)
+            | EvalError::ArityMismatch(_, index)
+            | EvalError::NotProcedure(_, index)
+            | EvalError::DivisionByZero(index)
+            | EvalError::IndexOutOfRange(_, index) => *index,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            EvalError::UnboundVariable(name, _) => format!("unbound variable `{}`", name),
+            EvalError::UninitializedVariable(name, _) => format!("variable `{}` used before its initialization", name),
+            EvalError::BadSyntax(message, _) => format!("bad syntax: {}", message),
+            EvalError::TypeMismatch(message, _) => format!("type mismatch: {}", message),
+            EvalError::ArityMismatch(message, _) => format!("arity mismatch: {}", message),
+            EvalError::NotProcedure(value, _) => format!("`{}` is not a procedure", value),
+            EvalError::DivisionByZero(_) => String::from("division by zero"),
+            EvalError::IndexOutOfRange(message, _) => format!("index out of range: {}", message),
+        }
+    }
+}
+
+
+impl Error {
+    pub fn index(&self) -> Option<(usize, usize)> {
+        match self {
+            Error::Scan(e) => Some(e.index()),
+            Error::Parse(e) => e.index(),
+            Error::Eval(e) => Some(e.index()),
+        }
+    }
+}
+
+
+// 错误信息中的位置从1开始计数
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (row, column) = self.index();
+        write!(f, "{} at {}:{}", self.message(), row + 1, column + 1)
+    }
+}
+
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken((row, column)) | ParseError::MisplacedDot((row, column)) => {
+                write!(f, "{} at {}:{}", self.message(), row + 1, column + 1)
+            },
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (row, column) = self.index();
+        write!(f, "{} at {}:{}", self.message(), row + 1, column + 1)
+    }
+}
+
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Scan(e) => write!(f, "scan error: {}", e),
+            Error::Parse(e) => write!(f, "parse error: {}", e),
+            Error::Eval(e) => write!(f, "eval error: {}", e),
+        }
+    }
+}
+
+
+impl error::Error for ScanError {}
+impl error::Error for ParseError {}
+impl error::Error for EvalError {}
+
+
+// Display已包含内层错误的信息，不再经source()重复给出
+impl error::Error for Error {}
+
+
+impl From<ScanError> for Error {
+    fn from(e: ScanError) -> Error {
+        Error::Scan(e)
+    }
+}
+
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+
+impl From<EvalError> for Error {
+    fn from(e: EvalError) -> Error {
+        Error::Eval(e)
+    }
+}
@@ -0,0 +1,377 @@
+use std::{collections::HashMap, io::{self, BufRead, Write}};
+
+use crate::{
+    json::Json, eval::{Env, Value}, diagnostics::{Diagnostic, end_of_input, token_width},
+    format::{FormatOptions, format},
+};
+use utils::{Analysis, Definition, LineIndex};
+mod utils;
+#[cfg(test)]
+mod tests;
+
+
+// JSON-RPC错误码
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// 补全项与文档符号的种类（见LSP规范中的CompletionItemKind与SymbolKind）
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+const COMPLETION_KEYWORD: i64 = 14;
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+
+// 按名称识别的特殊形式
+const SPECIAL_FORMS: [&str; 23] = [
+    "define", "set!", "if", "lambda", "quote", "quasiquote", "unquote", "unquote-splicing",
+    "let", "let*", "letrec", "letrec*", "cond", "case", "and", "or", "when", "unless", "begin",
+    "define-syntax", "let-syntax", "syntax-rules", "define-macro",
+];
+
+
+// 经标准输入输出通信的语言服务器：文档以全量方式同步
+#[derive(Default)]
+pub struct Server {
+    // 已打开的文档：URI -> 文本
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+
+type RequestResult = Result<Json, (i64, String)>;
+
+
+impl Server {
+    // 处理消息直至`exit`或输入结束，返回退出前是否已收到`shutdown`
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, output: &mut W) -> io::Result<bool> {
+        while let Some(body) = read_message(&mut input)? {
+            let message = match Json::parse(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    write_message(output, &error_response(Json::Null, PARSE_ERROR, e.to_string()))?;
+                    continue;
+                },
+            };
+
+            let params = message.get("params").cloned().unwrap_or(Json::Null);
+            let Some(method) = message.get("method").and_then(Json::as_str) else {
+                // 客户端对服务器请求的响应；服务器不发出请求，直接忽略
+                continue;
+            };
+
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.request(method, &params) {
+                        Ok(result) => Json::object([("jsonrpc", Json::str("2.0")), ("id", id.clone()), ("result", result)]),
+                        Err((code, e)) => error_response(id.clone(), code, e),
+                    };
+                    write_message(output, &response)?;
+                },
+                None if method == "exit" => return Ok(self.shutdown),
+                None => {
+                    for notification in self.notify(method, &params) {
+                        write_message(output, &notification)?;
+                    }
+                },
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> RequestResult {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, String::from("server is shutting down")));
+        }
+
+        match method {
+            "initialize" => Ok(Json::object([
+                ("capabilities", Json::object([
+                    ("textDocumentSync", Json::Int(1)),
+                    ("definitionProvider", Json::Bool(true)),
+                    ("referencesProvider", Json::Bool(true)),
+                    ("hoverProvider", Json::Bool(true)),
+                    ("completionProvider", Json::object([("triggerCharacters", Json::Array(vec![Json::str("(")]))])),
+                    ("documentSymbolProvider", Json::Bool(true)),
+                    ("documentFormattingProvider", Json::Bool(true)),
+                ])),
+                ("serverInfo", Json::object([
+                    ("name", Json::str(env!("CARGO_PKG_NAME"))),
+                    ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+                ])),
+            ])),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            },
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/documentSymbol" => self.document_symbol(params),
+            "textDocument/formatting" => self.formatting(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    // 处理通知，返回需发送给客户端的通知
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let document = params.get("textDocument");
+        let Some(uri) = document.and_then(|document| document.get("uri")).and_then(Json::as_str) else {
+            return Vec::new();
+        };
+        let uri = String::from(uri);
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = document.and_then(|document| document.get("text")).and_then(Json::as_str).unwrap_or("");
+                self.documents.insert(uri.clone(), String::from(text));
+            },
+            "textDocument/didChange" => {
+                // 全量同步：最后一次变更即为完整文本
+                let changes = params.get("contentChanges").and_then(Json::as_array).unwrap_or(&[]);
+                let Some(text) = changes.iter().rev().find_map(|change| change.get("text").and_then(Json::as_str)) else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.clone(), String::from(text));
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            },
+            _ => return Vec::new(),
+        }
+
+        vec![publish_diagnostics(&uri, self.diagnostics(&self.documents[&uri]))]
+    }
+
+    // 词法错误；无词法错误时为语法错误
+    fn diagnostics(&self, text: &str) -> Vec<Json> {
+        let analysis = Analysis::new(text);
+        let lines = LineIndex::new(text);
+
+        let diagnostics: Vec<Diagnostic> = if analysis.scan_errors.is_empty() {
+            analysis.parse_errors.iter().map(Diagnostic::from).collect()
+        } else {
+            analysis.scan_errors.iter().map(Diagnostic::from).collect()
+        };
+
+        diagnostics.into_iter().map(|diagnostic| {
+            let start = diagnostic.index.unwrap_or_else(|| end_of_input(text));
+            let width = match diagnostic.index {
+                Some((row, column)) => token_width(text.lines().nth(row).unwrap_or(""), column),
+                None => 0,
+            };
+
+            Json::object([
+                ("range", lines.range(start, lines.advance(start, width))),
+                ("severity", Json::Int(1)),
+                ("source", Json::str(env!("CARGO_PKG_NAME"))),
+                ("message", Json::Str(diagnostic.message)),
+            ])
+        }).collect()
+    }
+
+    fn definition(&self, params: &Json) -> RequestResult {
+        let (uri, text) = self.document(params)?;
+        let (analysis, lines) = (Analysis::new(text), LineIndex::new(text));
+        let Some((name, index)) = symbol_at(&analysis, &lines, params) else {
+            return Ok(Json::Null);
+        };
+
+        Ok(match analysis.binding_of(index) {
+            Some(binding) => Json::Array(vec![location(uri, &lines, binding, &name)]),
+            None => Json::Null,
+        })
+    }
+
+    fn references(&self, params: &Json) -> RequestResult {
+        let (uri, text) = self.document(params)?;
+        let (analysis, lines) = (Analysis::new(text), LineIndex::new(text));
+        let Some((name, index)) = symbol_at(&analysis, &lines, params) else {
+            return Ok(Json::Null);
+        };
+
+        let include_declaration = !matches!(
+            params.get("context").and_then(|context| context.get("includeDeclaration")),
+            Some(Json::Bool(false))
+        );
+
+        Ok(Json::Array(analysis.references(index).into_iter()
+            .filter(|reference| include_declaration || !reference.declaration)
+            .map(|reference| location(uri, &lines, reference.index, &name))
+            .collect()))
+    }
+
+    // 用户定义显示其（格式化后的）定义，形参等局部变量、内建过程与特殊形式显示其类别
+    fn hover(&self, params: &Json) -> RequestResult {
+        let (_, text) = self.document(params)?;
+        let (analysis, lines) = (Analysis::new(text), LineIndex::new(text));
+        let Some((name, index)) = symbol_at(&analysis, &lines, params) else {
+            return Ok(Json::Null);
+        };
+
+        let binding = analysis.binding_of(index);
+        let contents = if let Some(definition) = binding.and_then(|binding| analysis.definition_at(binding)) {
+            let source = definition.form.to_string();
+            let formatted = format(&source, &FormatOptions::default()).unwrap_or(source);
+            format!("```scheme\n{}\n```", formatted.trim_end())
+        } else if binding.is_some() {
+            format!("local variable `{}`", name)
+        } else if matches!(Env::global().lookup(&name), Some(Value::Builtin(_))) {
+            format!("builtin procedure `{}`", name)
+        } else if SPECIAL_FORMS.contains(&name.as_str()) {
+            format!("special form `{}`", name)
+        } else {
+            return Ok(Json::Null);
+        };
+
+        Ok(Json::object([
+            ("contents", Json::object([("kind", Json::str("markdown")), ("value", Json::Str(contents))])),
+            ("range", lines.range(index, lines.advance(index, name.chars().count()))),
+        ]))
+    }
+
+    // 特殊形式、内建过程及文档中定义的名字
+    fn completion(&self, params: &Json) -> RequestResult {
+        let (_, text) = self.document(params)?;
+        let analysis = Analysis::new(text);
+
+        let mut items: Vec<(String, i64, String)> = Vec::new();
+        for name in SPECIAL_FORMS {
+            items.push((String::from(name), COMPLETION_KEYWORD, String::from("special form")));
+        }
+        for (name, _) in Env::global().bindings() {
+            items.push((name, COMPLETION_FUNCTION, String::from("builtin procedure")));
+        }
+        let mut pending = analysis.definitions();
+        while let Some(mut definition) = pending.pop() {
+            pending.append(&mut definition.children);
+            if !items.iter().any(|(name, ..)| *name == definition.name) {
+                let kind = if definition.procedure { COMPLETION_FUNCTION } else { COMPLETION_VARIABLE };
+                items.push((definition.name, kind, String::from("defined in this document")));
+            }
+        }
+
+        Ok(Json::Array(items.into_iter().map(|(label, kind, detail)| Json::object([
+            ("label", Json::Str(label)),
+            ("kind", Json::Int(kind)),
+            ("detail", Json::Str(detail)),
+        ])).collect()))
+    }
+
+    fn document_symbol(&self, params: &Json) -> RequestResult {
+        let (_, text) = self.document(params)?;
+        let (analysis, lines) = (Analysis::new(text), LineIndex::new(text));
+        Ok(Json::Array(analysis.definitions().iter().map(|definition| document_symbol(definition, &analysis, &lines)).collect()))
+    }
+
+    // 以一处编辑替换整个文档；存在词法或语法错误时不做修改
+    fn formatting(&self, params: &Json) -> RequestResult {
+        let (_, text) = self.document(params)?;
+        let formatted = match format(text, &FormatOptions::default()) {
+            Ok(formatted) => formatted,
+            Err(_) => return Ok(Json::Null),
+        };
+        if formatted == *text {
+            return Ok(Json::Array(Vec::new()));
+        }
+
+        let lines = LineIndex::new(text);
+        Ok(Json::Array(vec![Json::object([
+            ("range", lines.range((0, 0), lines.end())),
+            ("newText", Json::Str(formatted)),
+        ])]))
+    }
+
+    // 请求参数所指文档的URI及文本
+    fn document<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a String), (i64, String)> {
+        let uri = params.get("textDocument").and_then(|document| document.get("uri")).and_then(Json::as_str)
+            .ok_or_else(|| (INVALID_PARAMS, String::from("missing `textDocument.uri`")))?;
+        match self.documents.get(uri) {
+            Some(text) => Ok((uri, text)),
+            None => Err((INVALID_PARAMS, format!("document `{}` is not open", uri))),
+        }
+    }
+}
+
+
+// 请求参数中`position`处的符号
+fn symbol_at(analysis: &Analysis, lines: &LineIndex, params: &Json) -> Option<(String, (usize, usize))> {
+    let position = lines.index_of(params.get("position")?)?;
+    analysis.symbol_at(position)
+}
+
+
+fn location(uri: &str, lines: &LineIndex, index: (usize, usize), name: &str) -> Json {
+    Json::object([
+        ("uri", Json::str(uri)),
+        ("range", lines.range(index, lines.advance(index, name.chars().count()))),
+    ])
+}
+
+
+fn document_symbol(definition: &Definition, analysis: &Analysis, lines: &LineIndex) -> Json {
+    let name_end = lines.advance(definition.name_index, definition.name.chars().count());
+    Json::object([
+        ("name", Json::str(definition.name.as_str())),
+        ("kind", Json::Int(if definition.procedure { SYMBOL_FUNCTION } else { SYMBOL_VARIABLE })),
+        ("range", lines.range(definition.form.index, analysis.end_of(definition.form, lines))),
+        ("selectionRange", lines.range(definition.name_index, name_end)),
+        ("children", Json::Array(definition.children.iter().map(|child| document_symbol(child, analysis, lines)).collect())),
+    ])
+}
+
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object([
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str("textDocument/publishDiagnostics")),
+        ("params", Json::object([("uri", Json::str(uri)), ("diagnostics", Json::Array(diagnostics))])),
+    ])
+}
+
+
+fn error_response(id: Json, code: i64, message: String) -> Json {
+    Json::object([
+        ("jsonrpc", Json::str("2.0")),
+        ("id", id),
+        ("error", Json::object([("code", Json::Int(code)), ("message", Json::Str(message))])),
+    ])
+}
+
+
+// 读入一条消息的正文；输入结束时返回None
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            match length {
+                Some(_) => break,
+                None => continue,
+            }
+        }
+        if let Some((name, value)) = line.split_once(':') && name.eq_ignore_ascii_case("Content-Length") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+
+fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
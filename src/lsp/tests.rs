@@ -0,0 +1,281 @@
+use std::io::Cursor;
+
+use crate::json::Json;
+use super::{Server, utils::{Analysis, LineIndex}};
+
+
+// 为各条消息加上Content-Length头
+fn frame(messages: &[&str]) -> String {
+    messages.iter().map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body)).collect()
+}
+
+
+// 运行服务器，返回其是否正常退出及全部输出消息
+fn session(messages: &[&str]) -> (bool, Vec<Json>) {
+    let mut output = Vec::new();
+    let clean = Server::default().run(Cursor::new(frame(messages)), &mut output).expect("server failed");
+    let output = String::from_utf8(output).expect("output is not UTF-8");
+
+    let mut rest = output.as_str();
+    let mut replies = Vec::new();
+    while !rest.is_empty() {
+        let Some((header, body)) = rest.split_once("\r\n\r\n") else {
+            panic!("malformed output: {:?}", rest);
+        };
+        let length: usize = header.trim_start_matches("Content-Length: ").parse().expect("bad Content-Length");
+        replies.push(Json::parse(&body[..length]).expect("bad JSON"));
+        rest = &body[length..];
+    }
+    (clean, replies)
+}
+
+
+fn did_open(uri: &str, text: &str) -> String {
+    let document = Json::object([
+        ("uri", Json::str(uri)), ("languageId", Json::str("scheme")), ("version", Json::Int(1)), ("text", Json::str(text)),
+    ]);
+    Json::object([
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str("textDocument/didOpen")),
+        ("params", Json::object([("textDocument", document)])),
+    ]).to_string()
+}
+
+
+fn request(id: i64, method: &str, params: &str) -> String {
+    format!(r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#, id, method, params)
+}
+
+
+fn at(line: usize, character: usize) -> String {
+    format!(r#"{{"textDocument":{{"uri":"file:///a.lisp"}},"position":{{"line":{},"character":{}}}}}"#, line, character)
+}
+
+
+fn result(reply: &Json) -> &Json {
+    reply.get("result").expect("no result")
+}
+
+
+#[test]
+fn frames_messages_by_byte_length() {
+    let initialize = request(1, "initialize", r#"{"rootUri":"file:///é"}"#);
+    // 头部名称不区分大小写，并可含其他头部
+    let input = format!("content-length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}", initialize.len(), initialize);
+    let mut output = Vec::new();
+    assert!(!Server::default().run(Cursor::new(input), &mut output).expect("server failed"));
+
+    let output = String::from_utf8(output).expect("output is not UTF-8");
+    let (header, body) = output.split_once("\r\n\r\n").expect("no header");
+    assert_eq!(header, format!("Content-Length: {}", body.len()));
+    let reply = Json::parse(body).expect("bad JSON");
+    assert_eq!(reply.get("id"), Some(&Json::Int(1)));
+    assert_eq!(result(&reply).get("capabilities").and_then(|capabilities| capabilities.get("hoverProvider")), Some(&Json::Bool(true)));
+}
+
+
+#[test]
+fn reports_malformed_and_unknown_messages() {
+    let (_, replies) = session(&["{not json", &request(2, "workspace/nothing", "{}")]);
+    let code = |reply: &Json| reply.get("error").and_then(|error| error.get("code")).and_then(Json::as_i64);
+    assert_eq!(code(&replies[0]), Some(-32700));
+    assert_eq!(replies[0].get("id"), Some(&Json::Null));
+    assert_eq!(code(&replies[1]), Some(-32601));
+}
+
+
+#[test]
+fn shutdown_then_exit() {
+    let shutdown = request(1, "shutdown", "null");
+    let after = request(2, "textDocument/hover", &at(0, 0));
+    let exit = r#"{"jsonrpc":"2.0","method":"exit"}"#;
+
+    let (clean, replies) = session(&[&shutdown, &after, exit, &request(3, "shutdown", "null")]);
+    assert!(clean);
+    // exit之后的消息不再处理
+    assert_eq!(replies.len(), 2);
+    assert_eq!(result(&replies[0]), &Json::Null);
+    assert_eq!(replies[1].get("error").and_then(|error| error.get("code")).and_then(Json::as_i64), Some(-32600));
+
+    // 未经shutdown的exit及输入结束均视为异常退出
+    assert!(!session(&[exit]).0);
+    assert!(!session(&[]).0);
+}
+
+
+#[test]
+fn did_change_republishes_diagnostics() {
+    let change = |text: &str| Json::object([
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str("textDocument/didChange")),
+        ("params", Json::object([
+            ("textDocument", Json::object([("uri", Json::str("file:///a.lisp")), ("version", Json::Int(2))])),
+            ("contentChanges", Json::Array(vec![Json::object([("text", Json::str(text))])])),
+        ])),
+    ]).to_string();
+    let close = r#"{"jsonrpc":"2.0","method":"textDocument/didClose","params":{"textDocument":{"uri":"file:///a.lisp"}}}"#;
+
+    let (_, replies) = session(&[&did_open("file:///a.lisp", "(define x\n  (f 1)"), &change("(define x 1)"), &change("(f \"é\" {)"), close]);
+    let diagnostics: Vec<String> = replies.iter().map(|reply| reply.get("params").expect("no params").to_string()).collect();
+    assert_eq!(diagnostics, [
+        concat!(
+            r#"{"uri":"file:///a.lisp","diagnostics":[{"range":{"start":{"line":0,"character":0},"end":{"line":0,"character":1}},"#,
+            r#""severity":1,"source":"mini_lisp","message":"expected `)` to close `(` opened at 1:1"}]}"#,
+        ),
+        r#"{"uri":"file:///a.lisp","diagnostics":[]}"#,
+        concat!(
+            r#"{"uri":"file:///a.lisp","diagnostics":[{"range":{"start":{"line":0,"character":7},"end":{"line":0,"character":8}},"#,
+            r#""severity":1,"source":"mini_lisp","message":"invalid character"}]}"#,
+        ),
+        r#"{"uri":"file:///a.lisp","diagnostics":[]}"#,
+    ]);
+}
+
+
+// LSP位置按UTF-16码元计列：`😀`占2个码元
+#[test]
+fn positions_count_utf16_code_units() {
+    let text = "(define s \"😀\") (f s)";
+    let references = request(1, "textDocument/references", &at(0, 19));
+    let (_, replies) = session(&[&did_open("file:///a.lisp", text), &references]);
+
+    let ranges: Vec<String> = result(&replies[1]).as_array().expect("no locations").iter()
+        .map(|location| location.get("range").expect("no range").to_string())
+        .collect();
+    assert_eq!(ranges, [
+        r#"{"start":{"line":0,"character":8},"end":{"line":0,"character":9}}"#,
+        r#"{"start":{"line":0,"character":19},"end":{"line":0,"character":20}}"#,
+    ]);
+}
+
+
+#[test]
+fn definition_hover_and_symbols() {
+    let text = "(define (square n)\n  (* n n))\n(square 3)";
+    let (_, replies) = session(&[
+        &did_open("file:///a.lisp", text),
+        &request(1, "textDocument/definition", &at(2, 3)),
+        &request(2, "textDocument/hover", &at(2, 1)),
+        &request(3, "textDocument/hover", &at(1, 3)),
+        &request(4, "textDocument/documentSymbol", r#"{"textDocument":{"uri":"file:///a.lisp"}}"#),
+    ]);
+
+    assert_eq!(
+        result(&replies[1]).to_string(),
+        r#"[{"uri":"file:///a.lisp","range":{"start":{"line":0,"character":9},"end":{"line":0,"character":15}}}]"#,
+    );
+    let hover = |reply: &Json| result(reply).get("contents").and_then(|contents| contents.get("value")).and_then(Json::as_str).map(String::from);
+    assert_eq!(hover(&replies[2]).as_deref(), Some("```scheme\n(define (square n) (* n n))\n```"));
+    assert_eq!(hover(&replies[3]).as_deref(), Some("builtin procedure `*`"));
+    assert_eq!(result(&replies[4]).to_string(), concat!(
+        r#"[{"name":"square","kind":12,"range":{"start":{"line":0,"character":0},"end":{"line":1,"character":10}},"#,
+        r#""selectionRange":{"start":{"line":0,"character":9},"end":{"line":0,"character":15}},"children":[]}]"#,
+    ));
+}
+
+
+#[test]
+fn completion_offers_forms_builtins_and_definitions() {
+    let (_, replies) = session(&[
+        &did_open("file:///a.lisp", "(define (square n) (* n n))\n(define limit 10)\n("),
+        &request(1, "textDocument/completion", &at(2, 1)),
+    ]);
+    let items: Vec<(String, i64)> = result(&replies[1]).as_array().expect("no items").iter()
+        .map(|item| (
+            String::from(item.get("label").and_then(Json::as_str).expect("no label")),
+            item.get("kind").and_then(Json::as_i64).expect("no kind"),
+        ))
+        .collect();
+    for expected in [("define", 14), ("lambda", 14), ("car", 3), ("square", 3), ("limit", 6)] {
+        assert!(items.contains(&(String::from(expected.0), expected.1)), "missing {:?}", expected);
+    }
+}
+
+
+#[test]
+fn formatting_replaces_the_whole_document() {
+    let formatting = request(1, "textDocument/formatting", r#"{"textDocument":{"uri":"file:///a.lisp"},"options":{"tabSize":2,"insertSpaces":true}}"#);
+
+    let (_, replies) = session(&[&did_open("file:///a.lisp", "(define   x\n 1)\n;😀 end"), &formatting]);
+    assert_eq!(result(&replies[1]).to_string(), concat!(
+        r#"[{"range":{"start":{"line":0,"character":0},"end":{"line":2,"character":7}},"#,
+        r#""newText":"(define x 1)\n;😀 end\n"}]"#,
+    ));
+
+    let (_, replies) = session(&[&did_open("file:///a.lisp", "(define x 1)\n"), &formatting]);
+    assert_eq!(result(&replies[1]), &Json::Array(Vec::new()));
+
+    let (_, replies) = session(&[&did_open("file:///a.lisp", "(define x"), &formatting]);
+    assert_eq!(result(&replies[1]), &Json::Null);
+}
+
+
+// 同名的全局定义、形参与let变量各自独立
+#[test]
+fn references_follow_scope() {
+    let text = "(define x 1)\n(define (f x) (+ x 1))\n(let ((x 2)) x)\n(display x)\n'x\n";
+    let (_, replies) = session(&[
+        &did_open("file:///a.lisp", text),
+        &request(1, "textDocument/references", &at(0, 8)),
+        &request(2, "textDocument/references", &at(1, 17)),
+        &request(3, "textDocument/references", &at(2, 7)),
+    ]);
+
+    let starts = |reply: &Json| -> Vec<(i64, i64)> {
+        result(reply).as_array().expect("no locations").iter()
+            .map(|location| {
+                let start = location.get("range").and_then(|range| range.get("start")).expect("no start");
+                let position = |key| start.get(key).and_then(Json::as_i64).expect("bad position");
+                (position("line"), position("character"))
+            })
+            .collect()
+    };
+    assert_eq!(starts(&replies[1]), [(0, 8), (3, 9)]);
+    assert_eq!(starts(&replies[2]), [(1, 11), (1, 17)]);
+    assert_eq!(starts(&replies[3]), [(2, 7), (2, 13)]);
+}
+
+
+// 跳转到定义经由作用域解析：let变量指向其绑定，遮蔽全局定义的形参指向形参
+#[test]
+fn definition_follows_scope() {
+    let text = "(define x 1)\n(let ((y 2)) y)\n(define (f x) x)";
+    let (_, replies) = session(&[
+        &did_open("file:///a.lisp", text),
+        &request(1, "textDocument/definition", &at(1, 13)),
+        &request(2, "textDocument/definition", &at(2, 14)),
+    ]);
+    assert_eq!(
+        result(&replies[1]).to_string(),
+        r#"[{"uri":"file:///a.lisp","range":{"start":{"line":1,"character":7},"end":{"line":1,"character":8}}}]"#,
+    );
+    assert_eq!(
+        result(&replies[2]).to_string(),
+        r#"[{"uri":"file:///a.lisp","range":{"start":{"line":2,"character":11},"end":{"line":2,"character":12}}}]"#,
+    );
+}
+
+
+// 悬停经由作用域解析：局部变量遮蔽同名的内建过程，内部定义显示其自身
+#[test]
+fn hover_follows_scope() {
+    let text = "(let ((car 1)) car)\n(define (f) (define (g) 1) (g))";
+    let (_, replies) = session(&[
+        &did_open("file:///a.lisp", text),
+        &request(1, "textDocument/hover", &at(0, 16)),
+        &request(2, "textDocument/hover", &at(1, 28)),
+    ]);
+    let hover = |reply: &Json| result(reply).get("contents").and_then(|contents| contents.get("value")).and_then(Json::as_str).map(String::from);
+    assert_eq!(hover(&replies[1]).as_deref(), Some("local variable `car`"));
+    assert_eq!(hover(&replies[2]).as_deref(), Some("```scheme\n(define (g) 1)\n```"));
+}
+
+
+// 引用、准引用与反引用等前缀形式的末尾是其后表达式的末尾
+#[test]
+fn prefix_forms_end_after_their_datum() {
+    let text = "'(a b) `(c ,d) ,(e) ,@(f g)";
+    let (analysis, lines) = (Analysis::new(text), LineIndex::new(text));
+    let ends: Vec<(usize, usize)> = analysis.program.iter().map(|datum| analysis.end_of(datum, &lines)).collect();
+    assert_eq!(ends, [(0, 6), (0, 14), (0, 19), (0, 27)]);
+}
@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+
+use crate::{Datum, DatumKind, ParseError, ScanError, TokenType, json::Json, scanner::scan_all, parser::parse_all};
+
+
+// 源码中字符位置与LSP位置（行号及UTF-16码元偏移）的换算
+pub struct LineIndex {
+    chars: Vec<char>,
+    // 各行首字符的字符偏移
+    line_starts: Vec<usize>,
+}
+
+
+impl LineIndex {
+    pub fn new(source: &str) -> LineIndex {
+        let chars: Vec<char> = source.chars().collect();
+        let mut line_starts = vec![0];
+        line_starts.extend(chars.iter().enumerate().filter(|(_, ch)| **ch == '\n').map(|(i, _)| i + 1));
+        LineIndex { chars, line_starts }
+    }
+
+    // 自index起跨过len个字符后的位置
+    pub fn advance(&self, index: (usize, usize), len: usize) -> (usize, usize) {
+        self.index(self.offset(index) + len)
+    }
+
+    // 输入末尾的位置
+    pub fn end(&self) -> (usize, usize) {
+        self.index(self.chars.len())
+    }
+
+    pub fn to_lsp(&self, (row, column): (usize, usize)) -> Json {
+        let start = self.line_starts.get(row).copied().unwrap_or(self.chars.len());
+        let end = self.offset((row, column));
+        let character: usize = self.chars[start..end].iter().map(|ch| ch.len_utf16()).sum();
+        Json::object([("line", Json::Int(row as i64)), ("character", Json::Int(character as i64))])
+    }
+
+    pub fn index_of(&self, position: &Json) -> Option<(usize, usize)> {
+        let row = position.get("line")?.as_i64()? as usize;
+        let character = position.get("character")?.as_i64()? as usize;
+        let start = *self.line_starts.get(row)?;
+
+        let mut units = 0;
+        let column = self.chars[start..].iter()
+            .take_while(|ch| {
+                let inside = units < character && **ch != '\n';
+                units += ch.len_utf16();
+                inside
+            })
+            .count();
+        Some((row, column))
+    }
+
+    pub fn range(&self, start: (usize, usize), end: (usize, usize)) -> Json {
+        Json::object([("start", self.to_lsp(start)), ("end", self.to_lsp(end))])
+    }
+
+    fn offset(&self, (row, column): (usize, usize)) -> usize {
+        self.line_starts.get(row).map_or(self.chars.len(), |start| (start + column).min(self.chars.len()))
+    }
+
+    fn index(&self, offset: usize) -> (usize, usize) {
+        let row = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        (row, offset - self.line_starts[row])
+    }
+}
+
+
+// 一个文档的分析结果（带错误恢复，出错时仍尽量提供语法树）
+pub struct Analysis {
+    pub program: Vec<Datum>,
+    pub scan_errors: Vec<ScanError>,
+    pub parse_errors: Vec<ParseError>,
+    // 各词法单元的类型、位置与字符数，按出现顺序排列
+    spans: Vec<(TokenType, (usize, usize), usize)>,
+}
+
+
+// `define`、`define-syntax`或`define-macro`引入的一个名字
+pub struct Definition<'a> {
+    pub name: String,
+    // 被定义的名字所在的位置
+    pub name_index: (usize, usize),
+    // 整个`define`表达式
+    pub form: &'a Datum,
+    // 是否定义为过程
+    pub procedure: bool,
+    // 过程体内部的定义
+    pub children: Vec<Definition<'a>>,
+}
+
+
+// 符号的一次出现
+pub struct Reference {
+    pub index: (usize, usize),
+    // 是否为引入绑定的位置
+    pub declaration: bool,
+}
+
+
+impl Analysis {
+    pub fn new(source: &str) -> Analysis {
+        let (tokens, token_table, scan_errors) = scan_all(source);
+        let (program, parse_errors) = parse_all(&tokens, &token_table);
+        let spans = tokens.iter()
+            .filter_map(|token| token_table.get(token.table_ptr).map(|item| (token.token_type, item.index, item.len)))
+            .collect();
+        Analysis { program, scan_errors, parse_errors, spans }
+    }
+
+    // 按嵌套层次组织的全部定义
+    pub fn definitions(&self) -> Vec<Definition<'_>> {
+        let mut definitions = Vec::new();
+        collect_definitions(&self.program, &mut definitions);
+        definitions
+    }
+
+    // 位于index的符号所指的绑定的位置；None表示名字未在文档中绑定
+    pub fn binding_of(&self, index: (usize, usize)) -> Option<(usize, usize)> {
+        self.occurrences().into_iter().find(|occurrence| occurrence.index == index)?.binding
+    }
+
+    // 位于binding处的名字由`define`等引入时，返回该定义
+    pub fn definition_at(&self, binding: (usize, usize)) -> Option<Definition<'_>> {
+        let mut pending = self.definitions();
+        while let Some(mut definition) = pending.pop() {
+            if definition.name_index == binding {
+                return Some(definition);
+            }
+            pending.append(&mut definition.children);
+        }
+        None
+    }
+
+    // 与位于index的符号指向同一绑定的全部出现（不含被引用的数据）
+    pub fn references(&self, index: (usize, usize)) -> Vec<Reference> {
+        let occurrences = self.occurrences();
+        let Some(target) = occurrences.iter().find(|occurrence| occurrence.index == index) else {
+            return Vec::new();
+        };
+        occurrences.iter()
+            .filter(|occurrence| occurrence.name == target.name && occurrence.binding == target.binding)
+            .map(|occurrence| Reference { index: occurrence.index, declaration: occurrence.declaration })
+            .collect()
+    }
+
+    // 文档中全部符号的出现及其所指的绑定
+    fn occurrences(&self) -> Vec<Occurrence<'_>> {
+        let mut resolver = Resolver { scopes: vec![HashMap::new()], occurrences: Vec::new() };
+        resolver.body(&self.program);
+        resolver.occurrences
+    }
+
+    // 覆盖位置index（含紧随其后的位置）的符号
+    pub fn symbol_at(&self, index: (usize, usize)) -> Option<(String, (usize, usize))> {
+        let mut symbols = Vec::new();
+        collect_symbols(&self.program, &mut symbols);
+        symbols.into_iter()
+            .find(|(name, (row, column))| *row == index.0 && *column <= index.1 && index.1 <= column + name.chars().count())
+            .map(|(name, index)| (String::from(name), index))
+    }
+
+    // 表达式末尾之后的位置
+    pub fn end_of(&self, datum: &Datum, lines: &LineIndex) -> (usize, usize) {
+        // 前缀形式止于其后的表达式
+        if let DatumKind::Quote(inner) | DatumKind::Quasiquote(inner) | DatumKind::Unquote(inner) | DatumKind::UnquoteSplicing(inner) = &datum.kind {
+            return self.end_of(inner, lines);
+        }
+
+        let start = self.spans.partition_point(|(_, index, _)| *index < datum.index);
+        let Some((token_type, index, len)) = self.spans.get(start) else {
+            return lines.end();
+        };
+        if !token_type.is_open() {
+            return lines.advance(*index, *len);
+        }
+
+        // 匹配的右括号；列表未闭合时延伸至最后一个词法单元
+        let mut depth = 0;
+        for (token_type, index, len) in &self.spans[start..] {
+            match token_type {
+                TokenType::LParen | TokenType::VectorParen => depth += 1,
+                TokenType::RParen => depth -= 1,
+                _ => (),
+            }
+            if depth == 0 {
+                return lines.advance(*index, *len);
+            }
+        }
+        self.spans.last().map_or(lines.end(), |(_, index, len)| lines.advance(*index, *len))
+    }
+}
+
+
+fn collect_definitions<'a>(datums: &'a [Datum], definitions: &mut Vec<Definition<'a>>) {
+    for datum in datums {
+        let DatumKind::List(items) = &datum.kind else {
+            continue;
+        };
+
+        match definition(datum, items) {
+            Some(mut definition) => {
+                collect_definitions(&items[2..], &mut definition.children);
+                definitions.push(definition);
+            },
+            None if !is_quote_form(items) => collect_definitions(items, definitions),
+            None => (),
+        }
+    }
+}
+
+
+// `(define name expr)`或`(define (name params ...) body ...)`（形参表可为非正规列表），以及`(define-syntax name transformer)`、`(define-macro (name params ...) body ...)`
+fn definition<'a>(datum: &'a Datum, items: &'a [Datum]) -> Option<Definition<'a>> {
+    if !matches!(items.first().map(|head| &head.kind), Some(DatumKind::Symbol(head)) if ["define", "define-syntax", "define-macro"].contains(&head.as_str())) {
+        return None;
+    }
+
+    let (name, name_index, procedure) = match &items.get(1)?.kind {
+        DatumKind::Symbol(name) => {
+            let procedure = matches!(
+                items.get(2).map(|value| &value.kind),
+                Some(DatumKind::List(value)) if matches!(value.first().map(|head| &head.kind), Some(DatumKind::Symbol(head)) if head == "lambda")
+            );
+            (name, items[1].index, procedure)
+        },
+        DatumKind::List(signature) | DatumKind::DottedList(signature, _) => match signature.first() {
+            Some(Datum { kind: DatumKind::Symbol(name), index }) => (name, *index, true),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(Definition { name: name.clone(), name_index, form: datum, procedure, children: Vec::new() })
+}
+
+
+// 符号的一次出现及其所指的绑定：绑定为引入该名字的位置，None表示名字未在文档中绑定（如内建过程）
+struct Occurrence<'a> {
+    name: &'a str,
+    index: (usize, usize),
+    binding: Option<(usize, usize)>,
+    declaration: bool,
+}
+
+
+// 按词法作用域把各符号解析到其最内层的绑定：`define`、`define-macro`、`define-syntax`、`lambda`与let族的形参
+struct Resolver<'a> {
+    // 由外向内的各层作用域：名字 -> 绑定位置
+    scopes: Vec<HashMap<&'a str, (usize, usize)>>,
+    occurrences: Vec<Occurrence<'a>>,
+}
+
+
+impl<'a> Resolver<'a> {
+    // 在最内层作用域中绑定符号；同一层中重复定义的名字指向首次定义
+    fn bind(&mut self, datum: &'a Datum) {
+        let DatumKind::Symbol(name) = &datum.kind else {
+            return;
+        };
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        let binding = *scope.entry(name).or_insert(datum.index);
+        self.occurrences.push(Occurrence { name, index: datum.index, binding: Some(binding), declaration: true });
+    }
+
+    fn reference(&mut self, name: &'a str, index: (usize, usize)) {
+        let binding = self.lookup(name);
+        self.occurrences.push(Occurrence { name, index, binding, declaration: false });
+    }
+
+    fn lookup(&self, name: &str) -> Option<(usize, usize)> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    // 形参表：符号、正规列表或非正规列表
+    fn params(&mut self, params: &'a Datum) {
+        match &params.kind {
+            DatumKind::Symbol(_) => self.bind(params),
+            DatumKind::List(items) => items.iter().for_each(|param| self.bind(param)),
+            DatumKind::DottedList(items, tail) => {
+                items.iter().for_each(|param| self.bind(param));
+                self.bind(tail);
+            },
+            _ => (),
+        }
+    }
+
+    // 顶层或过程体：其中的定义（含`begin`中的）作用于整个序列，先行绑定
+    fn body(&mut self, datums: &'a [Datum]) {
+        self.declare(datums);
+        datums.iter().for_each(|datum| self.expr(datum));
+    }
+
+    fn declare(&mut self, datums: &'a [Datum]) {
+        for datum in datums {
+            let DatumKind::List(items) = &datum.kind else {
+                continue;
+            };
+            if definition(datum, items).is_some() {
+                match &items[1].kind {
+                    DatumKind::List(signature) | DatumKind::DottedList(signature, _) => self.bind(&signature[0]),
+                    _ => self.bind(&items[1]),
+                }
+            } else if self.special(items) == Some("begin") {
+                self.declare(&items[1..]);
+            }
+        }
+    }
+
+    // 在新的作用域中绑定形参并处理过程体
+    fn scoped(&mut self, params: Option<&'a Datum>, body: &'a [Datum]) {
+        self.scopes.push(HashMap::new());
+        if let Some(params) = params {
+            self.params(params);
+        }
+        self.body(body);
+        self.scopes.pop();
+    }
+
+    // 未被局部变量遮蔽的特殊形式名
+    fn special(&self, items: &'a [Datum]) -> Option<&'a str> {
+        match items.first().map(|head| &head.kind) {
+            Some(DatumKind::Symbol(name)) if self.lookup(name).is_none() => Some(name),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, datum: &'a Datum) {
+        match &datum.kind {
+            DatumKind::Symbol(name) => self.reference(name, datum.index),
+            DatumKind::Quasiquote(template) => self.unquoted(template),
+            DatumKind::Unquote(inner) | DatumKind::UnquoteSplicing(inner) => self.expr(inner),
+            DatumKind::DottedList(items, tail) => {
+                items.iter().for_each(|item| self.expr(item));
+                self.expr(tail);
+            },
+            DatumKind::List(items) => self.form(items),
+            _ => (),
+        }
+    }
+
+    // 结构不合法的特殊形式按一般的组合处理
+    fn form(&mut self, items: &'a [Datum]) {
+        let rest = items.get(1..).unwrap_or_default();
+        match (self.special(items), rest) {
+            (Some("quote"), _) => (),
+            // 名字已在所在的作用域中绑定
+            (Some("define" | "define-macro"), [Datum { kind: DatumKind::List(signature) | DatumKind::DottedList(signature, _), .. }, body @ ..]) if !signature.is_empty() => {
+                self.scopes.push(HashMap::new());
+                signature[1..].iter().for_each(|param| self.bind(param));
+                if let DatumKind::DottedList(_, tail) = &rest[0].kind {
+                    self.bind(tail);
+                }
+                self.body(body);
+                self.scopes.pop();
+            },
+            (Some("define"), [Datum { kind: DatumKind::Symbol(_), .. }, value @ ..]) => value.iter().for_each(|value| self.expr(value)),
+            // 宏的变换规则是数据
+            (Some("define-syntax" | "syntax-rules"), _) => (),
+            (Some("lambda"), [params, body @ ..]) => self.scoped(Some(params), body),
+            (Some("let-syntax"), [Datum { kind: DatumKind::List(bindings), .. }, body @ ..]) => {
+                self.scopes.push(HashMap::new());
+                bindings.iter().filter_map(binding_parts).for_each(|(name, _)| self.bind(name));
+                self.body(body);
+                self.scopes.pop();
+            },
+            (Some("let"), [name @ Datum { kind: DatumKind::Symbol(_), .. }, Datum { kind: DatumKind::List(bindings), .. }, body @ ..]) => {
+                bindings.iter().filter_map(binding_parts).for_each(|(_, init)| init.iter().for_each(|init| self.expr(init)));
+                self.scopes.push(HashMap::new());
+                self.bind(name);
+                self.scoped_bindings(bindings, body);
+                self.scopes.pop();
+            },
+            (Some("let"), [Datum { kind: DatumKind::List(bindings), .. }, body @ ..]) => {
+                bindings.iter().filter_map(binding_parts).for_each(|(_, init)| init.iter().for_each(|init| self.expr(init)));
+                self.scoped_bindings(bindings, body);
+            },
+            (Some("let*"), [Datum { kind: DatumKind::List(bindings), .. }, body @ ..]) => {
+                let depth = self.scopes.len();
+                for (name, init) in bindings.iter().filter_map(binding_parts) {
+                    init.iter().for_each(|init| self.expr(init));
+                    self.scopes.push(HashMap::new());
+                    self.bind(name);
+                }
+                self.scoped(None, body);
+                self.scopes.truncate(depth);
+            },
+            (Some("letrec" | "letrec*"), [Datum { kind: DatumKind::List(bindings), .. }, body @ ..]) => {
+                self.scopes.push(HashMap::new());
+                let bindings: Vec<_> = bindings.iter().filter_map(binding_parts).collect();
+                bindings.iter().for_each(|(name, _)| self.bind(name));
+                bindings.iter().for_each(|(_, init)| init.iter().for_each(|init| self.expr(init)));
+                self.body(body);
+                self.scopes.pop();
+            },
+            // 各子句开头的数据列表不是表达式
+            (Some("case"), [key, clauses @ ..]) => {
+                self.expr(key);
+                for clause in clauses {
+                    match &clause.kind {
+                        DatumKind::List(clause) => clause.iter().skip(1).for_each(|item| self.expr(item)),
+                        _ => self.expr(clause),
+                    }
+                }
+            },
+            _ => items.iter().for_each(|item| self.expr(item)),
+        }
+    }
+
+    // 在新的作用域中绑定let的各变量并处理过程体
+    fn scoped_bindings(&mut self, bindings: &'a [Datum], body: &'a [Datum]) {
+        self.scopes.push(HashMap::new());
+        bindings.iter().filter_map(binding_parts).for_each(|(name, _)| self.bind(name));
+        self.body(body);
+        self.scopes.pop();
+    }
+
+    // 准引用模板中被反引用的部分（不区分嵌套层数）
+    fn unquoted(&mut self, template: &'a Datum) {
+        match &template.kind {
+            DatumKind::Unquote(inner) | DatumKind::UnquoteSplicing(inner) => self.expr(inner),
+            DatumKind::List(items) | DatumKind::Vector(items) => items.iter().for_each(|item| self.unquoted(item)),
+            DatumKind::DottedList(items, tail) => {
+                items.iter().for_each(|item| self.unquoted(item));
+                self.unquoted(tail);
+            },
+            DatumKind::Quote(inner) | DatumKind::Quasiquote(inner) => self.unquoted(inner),
+            _ => (),
+        }
+    }
+}
+
+
+// let族的一个绑定`(name init)`（init可省略）
+fn binding_parts(binding: &Datum) -> Option<(&Datum, &[Datum])> {
+    match &binding.kind {
+        DatumKind::List(items) if matches!(items.first().map(|name| &name.kind), Some(DatumKind::Symbol(_))) => Some((&items[0], &items[1..])),
+        DatumKind::Symbol(_) => Some((binding, &[])),
+        _ => None,
+    }
+}
+
+
+// 收集被引用的数据之外的全部符号
+fn collect_symbols<'a>(datums: &'a [Datum], symbols: &mut Vec<(&'a str, (usize, usize))>) {
+    for datum in datums {
+        match &datum.kind {
+            DatumKind::Symbol(name) => symbols.push((name, datum.index)),
+            DatumKind::List(items) if !is_quote_form(items) => collect_symbols(items, symbols),
+            DatumKind::DottedList(items, tail) => {
+                collect_symbols(items, symbols);
+                collect_symbols(std::slice::from_ref(tail.as_ref()), symbols);
+            },
+            DatumKind::Quasiquote(template) => collect_unquoted(template, symbols),
+            DatumKind::Unquote(inner) | DatumKind::UnquoteSplicing(inner) => collect_symbols(std::slice::from_ref(inner.as_ref()), symbols),
+            _ => (),
+        }
+    }
+}
+
+
+// 收集准引用模板中被反引用部分的符号（不区分嵌套层数）
+fn collect_unquoted<'a>(template: &'a Datum, symbols: &mut Vec<(&'a str, (usize, usize))>) {
+    match &template.kind {
+        DatumKind::Unquote(inner) | DatumKind::UnquoteSplicing(inner) => collect_symbols(std::slice::from_ref(inner.as_ref()), symbols),
+        DatumKind::List(items) | DatumKind::Vector(items) => items.iter().for_each(|item| collect_unquoted(item, symbols)),
+        DatumKind::DottedList(items, tail) => {
+            items.iter().for_each(|item| collect_unquoted(item, symbols));
+            collect_unquoted(tail, symbols);
+        },
+        DatumKind::Quote(inner) | DatumKind::Quasiquote(inner) => collect_unquoted(inner, symbols),
+        _ => (),
+    }
+}
+
+
+// `(quote datum)`
+fn is_quote_form(items: &[Datum]) -> bool {
+    matches!(items.first().map(|head| &head.kind), Some(DatumKind::Symbol(head)) if head == "quote")
+}
@@ -1,12 +1,20 @@
-use std::{ path::PathBuf, fs, io::{self, Write}, process };
+use std::{ path::{Path, PathBuf}, fs, io::{self, IsTerminal, Write}, process };
 
-use clap::{ Args, Parser, Subcommand };
+use clap::{ Args, Parser, Subcommand, ValueEnum };
 
-use mini_lisp::{ ScanError, ParseError::*, scanner::scan, parser::parse };
+use mini_lisp::{
+    Datum, TableItem, TokenUnit, json::Json, scanner::{ scan, scan_all }, parser::{ parse, parse_all },
+    eval::Env, expand::{ MacroEnv, expand_and_eval, expand_program }, repl::Repl, diagnostics::Diagnostic, report::{ scan_document, parse_document },
+    format::{ FormatOptions, format }, lsp::Server,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
-struct Cli {   
+struct Cli {
+    /// when to use ANSI colors in error messages
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -17,129 +25,300 @@ struct CommonArgs {
     name: PathBuf,
 }
 
+#[derive(Args, Debug)]
+struct AnalysisArgs {
+    /// mini-lisp source code
+    name: PathBuf,
+
+    /// output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+struct FmtArgs {
+    /// mini-lisp source files
+    #[arg(required = true)]
+    names: Vec<PathBuf>,
+
+    /// don't write the files; exit with failure if any of them isn't formatted
+    #[arg(long)]
+    check: bool,
+
+    /// maximum line width
+    #[arg(long, default_value_t = 80)]
+    width: usize,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// do lexical analysis
-    Scan(CommonArgs),
+    Scan(AnalysisArgs),
 
     /// do lexical and syntax analysis
-    Parse(CommonArgs),
+    Parse(AnalysisArgs),
+
+    /// execute a mini-lisp program
+    Run(CommonArgs),
+
+    /// expand macros and print the resulting program
+    Expand(CommonArgs),
+
+    /// start an interactive session
+    Repl,
+
+    /// format mini-lisp source files in place
+    Fmt(FmtArgs),
+
+    /// run a Language Server Protocol server over stdin/stdout
+    Lsp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// human-readable listing
+    Text,
+    /// structured JSON document
+    Json,
+    /// the JSON document's structure as an S-expression
+    Sexpr,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    /// color when stderr is a terminal
+    Auto,
+    Always,
+    Never,
 }
 
 fn main() {
     let cli = Cli::parse();
 
+    let color = match cli.color {
+        ColorChoice::Auto => io::stderr().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+
     match &cli.command {
         Commands::Scan(args) => {
             let path = &args.name;
+            let input = read_source(path);
 
-            let Ok(input) = fs::read_to_string(path) else {
-                eprintln!("Something went wrong reading the file");
-                process::exit(1);
-            };
+            // 对 `filename` 做词法分析，报告全部词法错误
+            let (token_sequence, token_table, errors) = scan_all(input.as_str());
 
-            // 对 `filename` 做词法分析
-            match scan(input.as_str()) {
-                Ok((token_sequence, token_table)) => {
-                    println!("tokens:");
-                    for token in token_sequence {
-                        print!("<{:?}, {}> ", token.token_type, token.table_ptr);
-                        io::stdout().flush().expect("flush failed");
-                    }
-                    println!("\n");
-
-                    println!("token table:");
-                    for (i, table_item) in token_table.iter().enumerate() {
-                        println!("{:>3}: {:?}", i, table_item);
-                    }
-                },
+            if args.format != OutputFormat::Text {
+                let document = scan_document(&input, &token_sequence, &token_table, &errors);
+                print_document(&document, args.format);
+                process::exit(if errors.is_empty() { 0 } else { 1 });
+            }
 
-                Err(e) => {
-                    match e {
-                        ScanError::InvalidCharacter((row, column)) => {
-                            eprintln!(
-                                "tokenize() failed at row {} column {}: Invalid Character",
-                                row + 1, column + 1
-                            );
-                        },
-
-                        ScanError::InvalidToken((row, column)) => {
-                            eprintln!(
-                                "tokenize() failed at row {} column {}: Invalid Token",
-                                row + 1, column + 1
-                            );
-                        }
-                    };
+            print_tokens(&token_sequence, &token_table);
 
-                    process::exit(1);
+            if !errors.is_empty() {
+                io::stdout().flush().expect("flush failed");
+                for e in errors.iter() {
+                    eprint!("{}", Diagnostic::from(e).render(&input, &path.display().to_string(), color));
                 }
+                process::exit(1);
             }
         },
 
         Commands::Parse(args) => {
             let path = &args.name;
-            
-            let Ok(input) = fs::read_to_string(path) else {
-                eprintln!("Something went wrong reading the file");
+            let input = read_source(path);
+
+            if args.format != OutputFormat::Text {
+                let (token_sequence, token_table, scan_errors) = scan_all(input.as_str());
+                let (program, parse_errors) = if scan_errors.is_empty() {
+                    let (program, parse_errors) = parse_all(&token_sequence, &token_table);
+                    (Some(program), parse_errors)
+                } else {
+                    (None, Vec::new())
+                };
+
+                let document = parse_document(&input, &token_sequence, &token_table, program.as_deref(), &scan_errors, &parse_errors);
+                print_document(&document, args.format);
+                process::exit(if scan_errors.is_empty() && parse_errors.is_empty() { 0 } else { 1 });
+            }
+
+            let (token_sequence, token_table) = match scan(input.as_str()) {
+                Ok(result) => result,
+                Err(e) => report(Diagnostic::from(&e), &input, path, color),
+            };
+
+            // 输出词法分析结果
+            println!("=====================");
+            println!("====== Scanner ======");
+            println!("=====================");
+            print_tokens(&token_sequence, &token_table);
+
+            // 输出语法分析结果
+            println!("\n");
+            println!("====================");
+            println!("====== Parser ======");
+            println!("====================");
+            // 报告全部语法错误，并输出成功识别的部分语法树
+            let (program, errors) = parse_all(&token_sequence, &token_table);
+            if errors.is_empty() {
+                println!("parsing success");
+            } else {
+                println!("parsing failed with {} error(s)", errors.len());
+            }
+            print_tree(&program);
+
+            if !errors.is_empty() {
+                io::stdout().flush().expect("flush failed");
+                for e in errors.iter() {
+                    eprint!("{}", Diagnostic::from(e).render(&input, &path.display().to_string(), color));
+                }
                 process::exit(1);
+            }
+        },
+
+        Commands::Run(args) => {
+            let path = &args.name;
+            let input = read_source(path);
+
+            let (token_sequence, token_table) = match scan(input.as_str()) {
+                Ok(result) => result,
+                Err(e) => report(Diagnostic::from(&e), &input, path, color),
             };
 
-            match scan(input.as_str()) {
-                Ok((token_sequence, token_table)) => {
-                    // 输出词法分析结果
-                    println!("=====================");
-                    println!("====== Scanner ======");
-                    println!("=====================");
-                    println!("tokens:");
-                    for token in token_sequence.iter() {
-                        print!("<{:?}, {}> ", token.token_type, token.table_ptr);
-                        io::stdout().flush().expect("flush failed");
-                    }
-                    println!("\n");
-
-                    println!("token table:");
-                    for (i, table_item) in token_table.iter().enumerate() {
-                        println!("{:>3}: {:?}", i, table_item);
-                    }
-
-                    // 输出语法分析结果
-                    println!("\n");
-                    println!("====================");
-                    println!("====== Parser ======");
-                    println!("====================");
-                    match parse(&token_sequence, &token_table) {
-                        Ok(()) => println!("parsing success"),
-                        Err(e) => {
-                            match e {
-                                UnexpectedToken((x, y)) => eprintln!("parse() failed at row {} column {}: Unexpected Token", x + 1, y + 1),
-                                UnexpectedEndOfInput => eprintln!("parse() failed: Unexpected End Of Input"),
-                                UnknownScanError => eprintln!("parse() failed: Unknown Scan Error"),
-                            }
-                            process::exit(1);
-                        }
-                    }
-                },
+            let program = match parse(&token_sequence, &token_table) {
+                Ok(program) => program,
+                Err(e) => report(Diagnostic::from(&e), &input, path, color),
+            };
 
-                Err(e) => {
-                    match e {
-                        ScanError::InvalidCharacter((row, column)) => {
-                            eprintln!(
-                                "tokenize() failed at row {} column {}: Invalid Character",
-                                row + 1, column + 1
-                            );
-                        },
-
-                        ScanError::InvalidToken((row, column)) => {
-                            eprintln!(
-                                "tokenize() failed at row {} column {}: Invalid Token",
-                                row + 1, column + 1
-                            );
-                        }
-                    };
+            let result = expand_and_eval(&program, &MacroEnv::global(&Env::global()));
+            io::stdout().flush().expect("flush failed");
 
+            if let Err(e) = result {
+                report(Diagnostic::from(&e), &input, path, color);
+            }
+        },
+
+        Commands::Expand(args) => {
+            let path = &args.name;
+            let input = read_source(path);
+
+            let (token_sequence, token_table) = match scan(input.as_str()) {
+                Ok(result) => result,
+                Err(e) => report(Diagnostic::from(&e), &input, path, color),
+            };
+
+            let program = match parse(&token_sequence, &token_table) {
+                Ok(program) => program,
+                Err(e) => report(Diagnostic::from(&e), &input, path, color),
+            };
+
+            let program = match expand_program(&program, &MacroEnv::global(&Env::global())) {
+                Ok(program) => program,
+                Err(e) => report(Diagnostic::from(&e), &input, path, color),
+            };
+
+            // 逐个格式化后输出；含卫生改名所生成的名字（无法被重新读入）的表达式按原样输出
+            for datum in &program {
+                let expanded = format!("{}\n", datum);
+                match format(&expanded, &FormatOptions::default()) {
+                    Ok(formatted) => print!("{}", formatted),
+                    Err(_) => print!("{}", expanded),
+                }
+            }
+        },
+
+        Commands::Repl => {
+            let stdin = io::stdin();
+            if let Err(e) = Repl::new(color).run(stdin.lock(), &mut io::stdout(), &mut io::stderr()) {
+                eprintln!("repl failed: {}", e);
+                process::exit(1);
+            }
+        },
+
+        Commands::Fmt(args) => {
+            let options = FormatOptions { width: args.width };
+            let mut unformatted = false;
+
+            for path in &args.names {
+                let input = read_source(path);
+                let formatted = match format(&input, &options) {
+                    Ok(formatted) => formatted,
+                    Err(e) => report(Diagnostic::from(&e), &input, path, color),
+                };
+                if formatted == input {
+                    continue;
+                }
+
+                if args.check {
+                    println!("would reformat `{}`", path.display());
+                    unformatted = true;
+                } else if let Err(e) = fs::write(path, formatted) {
+                    eprintln!("cannot write `{}`: {}", path.display(), e);
+                    process::exit(1);
+                }
+            }
+
+            if unformatted {
+                process::exit(1);
+            }
+        },
+
+        Commands::Lsp => {
+            let stdin = io::stdin();
+            match Server::default().run(stdin.lock(), &mut io::stdout()) {
+                // 未经`shutdown`即退出时以失败状态退出
+                Ok(clean) => process::exit(if clean { 0 } else { 1 }),
+                Err(e) => {
+                    eprintln!("lsp failed: {}", e);
                     process::exit(1);
                 },
             }
         },
     }
 }
+
+fn read_source(path: &Path) -> String {
+    match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("cannot read `{}`: {}", path.display(), e);
+            process::exit(1);
+        },
+    }
+}
+
+// 输出诊断信息后以失败状态退出
+fn report(diagnostic: Diagnostic, input: &str, path: &Path, color: bool) -> ! {
+    eprint!("{}", diagnostic.render(input, &path.display().to_string(), color));
+    process::exit(1);
+}
+
+fn print_document(document: &Json, format: OutputFormat) {
+    match format {
+        OutputFormat::Sexpr => println!("{}", document.to_sexpr()),
+        _ => println!("{}", document),
+    }
+}
+
+fn print_tokens(token_sequence: &[TokenUnit], token_table: &[TableItem]) {
+    println!("tokens:");
+    for token in token_sequence {
+        print!("<{:?}, {}> ", token.token_type, token.table_ptr);
+        io::stdout().flush().expect("flush failed");
+    }
+    println!("\n");
+
+    println!("token table:");
+    for (i, table_item) in token_table.iter().enumerate() {
+        println!("{:>3}: {:?}", i, table_item);
+    }
+}
+
+fn print_tree(program: &[Datum]) {
+    println!("\nsyntax tree:");
+    for datum in program {
+        println!("{:>3}:{:<3} {}", datum.index.0 + 1, datum.index.1 + 1, datum);
+    }
+}
@@ -0,0 +1,270 @@
+use std::{cmp::Ordering, fmt, ops::{Add, Mul, Neg, Sub}};
+
+mod bigint;
+mod utils;
+pub use bigint::BigInt;
+#[cfg(test)]
+mod tests;
+
+
+// 数值塔：精确整数、精确分数与非精确实数
+// 精确数的表示总是规范的：能放入isize的整数为Int，分数已约分且分母大于1
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Int(isize),
+    // 超出isize范围的整数
+    Big(BigInt),
+    // 分子及（正的）分母
+    Rational(BigInt, BigInt),
+    Float(f64),
+}
+
+
+impl Number {
+    // 由任意精度整数构造规范的精确整数
+    pub fn integer(value: BigInt) -> Number {
+        match value.to_i128().and_then(|v| isize::try_from(v).ok()) {
+            Some(v) => Number::Int(v),
+            None => Number::Big(value),
+        }
+    }
+
+    // 由分子、分母构造规范的精确数；分母为零时返回None
+    pub fn ratio(numerator: BigInt, denominator: BigInt) -> Option<Number> {
+        if denominator.is_zero() {
+            return None;
+        }
+        let gcd = numerator.gcd(&denominator);
+        let (mut numerator, mut denominator) = match (numerator.div_rem(&gcd), denominator.div_rem(&gcd)) {
+            (Some((numerator, _)), Some((denominator, _))) => (numerator, denominator),
+            _ => (numerator, denominator),
+        };
+        if denominator.is_negative() {
+            numerator = -&numerator;
+            denominator = -&denominator;
+        }
+
+        if denominator == BigInt::one() {
+            Some(Number::integer(numerator))
+        } else {
+            Some(Number::Rational(numerator, denominator))
+        }
+    }
+
+    // 解析数值字面量（文法见utils.rs）；出错时返回首个非法字符的字符偏移
+    pub fn parse(literal: &str) -> Result<Number, usize> {
+        utils::parse_literal(literal)
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self {
+            Number::Int(_) | Number::Big(_) => true,
+            Number::Rational(..) => false,
+            Number::Float(v) => v.is_finite() && v.fract() == 0.0,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(v) => *v == 0,
+            Number::Float(v) => *v == 0.0,
+            // 规范表示下不为零
+            Number::Big(_) | Number::Rational(..) => false,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(v) => *v as f64,
+            Number::Big(v) => v.to_f64(),
+            Number::Rational(numerator, denominator) => ratio_to_f64(numerator, denominator),
+            Number::Float(v) => *v,
+        }
+    }
+
+    // `exact->inexact`
+    pub fn to_inexact(&self) -> Number {
+        Number::Float(self.to_f64())
+    }
+
+    // `inexact->exact`：浮点数按其二进制值精确转换；无穷大与NaN返回None
+    pub fn to_exact(&self) -> Option<Number> {
+        let Number::Float(v) = self else {
+            return Some(self.clone());
+        };
+        if !v.is_finite() {
+            return None;
+        }
+
+        // v = ±mantissa * 2^exponent
+        let bits = v.to_bits();
+        let biased = ((bits >> 52) & 0x7ff) as i64;
+        let mut mantissa = (bits & ((1 << 52) - 1)) as i128;
+        let exponent = if biased == 0 {
+            -1074
+        } else {
+            mantissa |= 1 << 52;
+            biased - 1075
+        };
+        if *v < 0.0 {
+            mantissa = -mantissa;
+        }
+
+        let mantissa = BigInt::from(mantissa);
+        if exponent >= 0 {
+            Some(Number::integer(mantissa.shl(exponent as u64)))
+        } else {
+            Number::ratio(mantissa, BigInt::one().shl(exponent.unsigned_abs()))
+        }
+    }
+
+    // 精确数的分子与分母
+    fn parts(&self) -> Option<(BigInt, BigInt)> {
+        match self {
+            Number::Int(v) => Some((BigInt::from(*v), BigInt::one())),
+            Number::Big(v) => Some((v.clone(), BigInt::one())),
+            Number::Rational(numerator, denominator) => Some((numerator.clone(), denominator.clone())),
+            Number::Float(_) => None,
+        }
+    }
+
+    pub fn numerator(&self) -> Option<Number> {
+        match self {
+            Number::Float(_) => self.to_exact()?.numerator().map(|v| v.to_inexact()),
+            _ => self.parts().map(|(numerator, _)| Number::integer(numerator)),
+        }
+    }
+
+    pub fn denominator(&self) -> Option<Number> {
+        match self {
+            Number::Float(_) => self.to_exact()?.denominator().map(|v| v.to_inexact()),
+            _ => self.parts().map(|(_, denominator)| Number::integer(denominator)),
+        }
+    }
+
+    // 除法；精确数除以精确的零时返回None
+    pub fn checked_div(&self, other: &Number) -> Option<Number> {
+        match (self.parts(), other.parts()) {
+            (Some((n1, d1)), Some((n2, d2))) => Number::ratio(&n1 * &d2, &d1 * &n2),
+            _ => Some(Number::Float(self.to_f64() / other.to_f64())),
+        }
+    }
+
+    // 整数的向零取整除法，返回商与余数；非整数或除数为零时返回None
+    pub fn div_rem(&self, other: &Number) -> Option<(Number, Number)> {
+        if !self.is_integer() || !other.is_integer() || other.is_zero() {
+            return None;
+        }
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) if let (Some(q), Some(r)) = (a.checked_div(*b), a.checked_rem(*b)) => {
+                Some((Number::Int(q), Number::Int(r)))
+            },
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                let (a, b) = (self.to_f64(), other.to_f64());
+                Some((Number::Float((a / b).trunc()), Number::Float(a % b)))
+            },
+            _ => {
+                let (a, b) = (self.parts()?.0, other.parts()?.0);
+                let (q, r) = a.div_rem(&b)?;
+                Some((Number::integer(q), Number::integer(r)))
+            },
+        }
+    }
+
+    // 数值比较；与NaN比较时返回None
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Some(a.cmp(b)),
+            (Number::Float(a), Number::Float(b)) => a.partial_cmp(b),
+            // 非精确数与精确数比较时按精确值比较
+            (Number::Float(a), _) if a.is_infinite() => Some(if *a > 0.0 { Ordering::Greater } else { Ordering::Less }),
+            (_, Number::Float(b)) if b.is_infinite() => Some(if *b > 0.0 { Ordering::Less } else { Ordering::Greater }),
+            _ => {
+                let (n1, d1) = self.to_exact()?.parts()?;
+                let (n2, d2) = other.to_exact()?.parts()?;
+                Some((&n1 * &d2).cmp(&(&n2 * &d1)))
+            },
+        }
+    }
+
+    // 精确数运算：两个isize时走快速路径，溢出时转为任意精度
+    fn exact_op(&self, other: &Number, int_op: fn(isize, isize) -> Option<isize>, big_op: fn(&BigInt, &BigInt) -> BigInt, float_op: fn(f64, f64) -> f64) -> Number {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) if let Some(v) = int_op(*a, *b) => Number::Int(v),
+            (Number::Float(_), _) | (_, Number::Float(_)) => Number::Float(float_op(self.to_f64(), other.to_f64())),
+            _ => match (self.parts(), other.parts()) {
+                (Some((n1, d1)), Some((n2, d2))) if d1 == d2 => {
+                    Number::ratio(big_op(&n1, &n2), d1).unwrap_or(Number::Float(f64::NAN))
+                },
+                (Some((n1, d1)), Some((n2, d2))) => Number::ratio(big_op(&(&n1 * &d2), &(&n2 * &d1)), &d1 * &d2).unwrap_or(Number::Float(f64::NAN)),
+                _ => Number::Float(f64::NAN),
+            },
+        }
+    }
+}
+
+
+impl Add for &Number {
+    type Output = Number;
+
+    fn add(self, other: &Number) -> Number {
+        self.exact_op(other, isize::checked_add, |a, b| a + b, |a, b| a + b)
+    }
+}
+
+
+impl Sub for &Number {
+    type Output = Number;
+
+    fn sub(self, other: &Number) -> Number {
+        self.exact_op(other, isize::checked_sub, |a, b| a - b, |a, b| a - b)
+    }
+}
+
+
+impl Mul for &Number {
+    type Output = Number;
+
+    fn mul(self, other: &Number) -> Number {
+        match (self.parts(), other.parts()) {
+            (Some((n1, d1)), Some((n2, d2))) if !matches!((self, other), (Number::Int(_), Number::Int(_))) => {
+                Number::ratio(&n1 * &n2, &d1 * &d2).unwrap_or(Number::Float(f64::NAN))
+            },
+            _ => self.exact_op(other, isize::checked_mul, |a, b| a * b, |a, b| a * b),
+        }
+    }
+}
+
+
+impl Neg for &Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        &Number::Int(0) - self
+    }
+}
+
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(v) => write!(f, "{}", v),
+            Number::Big(v) => write!(f, "{}", v),
+            Number::Rational(numerator, denominator) => write!(f, "{}/{}", numerator, denominator),
+            Number::Float(v) if v.is_nan() => write!(f, "+nan.0"),
+            Number::Float(v) if v.is_infinite() => write!(f, "{}inf.0", if *v > 0.0 { "+" } else { "-" }),
+            Number::Float(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+
+// 分子、分母过大时先同时缩小，避免转换为f64时溢出
+fn ratio_to_f64(numerator: &BigInt, denominator: &BigInt) -> f64 {
+    let shift = numerator.bits().max(denominator.bits()).saturating_sub(1000);
+    numerator.shr(shift).to_f64() / denominator.shr(shift).to_f64()
+}
@@ -0,0 +1,393 @@
+use std::{cmp::Ordering, fmt, ops::{Add, Mul, Neg, Sub}};
+
+
+// 任意精度整数：符号与绝对值，绝对值以2^32为基数按低位在前存放，且没有高位的0
+// （零的绝对值为空，符号为非负）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt { negative: false, magnitude: Vec::new() }
+    }
+
+    pub fn one() -> BigInt {
+        BigInt { negative: false, magnitude: vec![1] }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt { negative: false, magnitude: self.magnitude.clone() }
+    }
+
+    // 按radix进制解析可带符号的数字串
+    pub fn parse(literal: &str, radix: u32) -> Option<BigInt> {
+        let (negative, digits) = match literal.as_bytes().first() {
+            Some(b'-') => (true, &literal[1..]),
+            Some(b'+') => (false, &literal[1..]),
+            _ => (false, literal),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut magnitude: Vec<u32> = Vec::new();
+        for ch in digits.chars() {
+            let digit = ch.to_digit(radix)?;
+            // magnitude = magnitude * radix + digit
+            let mut carry = digit as u64;
+            for limb in magnitude.iter_mut() {
+                let product = *limb as u64 * radix as u64 + carry;
+                *limb = product as u32;
+                carry = product >> 32;
+            }
+            if carry != 0 {
+                magnitude.push(carry as u32);
+            }
+        }
+
+        Some(BigInt::from_parts(negative, magnitude))
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.magnitude.len() > 4 {
+            return None;
+        }
+        let magnitude = self.magnitude.iter().rev().fold(0u128, |acc, limb| (acc << 32) | *limb as u128);
+        if self.negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.magnitude.iter().rev().fold(0.0, |acc, limb| acc * 4294967296.0 + *limb as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    // 二进制位数（不含符号）
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            Some(top) => self.magnitude.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    // 乘以2^bits
+    pub fn shl(&self, bits: u64) -> BigInt {
+        let mut magnitude = vec![0; (bits / 32) as usize];
+        magnitude.extend(shl_bits(&self.magnitude, (bits % 32) as u32));
+        BigInt::from_parts(self.negative, magnitude)
+    }
+
+    // 绝对值除以2^bits（向零取整）
+    pub fn shr(&self, bits: u64) -> BigInt {
+        let limbs = (bits / 32) as usize;
+        if limbs >= self.magnitude.len() {
+            return BigInt::zero();
+        }
+        BigInt::from_parts(self.negative, shr_bits(&self.magnitude[limbs..], (bits % 32) as u32))
+    }
+
+    // 向零取整的商及余数（余数与被除数同号）；除数为零时返回None
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitude(&self.magnitude, &divisor.magnitude);
+        Some((
+            BigInt::from_parts(self.negative != divisor.negative, quotient),
+            BigInt::from_parts(self.negative, remainder),
+        ))
+    }
+
+    // 最大公约数（非负）
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let remainder = a.div_rem(&b).map_or_else(BigInt::zero, |(_, remainder)| remainder);
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+
+    // 按radix进制（2至36）输出
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return String::from("0");
+        }
+
+        let mut digits = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            let mut remainder = 0u64;
+            for limb in magnitude.iter_mut().rev() {
+                let current = (remainder << 32) | *limb as u64;
+                *limb = (current / radix as u64) as u32;
+                remainder = current % radix as u64;
+            }
+            trim(&mut magnitude);
+            digits.push(char::from_digit(remainder as u32, radix).unwrap_or('?'));
+        }
+
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+
+    fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        trim(&mut magnitude);
+        BigInt { negative: negative && !magnitude.is_empty(), magnitude }
+    }
+}
+
+
+impl From<i128> for BigInt {
+    fn from(value: i128) -> BigInt {
+        let mut rest = value.unsigned_abs();
+        let mut magnitude = Vec::new();
+        while rest != 0 {
+            magnitude.push(rest as u32);
+            rest >>= 32;
+        }
+        BigInt::from_parts(value < 0, magnitude)
+    }
+}
+
+
+impl From<isize> for BigInt {
+    fn from(value: isize) -> BigInt {
+        BigInt::from(value as i128)
+    }
+}
+
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_magnitude(&self.magnitude, &other.magnitude));
+        }
+        // 异号相加即绝对值相减，结果取绝对值较大者的符号
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_magnitude(&other.magnitude, &self.magnitude)),
+            _ => BigInt::from_parts(self.negative, sub_magnitude(&self.magnitude, &other.magnitude)),
+        }
+    }
+}
+
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.magnitude.iter().enumerate() {
+                let current = product[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                product[i + j] = current as u32;
+                carry = current >> 32;
+            }
+            product[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::from_parts(self.negative != other.negative, product)
+    }
+}
+
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.magnitude.clone())
+    }
+}
+
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str_radix(10))
+    }
+}
+
+
+// 去除高位的0
+fn trim(magnitude: &mut Vec<u32>) {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+}
+
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, limb) in long.iter().enumerate() {
+        let current = *limb as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        sum.push(current as u32);
+        carry = current >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+
+// 要求a >= b
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, limb) in a.iter().enumerate() {
+        let current = *limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        difference.push(current as u32);
+        borrow = if current < 0 { 1 } else { 0 };
+    }
+    difference
+}
+
+
+// 左移bits（小于32）位，结果比原数多一个高位
+fn shl_bits(magnitude: &[u32], bits: u32) -> Vec<u32> {
+    let mut shifted = Vec::with_capacity(magnitude.len() + 1);
+    let mut carry = 0u32;
+    for limb in magnitude {
+        shifted.push((limb << bits) | carry);
+        carry = if bits == 0 { 0 } else { limb >> (32 - bits) };
+    }
+    shifted.push(carry);
+    shifted
+}
+
+
+// 右移bits（小于32）位
+fn shr_bits(magnitude: &[u32], bits: u32) -> Vec<u32> {
+    let mut shifted = vec![0u32; magnitude.len()];
+    for (i, limb) in magnitude.iter().enumerate() {
+        shifted[i] = limb >> bits;
+        if bits != 0 && let Some(next) = magnitude.get(i + 1) {
+            shifted[i] |= next << (32 - bits);
+        }
+    }
+    shifted
+}
+
+
+// 绝对值的长除法（Knuth算法D），除数非零
+fn div_rem_magnitude(dividend: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_magnitude(dividend, divisor) == Ordering::Less {
+        return (Vec::new(), dividend.to_vec());
+    }
+
+    // 单个基数位的除数
+    if let [single] = divisor {
+        let mut quotient = vec![0u32; dividend.len()];
+        let mut remainder = 0u64;
+        for i in (0..dividend.len()).rev() {
+            let current = (remainder << 32) | dividend[i] as u64;
+            quotient[i] = (current / *single as u64) as u32;
+            remainder = current % *single as u64;
+        }
+        return (quotient, vec![remainder as u32]);
+    }
+
+    // 规格化：使除数最高位的基数位的最高二进制位为1
+    let shift = divisor[divisor.len() - 1].leading_zeros();
+    let mut v = shl_bits(divisor, shift);
+    v.pop();
+    let mut u = shl_bits(dividend, shift);
+    let n = v.len();
+    let m = u.len() - n - 1;
+    let mut quotient = vec![0u32; m + 1];
+
+    for j in (0..=m).rev() {
+        // 估计商的一位并修正
+        let numerator = ((u[j + n] as u64) << 32) | u[j + n - 1] as u64;
+        let mut q_hat = numerator / v[n - 1] as u64;
+        let mut r_hat = numerator % v[n - 1] as u64;
+        while q_hat >= 1 << 32 || q_hat * v[n - 2] as u64 > ((r_hat << 32) | u[j + n - 2] as u64) {
+            q_hat -= 1;
+            r_hat += v[n - 1] as u64;
+            if r_hat >= 1 << 32 {
+                break;
+            }
+        }
+
+        // u[j..=j+n] -= q_hat * v
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let product = q_hat * v[i] as u64 + carry;
+            carry = product >> 32;
+            let current = u[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+            u[i + j] = current as u32;
+            borrow = if current < 0 { 1 } else { 0 };
+        }
+        let current = u[j + n] as i64 - borrow - carry as i64;
+        u[j + n] = current as u32;
+
+        // 估计值大了1：加回一个除数
+        if current < 0 {
+            q_hat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+
+        quotient[j] = q_hat as u32;
+    }
+
+    (quotient, shr_bits(&u[..n], shift))
+}
@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+
+use super::{BigInt, Number};
+
+
+fn num(literal: &str) -> Number {
+    Number::parse(literal).unwrap_or_else(|_| panic!("not a number: {}", literal))
+}
+
+
+#[test]
+fn parses_canonical_numbers() {
+    assert_eq!(num("42"), Number::Int(42));
+    assert_eq!(num("-0"), Number::Int(0));
+    assert_eq!(num("6/4").to_string(), "3/2");
+    assert_eq!(num("-10/5"), Number::Int(-2));
+    assert_eq!(num("2.5"), Number::Float(2.5));
+    assert_eq!(num("1e3"), Number::Float(1000.0));
+    assert!(matches!(num("123456789012345678901234567890"), Number::Big(_)));
+    assert_eq!(num("123456789012345678901234567890").to_string(), "123456789012345678901234567890");
+    for malformed in ["1/0", "1/-2", "1/2/3", "1x"] {
+        assert!(Number::parse(malformed).is_err(), "{}", malformed);
+    }
+}
+
+
+#[test]
+fn exact_arithmetic_promotes_and_demotes() {
+    let max = Number::Int(isize::MAX);
+    let sum = &max + &Number::Int(1);
+    assert!(matches!(sum, Number::Big(_)));
+    assert_eq!(&sum - &Number::Int(1), max);
+    assert_eq!(&num("1/3") + &num("2/3"), Number::Int(1));
+    assert_eq!((&num("1/3") * &num("3/4")).to_string(), "1/4");
+    assert_eq!((-&num("1/2")).to_string(), "-1/2");
+    assert_eq!(num("7").checked_div(&num("2")).map(|v| v.to_string()).as_deref(), Some("7/2"));
+    assert_eq!(num("7").checked_div(&num("0")), None);
+    // 精确数与非精确数运算得非精确数
+    assert_eq!(&num("1/2") + &num("0.25"), Number::Float(0.75));
+    assert_eq!(num("1.0").checked_div(&num("0")), Some(Number::Float(f64::INFINITY)));
+}
+
+
+#[test]
+fn bigint_multiplication_and_division() {
+    let factorial = (1..=30).fold(Number::Int(1), |acc, i| &acc * &Number::Int(i));
+    assert_eq!(factorial.to_string(), "265252859812191058636308480000000");
+    let Some((quotient, remainder)) = factorial.div_rem(&num("1000000007")) else {
+        panic!("division failed");
+    };
+    assert_eq!((&(&quotient * &num("1000000007")) + &remainder), factorial);
+    assert_eq!(BigInt::from(-255isize).to_str_radix(16), "-ff");
+    assert_eq!(BigInt::parse("-ff", 16), Some(BigInt::from(-255isize)));
+}
+
+
+#[test]
+fn exactness_conversions_and_comparison() {
+    assert_eq!(num("0.5").to_exact(), Some(num("1/2")));
+    assert_eq!(num("1/4").to_inexact(), Number::Float(0.25));
+    assert_eq!(Number::Float(f64::NAN).to_exact(), None);
+    assert_eq!(num("1/3").compare(&num("0.3")), Some(Ordering::Greater));
+    assert_eq!(num("100000000000000000000").compare(&num("1e20")), Some(Ordering::Equal));
+    assert_eq!(num("1").compare(&Number::Float(f64::NAN)), None);
+    assert_eq!(num("-6/4").numerator(), Some(Number::Int(-3)));
+    assert_eq!(num("-6/4").denominator(), Some(Number::Int(2)));
+}
+
+
+#[test]
+fn radix_and_exactness_prefixes() {
+    assert_eq!(num("#xFF"), Number::Int(255));
+    assert_eq!(num("#b-101"), Number::Int(-5));
+    assert_eq!(num("#o17/4").to_string(), "15/4");
+    assert_eq!(num("#d10"), Number::Int(10));
+    assert_eq!(num("#e1.25").to_string(), "5/4");
+    assert_eq!(num("#i1/4"), Number::Float(0.25));
+    assert_eq!(num("#x#i10"), Number::Float(16.0));
+    assert_eq!(num("#E#X1f"), Number::Int(31));
+    assert_eq!(num("#e1e3"), Number::Int(1000));
+    assert_eq!(num("1_000_000"), Number::Int(1000000));
+    assert_eq!(num("#xdead_beef"), Number::Int(0xdeadbeef));
+}
+
+
+#[test]
+fn special_floats() {
+    assert_eq!(num("+inf.0"), Number::Float(f64::INFINITY));
+    assert_eq!(num("-INF.0"), Number::Float(f64::NEG_INFINITY));
+    assert!(matches!(num("+nan.0"), Number::Float(v) if v.is_nan()));
+    assert_eq!(num("-inf.0").to_string(), "-inf.0");
+    assert_eq!(num("+nan.0").to_string(), "+nan.0");
+    assert_eq!(num(".5"), Number::Float(0.5));
+    assert_eq!(num("5."), Number::Float(5.0));
+}
+
+
+// 出错时返回首个非法字符的偏移
+#[test]
+fn malformed_literals_report_the_offending_character() {
+    for (literal, offset) in [
+        ("#x1g", 3),
+        ("#b102", 4),
+        ("#x#x1", 3),
+        ("#e#i1", 3),
+        ("#q1", 1),
+        ("#x1.5", 3),
+        ("1/0", 2),
+        ("1/", 1),
+        ("1__0", 1),
+        ("_1", 0),
+        ("1_", 1),
+        ("1e", 1),
+        ("1e+", 2),
+        ("1.2.3", 3),
+        ("#e+inf.0", 3),
+        ("inf.0", 0),
+        ("12abc", 2),
+    ] {
+        assert_eq!(Number::parse(literal), Err(offset), "{}", literal);
+    }
+}
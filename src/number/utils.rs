@@ -0,0 +1,171 @@
+use super::{BigInt, Number};
+
+
+// 数值字面量的文法：
+//   number  -> prefix* sign? body
+//   prefix  -> #x | #b | #o | #d | #e | #i     （进制与精确性前缀各至多一个，顺序任意，不区分大小写）
+//   body    -> uint | uint/uint | decimal | inf.0 | nan.0（后两者须带符号）
+//   decimal -> (uint? . uint | uint .?) exponent?，仅限十进制
+//   uint    -> 数位序列，相邻数位之间可插入一个分隔符`_`
+// 出错时返回首个非法字符在字面量中的字符偏移
+pub fn parse_literal(literal: &str) -> Result<Number, usize> {
+    let chars: Vec<char> = literal.chars().collect();
+    // 期望更多字符却已到末尾时，指向最后一个字符
+    let fail = |at: usize| at.min(chars.len().saturating_sub(1));
+
+    let mut radix = None;
+    let mut exact = None;
+    let mut pos = 0;
+    while chars.get(pos) == Some(&'#') {
+        match chars.get(pos + 1).map(char::to_ascii_lowercase) {
+            Some('x') if radix.is_none() => radix = Some(16),
+            Some('b') if radix.is_none() => radix = Some(2),
+            Some('o') if radix.is_none() => radix = Some(8),
+            Some('d') if radix.is_none() => radix = Some(10),
+            Some('e') if exact.is_none() => exact = Some(true),
+            Some('i') if exact.is_none() => exact = Some(false),
+            _ => return Err(fail(pos + 1)),
+        }
+        pos += 2;
+    }
+    let radix = radix.unwrap_or(10);
+
+    let negative = chars.get(pos) == Some(&'-');
+    let signed = matches!(chars.get(pos), Some('+' | '-'));
+    let start = if signed { pos + 1 } else { pos };
+
+    // 无穷大与NaN没有精确的表示
+    let rest: String = chars[start..].iter().collect::<String>().to_ascii_lowercase();
+    if signed && (rest == "inf.0" || rest == "nan.0") {
+        if exact == Some(true) {
+            return Err(start);
+        }
+        let value = if rest == "nan.0" { f64::NAN } else { f64::INFINITY };
+        return Ok(Number::Float(if negative { -value } else { value }));
+    }
+
+    let (integer, end) = digits(&chars, start, radix)?;
+    let number = match chars.get(end) {
+        None if integer.is_empty() => return Err(fail(end)),
+        None => Number::integer(signed_int(negative, &integer, radix)),
+        Some('/') if !integer.is_empty() => {
+            let (denominator, end_of_denominator) = digits(&chars, end + 1, radix)?;
+            if denominator.is_empty() {
+                return Err(fail(end + 1));
+            }
+            if end_of_denominator < chars.len() {
+                return Err(end_of_denominator);
+            }
+            match Number::ratio(signed_int(negative, &integer, radix), signed_int(false, &denominator, radix)) {
+                Some(number) => number,
+                // 分母为零
+                None => return Err(end + 1),
+            }
+        },
+        Some('.' | 'e' | 'E') if radix == 10 => return decimal(&chars, negative, integer, end, exact),
+        Some(_) => return Err(end),
+    };
+
+    Ok(match exact {
+        Some(false) => number.to_inexact(),
+        _ => number,
+    })
+}
+
+
+// 自pos起读取radix进制的数位（去掉分隔符），返回数位及其后的位置
+fn digits(chars: &[char], pos: usize, radix: u32) -> Result<(String, usize), usize> {
+    let mut digits = String::new();
+    let mut i = pos;
+    while let Some(&ch) = chars.get(i) {
+        if ch.is_digit(radix) {
+            digits.push(ch);
+        } else if ch == '_' {
+            // 分隔符只能位于两个数位之间
+            if digits.is_empty() || !chars.get(i + 1).is_some_and(|next| next.is_digit(radix)) {
+                return Err(i);
+            }
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    Ok((digits, i))
+}
+
+
+// 十进制小数及指数部分；integer为已读取的整数部分，pos指向其后的`.`或指数标记
+fn decimal(chars: &[char], negative: bool, integer: String, pos: usize, exact: Option<bool>) -> Result<Number, usize> {
+    let fail = |at: usize| at.min(chars.len().saturating_sub(1));
+
+    let (fraction, mut pos) = match chars.get(pos) {
+        Some('.') => digits(chars, pos + 1, 10)?,
+        _ => (String::new(), pos),
+    };
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(fail(pos));
+    }
+
+    let mut exponent = 0_i64;
+    if let Some('e' | 'E') = chars.get(pos) {
+        let sign = matches!(chars.get(pos + 1), Some('+' | '-'));
+        let start = if sign { pos + 2 } else { pos + 1 };
+        let (digits, end) = digits(chars, start, 10)?;
+        if digits.is_empty() {
+            return Err(fail(start));
+        }
+        // 指数过大时按饱和值处理：非精确数为无穷大或0，精确数无法表示
+        exponent = digits.parse::<i64>().unwrap_or(i64::MAX / 2);
+        if chars[pos + 1] == '-' {
+            exponent = -exponent;
+        }
+        if exact == Some(true) && exponent.unsigned_abs() > 100_000 {
+            return Err(start);
+        }
+        pos = end;
+    }
+    if pos < chars.len() {
+        return Err(pos);
+    }
+
+    if exact == Some(true) {
+        // 有效数字 * 10^(指数 - 小数位数)
+        let mantissa = signed_int(negative, &format!("{}{}", integer, fraction), 10);
+        let scale = exponent - fraction.len() as i64;
+        let power = pow10(scale.unsigned_abs());
+        return Ok(if scale >= 0 {
+            Number::integer(&mantissa * &power)
+        } else {
+            Number::ratio(mantissa, power).unwrap_or(Number::Int(0))
+        });
+    }
+
+    let sign = if negative { "-" } else { "" };
+    let integer = if integer.is_empty() { "0" } else { integer.as_str() };
+    let fraction = if fraction.is_empty() { "0" } else { fraction.as_str() };
+    match format!("{}{}.{}e{}", sign, integer, fraction, exponent).parse::<f64>() {
+        Ok(value) => Ok(Number::Float(value)),
+        Err(_) => Err(0),
+    }
+}
+
+
+fn signed_int(negative: bool, digits: &str, radix: u32) -> BigInt {
+    let value = BigInt::parse(digits, radix).unwrap_or_else(BigInt::zero);
+    if negative { -&value } else { value }
+}
+
+
+fn pow10(exponent: u64) -> BigInt {
+    let mut result = BigInt::one();
+    let mut base = BigInt::from(10_isize);
+    let mut exponent = exponent;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = &result * &base;
+        }
+        base = &base * &base;
+        exponent >>= 1;
+    }
+    result
+}
@@ -1,15 +1,61 @@
-use utils::parse_start;
+use utils::{DatumBuilder, Parser, resync};
 
-use crate::{ParseError, TableItem, TokenUnit};
+use crate::{Datum, ParseError, TableItem, TokenUnit, cst::SyntaxNode};
+use cst::CstBuilder;
 mod utils;
+mod cst;
+#[cfg(test)]
+mod tests;
 
 
-pub fn parse(tokens: &Vec<TokenUnit>, token_table: &Vec<TableItem>) -> Result<(), ParseError> {
-    let mut current_tokens = &tokens[..];
-    loop {
-        current_tokens = parse_start(current_tokens, token_table)?;
-        if current_tokens.is_empty() {
-            break Ok(());
+// 对词法单元序列做语法分析，返回各顶层表达式的语法树
+pub fn parse(tokens: &[TokenUnit], token_table: &[TableItem]) -> Result<Vec<Datum>, ParseError> {
+    let mut parser = Parser::new(tokens, token_table, DatumBuilder::default());
+    parser.program()?;
+    Ok(parser.sink.program)
+}
+
+
+// 带错误恢复的语法分析：出错后跳过该顶层表达式继续分析，
+// 返回成功识别的顶层表达式及全部语法错误
+pub fn parse_all(tokens: &[TokenUnit], token_table: &[TableItem]) -> (Vec<Datum>, Vec<ParseError>) {
+    let mut program = Vec::new();
+    let mut errors = Vec::new();
+    let mut pos = 0;
+
+    while pos < tokens.len() {
+        let mut parser = Parser::new(&tokens[pos..], token_table, DatumBuilder::default());
+        match parser.element() {
+            Ok(()) => {
+                program.append(&mut parser.sink.program);
+                pos = tokens.len() - parser.rest().len();
+            },
+            Err(e) => {
+                let (next, unclosed) = resync(tokens, pos, token_table);
+
+                if unclosed.is_empty() {
+                    errors.push(e);
+                } else {
+                    // 表达式在下一个顶层`(`前被截断：报告截断部分中真正的语法错误及未闭合的`(`
+                    if let Err(e @ ParseError::UnexpectedToken(_)) = Parser::new(&tokens[pos..next], token_table, DatumBuilder::default()).element() {
+                        errors.push(e);
+                    }
+                    errors.extend(unclosed.into_iter().map(ParseError::UnclosedList));
+                }
+
+                pos = next;
+            },
         }
     }
+
+    (program, errors)
+}
+
+
+// 由无损词法分析（scan_lossless）的结果构造CST，input须为被扫描的原始输入
+// 与parse()共用同一文法，仅事件的接收者不同
+pub fn parse_lossless(input: &str, tokens: &[TokenUnit], token_table: &[TableItem]) -> Result<SyntaxNode, ParseError> {
+    let mut parser = Parser::new(tokens, token_table, CstBuilder::new(input));
+    parser.program()?;
+    Ok(parser.sink.finish())
 }
@@ -0,0 +1,57 @@
+use crate::{ParseError::{self, *}, TableItem, TokenUnit, cst::{NodeKind, SyntaxElement, SyntaxNode, SyntaxToken}};
+use super::utils::Sink;
+
+
+// 由无损词法分析的结果构造CST的接收者：trivia作为其所在节点的子元素原样保留
+pub struct CstBuilder<'a> {
+    // 尚未归入词法单元的源码
+    rest: &'a str,
+    // 尚未结束的节点
+    stack: Vec<SyntaxNode>,
+    // 已结束的最外层节点
+    root: Option<SyntaxNode>,
+}
+
+
+impl<'a> CstBuilder<'a> {
+    pub fn new(input: &'a str) -> CstBuilder<'a> {
+        CstBuilder { rest: input, stack: Vec::new(), root: None }
+    }
+
+    pub fn finish(self) -> SyntaxNode {
+        self.root.unwrap_or(SyntaxNode { kind: NodeKind::Program, children: Vec::new() })
+    }
+}
+
+
+impl Sink for CstBuilder<'_> {
+    fn open(&mut self, kind: NodeKind) {
+        self.stack.push(SyntaxNode { kind, children: Vec::new() });
+    }
+
+    // 取出词法单元的源码文本
+    fn token(&mut self, token_unit: &TokenUnit, table_item: &TableItem) -> Result<(), ParseError> {
+        let Some(node) = self.stack.last_mut() else {
+            return Err(UnknownScanError);
+        };
+
+        let bytes = self.rest.char_indices().nth(table_item.len).map_or(self.rest.len(), |(i, _)| i);
+        node.children.push(SyntaxElement::Token(SyntaxToken {
+            token_type: token_unit.token_type,
+            text: String::from(&self.rest[..bytes]),
+            index: table_item.index,
+        }));
+        self.rest = &self.rest[bytes..];
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        let Some(node) = self.stack.pop() else {
+            return;
+        };
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(SyntaxElement::Node(node)),
+            None => self.root = Some(node),
+        }
+    }
+}
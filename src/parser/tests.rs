@@ -0,0 +1,254 @@
+use crate::{Datum, DatumKind, ParseError, TokenType, ValueType, number::Number, cst::{NodeKind, SyntaxElement, SyntaxNode}, scanner::{scan, scan_lossless}};
+use super::{parse, parse_all, parse_lossless};
+
+
+fn parse_source(source: &str) -> Result<Vec<Datum>, ParseError> {
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed: {}", source);
+    };
+    parse(&tokens, &token_table)
+}
+
+
+// 带错误恢复地分析，返回各顶层表达式的文本及全部错误
+fn parse_all_source(source: &str) -> (Vec<String>, Vec<ParseError>) {
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed: {}", source);
+    };
+    let (program, errors) = parse_all(&tokens, &token_table);
+    (program.iter().map(|datum| datum.to_string()).collect(), errors)
+}
+
+
+fn parse_lossless_source(source: &str) -> Result<SyntaxNode, ParseError> {
+    let Ok((tokens, token_table)) = scan_lossless(source) else {
+        panic!("scan failed: {}", source);
+    };
+    parse_lossless(source, &tokens, &token_table)
+}
+
+
+fn symbol(name: &str, index: (usize, usize)) -> Datum {
+    Datum { kind: DatumKind::Symbol(String::from(name)), index }
+}
+
+
+#[test]
+fn builds_nested_datums_with_positions() {
+    let Ok(program) = parse_source("(f 1\n  '(x \"s\"))") else {
+        panic!("parse failed");
+    };
+    let quoted = Datum {
+        kind: DatumKind::List(vec![
+            symbol("x", (1, 4)),
+            Datum { kind: DatumKind::Const(ValueType::Str(String::from("s"))), index: (1, 6) },
+        ]),
+        index: (1, 3),
+    };
+    let expected = Datum {
+        kind: DatumKind::List(vec![
+            symbol("f", (0, 1)),
+            Datum { kind: DatumKind::Const(ValueType::Number(Number::Int(1))), index: (0, 3) },
+            Datum { kind: DatumKind::Quote(Box::new(quoted)), index: (1, 2) },
+        ]),
+        index: (0, 0),
+    };
+    assert_eq!(program, [expected]);
+}
+
+
+#[test]
+fn parses_each_top_level_form() {
+    let Ok(program) = parse_source("(define x 1) x (+ x 2.5) () #t") else {
+        panic!("parse failed");
+    };
+    let printed: Vec<String> = program.iter().map(|datum| datum.to_string()).collect();
+    assert_eq!(printed, ["(define x 1)", "x", "(+ x 2.5)", "()", "#t"]);
+}
+
+
+#[test]
+fn reports_syntax_errors() {
+    assert!(matches!(parse_source("(a (b)"), Err(ParseError::UnclosedList((0, 0)))));
+    assert!(matches!(parse_source("a )"), Err(ParseError::UnexpectedToken((0, 2)))));
+}
+
+
+#[test]
+fn datum_comments_skip_the_next_datum() {
+    let Ok(program) = parse_source("#;(ignored 1) (a #; b c) #; d") else {
+        panic!("parse failed");
+    };
+    let printed: Vec<String> = program.iter().map(|datum| datum.to_string()).collect();
+    assert_eq!(printed, ["(a c)"]);
+}
+
+
+#[test]
+fn parse_all_skips_stray_closers_and_keeps_going() {
+    let (program, errors) = parse_all_source("(a) )\n(b ) ) (c)");
+    assert_eq!(program, ["(a)", "(b)", "(c)"]);
+    assert_eq!(errors, [ParseError::UnexpectedToken((0, 4)), ParseError::UnexpectedToken((1, 5))]);
+}
+
+
+#[test]
+fn parse_all_reports_unclosed_lists_before_the_next_top_level_form() {
+    let (program, errors) = parse_all_source("(define (f x)\n  (+ x 1)\n(define y 2)\n(f y)");
+    assert_eq!(program, ["(define y 2)", "(f y)"]);
+    assert_eq!(errors, [ParseError::UnclosedList((0, 0))]);
+}
+
+
+#[test]
+fn parse_all_reports_syntax_errors_inside_truncated_forms() {
+    let (program, errors) = parse_all_source("(a (b ')\n(c)");
+    assert_eq!(program, ["(c)"]);
+    assert_eq!(errors, [ParseError::UnexpectedToken((0, 7)), ParseError::UnclosedList((0, 0))]);
+}
+
+
+#[test]
+fn parse_all_agrees_with_parse_on_valid_input() {
+    let source = "(define x 1) #;(skip) 'x (f \"s\")";
+    let (program, errors) = parse_all_source(source);
+    let Ok(expected) = parse_source(source) else {
+        panic!("parse failed");
+    };
+    assert!(errors.is_empty());
+    assert_eq!(program, expected.iter().map(|datum| datum.to_string()).collect::<Vec<_>>());
+}
+
+
+#[test]
+fn cst_round_trips_the_source() {
+    let source = "; header\n(define (f x)  ; doc\n  '( x #|b|# 1))\n\n#; (skip me)\n(f \"a\\nb\")  \n";
+    let Ok(tree) = parse_lossless_source(source) else {
+        panic!("parse failed");
+    };
+    assert_eq!(tree.kind, NodeKind::Program);
+    assert_eq!(tree.to_string(), source);
+}
+
+
+#[test]
+fn cst_nodes_keep_their_structure() {
+    let Ok(tree) = parse_lossless_source(" '(a #;b) ") else {
+        panic!("parse failed");
+    };
+    let nodes: Vec<&SyntaxElement> = tree.significant_children().collect();
+    let [SyntaxElement::Node(quote)] = nodes.as_slice() else {
+        panic!("expected one node: {:?}", nodes);
+    };
+    assert_eq!(quote.kind, NodeKind::Quote);
+    assert_eq!(quote.index(), Some((0, 1)));
+    assert_eq!(quote.text(), "'(a #;b)");
+
+    let Some(SyntaxElement::Node(list)) = quote.significant_children().nth(1) else {
+        panic!("expected a list");
+    };
+    let kinds: Vec<String> = list.significant_children()
+        .map(|child| match child {
+            SyntaxElement::Node(node) => format!("{:?}", node.kind),
+            SyntaxElement::Token(token) => format!("{:?}", token.token_type),
+        })
+        .collect();
+    assert_eq!(kinds, ["LParen", "Id", "DatumComment", "RParen"]);
+    assert!(list.children.iter().any(|child| matches!(child, SyntaxElement::Token(token) if token.token_type == TokenType::Whitespace)));
+}
+
+
+#[test]
+fn cst_reports_unclosed_lists() {
+    assert_eq!(parse_lossless_source("(a\n  (b c)").err(), Some(ParseError::UnclosedList((0, 0))));
+    assert_eq!(parse_lossless_source("a )").err(), Some(ParseError::UnexpectedToken((0, 2))));
+}
+
+
+#[test]
+fn dotted_lists() {
+    let Ok(program) = parse_source("(a . b) '(1 2 . (3)) (x . (y . ()))") else {
+        panic!("parse failed");
+    };
+    let printed: Vec<String> = program.iter().map(|datum| datum.to_string()).collect();
+    assert_eq!(printed, ["(a . b)", "'(1 2 . (3))", "(x . (y . ()))"]);
+    let DatumKind::DottedList(items, tail) = &program[0].kind else {
+        panic!("expected a dotted list");
+    };
+    assert_eq!(items, &[symbol("a", (0, 1))]);
+    assert_eq!(tail.as_ref(), &symbol("b", (0, 5)));
+}
+
+
+#[test]
+fn misplaced_dots() {
+    for (source, index) in [("(. a)", (0, 1)), ("(a .)", (0, 3)), ("(a . b c)", (0, 3)), ("(a . . b)", (0, 3)), (".", (0, 0))] {
+        assert_eq!(parse_source(source).err(), Some(ParseError::MisplacedDot(index)), "{}", source);
+    }
+    assert_eq!(parse_lossless_source("(a . b c)").err(), Some(ParseError::MisplacedDot((0, 3))));
+    let Ok(tree) = parse_lossless_source("(a .  b) ; x\n") else {
+        panic!("parse failed");
+    };
+    assert_eq!(tree.to_string(), "(a .  b) ; x\n");
+}
+
+
+#[test]
+fn quasiquote_prefixes() {
+    let Ok(program) = parse_source("`(a ,b ,@(c d) . ,e) ,@x") else {
+        panic!("parse failed");
+    };
+    let printed: Vec<String> = program.iter().map(|datum| datum.to_string()).collect();
+    assert_eq!(printed, ["`(a ,b ,@(c d) . ,e)", ",@x"]);
+    let DatumKind::UnquoteSplicing(spliced) = &program[1].kind else {
+        panic!("expected unquote-splicing");
+    };
+    assert_eq!((program[1].index, spliced.index), ((0, 21), (0, 23)));
+    assert_eq!(parse_source("(a `)").err(), Some(ParseError::UnexpectedToken((0, 4))));
+}
+
+
+#[test]
+fn vector_literals() {
+    let Ok(program) = parse_source("#(1 #(a) \"s\") '#()") else {
+        panic!("parse failed");
+    };
+    let printed: Vec<String> = program.iter().map(|datum| datum.to_string()).collect();
+    assert_eq!(printed, ["#(1 #(a) \"s\")", "'#()"]);
+    let DatumKind::Vector(items) = &program[0].kind else {
+        panic!("expected a vector");
+    };
+    assert_eq!(items[1].index, (0, 4));
+    assert_eq!(parse_source("#(a . b)").err(), Some(ParseError::MisplacedDot((0, 4))));
+    assert_eq!(parse_source("#(a").err(), Some(ParseError::UnclosedList((0, 0))));
+    let Ok(tree) = parse_lossless_source("#( 1  2 ) ; v") else {
+        panic!("parse failed");
+    };
+    assert_eq!(tree.to_string(), "#( 1  2 ) ; v");
+}
+
+
+// 出错的记号不是`(`时只跳过该记号，其后的表达式照常识别
+#[test]
+fn parse_all_resyncs_after_a_stray_token() {
+    let (program, errors) = parse_all_source(". (define x 1) ) (foo)");
+    assert_eq!(program, ["(define x 1)", "(foo)"]);
+    assert_eq!(errors, [ParseError::MisplacedDot((0, 0)), ParseError::UnexpectedToken((0, 15))]);
+}
+
+
+// 两种语法树由同一文法得到：数据注释作用的范围一致，CST按原样还原输入
+#[test]
+fn datum_tree_and_cst_agree_on_datum_comments() {
+    let input = "#; #; a b c ' #; d e (f . #; g h) ; end\n";
+    let Ok(program) = parse_source(input) else {
+        panic!("parse failed");
+    };
+    let program: Vec<String> = program.iter().map(|datum| datum.to_string()).collect();
+    assert_eq!(program, ["c", "'e", "(f . h)"]);
+
+    let Ok(root) = parse_lossless_source(input) else {
+        panic!("parse failed");
+    };
+    assert_eq!(root.text(), input);
+}
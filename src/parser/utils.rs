@@ -1,74 +1,345 @@
-use crate::{ParseError::{self, *}, TableItem, TokenType::{self, *}, TokenUnit};
+use crate::{Datum, DatumKind, ParseError::{self, *}, TableItem, TokenType::*, TokenUnit, ValueType, cst::NodeKind};
 
 
-// 开始符号的子程序：`start -> '(list) | (list) | 'atom | atom`
-pub fn parse_start<'a>(tokens: &'a [TokenUnit], token_table: &Vec<TableItem>) -> Result<&'a [TokenUnit], ParseError> {
-    // 匹配可选的`'`
-    let mut tokens = tokens;
-    if let Some(first) = tokens.get(0) {
-        if first.token_type == QuoteMark {
-            tokens = expect_ts(tokens, token_table, QuoteMark)?;
+// 语法分析的事件接收者：文法只在Parser中实现一次，
+// 由不同的接收者分别构造语法树（DatumBuilder）与无损语法树（CstBuilder）
+pub trait Sink {
+    // 开始一个节点：整个输入、列表、向量、前缀表达式或数据注释
+    fn open(&mut self, kind: NodeKind);
+    // 当前节点中的一个词法单元（含trivia）
+    fn token(&mut self, token_unit: &TokenUnit, table_item: &TableItem) -> Result<(), ParseError>;
+    // 结束最近开始的节点
+    fn close(&mut self);
+}
+
+
+// 递归下降的语法分析器：识别词法单元序列，并把识别的过程依次通知sink
+pub struct Parser<'a, S: Sink> {
+    tokens: &'a [TokenUnit],
+    token_table: &'a [TableItem],
+    pub sink: S,
+}
+
+
+impl<'a, S: Sink> Parser<'a, S> {
+    pub fn new(tokens: &'a [TokenUnit], token_table: &'a [TableItem], sink: S) -> Parser<'a, S> {
+        Parser { tokens, token_table, sink }
+    }
+
+    // 尚未识别的词法单元
+    pub fn rest(&self) -> &'a [TokenUnit] {
+        self.tokens
+    }
+
+    // `program -> trivia* (element trivia*)*`
+    pub fn program(&mut self) -> Result<(), ParseError> {
+        self.sink.open(NodeKind::Program);
+        loop {
+            self.trivia()?;
+            if self.tokens.is_empty() {
+                break;
+            }
+            self.element()?;
+        }
+        self.sink.close();
+        Ok(())
+    }
+
+    // 顶层或列表中的一个元素：`element -> start | #; datum`
+    pub fn element(&mut self) -> Result<(), ParseError> {
+        match self.tokens.first().map(|token_unit| token_unit.token_type) {
+            Some(DatumComment) => self.comment(),
+            _ => self.start(),
         }
     }
 
-    if let Some(first) = tokens.get(0) {
-        if is_atom(first.token_type) {
-            expect_ts(tokens, token_table, first.token_type)
-        } else if first.token_type == LParen {
-            let tokens = expect_ts(tokens, token_table, LParen)?;
-            let tokens = parse_list(tokens, token_table)?;
-            Ok(expect_ts(tokens, token_table, RParen)?)
-        } else {
-            match token_table.get(first.table_ptr) {
-                Some(table_item) => Err(UnexpectedToken(table_item.index)),
-                None => Err(UnknownScanError)
+    // 数据注释`#; datum`：其中的表达式整体被忽略
+    fn comment(&mut self) -> Result<(), ParseError> {
+        self.sink.open(NodeKind::DatumComment);
+        self.bump()?;
+        self.trivia()?;
+        self.datum()?;
+        self.sink.close();
+        Ok(())
+    }
+
+    // 前缀记号、`#;`及`.`之后须出现的表达式，其前可有数据注释
+    fn datum(&mut self) -> Result<(), ParseError> {
+        while let Some(first) = self.tokens.first() && first.token_type == DatumComment {
+            self.comment()?;
+            self.trivia()?;
+        }
+        self.start()
+    }
+
+    // 开始符号的子程序：`start -> prefix datum | (list) | #(list) | atom`，其中`prefix -> ' | ` | , | ,@`
+    fn start(&mut self) -> Result<(), ParseError> {
+        let Some(first) = self.tokens.first() else {
+            return Err(UnexpectedEndOfInput);
+        };
+
+        match first.token_type {
+            QuoteMark | QuasiquoteMark | UnquoteMark | UnquoteSplicingMark => {
+                self.sink.open(match first.token_type {
+                    QuasiquoteMark => NodeKind::Quasiquote,
+                    UnquoteMark => NodeKind::Unquote,
+                    UnquoteSplicingMark => NodeKind::UnquoteSplicing,
+                    _ => NodeKind::Quote,
+                });
+                self.bump()?;
+                self.trivia()?;
+                self.datum()?;
+                self.sink.close();
+                Ok(())
+            },
+            LParen | VectorParen => self.list(),
+            RParen => Err(UnexpectedToken(self.index_of(first)?)),
+            // 列表之外的`.`
+            Dot => Err(MisplacedDot(self.index_of(first)?)),
+            _ => self.bump(),
+        }
+    }
+
+    // 非终结符list的子程序：`list -> element* | element+ . datum`，数据注释不计为元素
+    // 以循环代替尾递归，避免长列表耗尽调用栈；向量中不允许`.`
+    fn list(&mut self) -> Result<(), ParseError> {
+        let open = &self.tokens[0];
+        let vector = open.token_type == VectorParen;
+        let index = self.index_of(open)?;
+        self.sink.open(if vector { NodeKind::Vector } else { NodeKind::List });
+        self.bump()?;
+
+        // 已识别的元素个数；`.`的位置及其后的元素个数
+        let mut items = 0;
+        let mut dot: Option<((usize, usize), usize)> = None;
+        loop {
+            self.trivia()?;
+            let Some(first) = self.tokens.first() else {
+                return Err(UnclosedList(index));
+            };
+            match first.token_type {
+                RParen => {
+                    if let Some((dot_index, 0)) = dot {
+                        return Err(MisplacedDot(dot_index));
+                    }
+                    self.bump()?;
+                    self.sink.close();
+                    return Ok(());
+                },
+                Dot => {
+                    let dot_index = self.index_of(first)?;
+                    if let Some((dot_index, _)) = dot {
+                        return Err(MisplacedDot(dot_index));
+                    }
+                    if items == 0 || vector {
+                        return Err(MisplacedDot(dot_index));
+                    }
+                    dot = Some((dot_index, 0));
+                    self.bump()?;
+                },
+                token_type => {
+                    if let Some((dot_index, 1)) = dot && token_type != DatumComment {
+                        return Err(MisplacedDot(dot_index));
+                    }
+                    // 输入在列表内结束时，报告未闭合的`(`的位置
+                    self.element().map_err(|e| match e {
+                        UnexpectedEndOfInput => UnclosedList(index),
+                        e => e,
+                    })?;
+                    if token_type != DatumComment {
+                        items += 1;
+                        if let Some((_, after)) = &mut dot {
+                            *after += 1;
+                        }
+                    }
+                },
             }
         }
-    } else {
-        Err(UnexpectedEndOfInput)
     }
+
+    // 连续的trivia
+    fn trivia(&mut self) -> Result<(), ParseError> {
+        while let Some(first) = self.tokens.first() && first.token_type.is_trivia() {
+            self.bump()?;
+        }
+        Ok(())
+    }
+
+    // 把首个词法单元交给sink
+    fn bump(&mut self) -> Result<(), ParseError> {
+        let Some((first, tokens)) = self.tokens.split_first() else {
+            return Err(UnexpectedEndOfInput);
+        };
+        let Some(table_item) = self.token_table.get(first.table_ptr) else {
+            return Err(UnknownScanError);
+        };
+        self.sink.token(first, table_item)?;
+        self.tokens = tokens;
+        Ok(())
+    }
+
+    // 查询词法单元在源码中的位置
+    fn index_of(&self, token_unit: &TokenUnit) -> Result<(usize, usize), ParseError> {
+        match self.token_table.get(token_unit.table_ptr) {
+            Some(table_item) => Ok(table_item.index),
+            None => Err(UnknownScanError),
+        }
+    }
+}
+
+
+// 构造语法树的接收者：忽略trivia与数据注释
+#[derive(Default)]
+pub struct DatumBuilder {
+    // 尚未结束的节点
+    stack: Vec<PartialDatum>,
+    // 位于数据注释中的节点层数，大于0时忽略全部事件
+    ignored: usize,
+    // 已识别的顶层表达式
+    pub program: Vec<Datum>,
 }
 
 
-// 非终结符list的子程序：`list -> start list | epsilon`
-fn parse_list<'a>(tokens: &'a [TokenUnit], token_table: &Vec<TableItem>) -> Result<&'a [TokenUnit], ParseError> {
-    match tokens.get(0) {
-        Some(token_unit) => {
-            if token_unit.token_type == LParen || is_atom(token_unit.token_type) || token_unit.token_type == QuoteMark {
-                let tokens = parse_start(tokens, token_table)?;
-                Ok(parse_list(tokens, token_table)?)
-            } else if token_unit.token_type == RParen {
-                Ok(tokens)
-            } else {
-                match token_table.get(token_unit.table_ptr) {
-                    Some(table_item) => Err(UnexpectedToken(table_item.index)),
-                    None => Err(UnknownScanError)
+// 尚未结束的节点：位置为其首个词法单元（`(`、`#(`或前缀记号）的位置
+struct PartialDatum {
+    kind: NodeKind,
+    index: Option<(usize, usize)>,
+    items: Vec<Datum>,
+    // 是否出现了`.`：若是，最后一个元素为cdr
+    dotted: bool,
+}
+
+
+impl DatumBuilder {
+    fn push(&mut self, datum: Datum) {
+        match self.stack.last_mut() {
+            Some(partial) => partial.items.push(datum),
+            None => self.program.push(datum),
+        }
+    }
+}
+
+
+impl Sink for DatumBuilder {
+    fn open(&mut self, kind: NodeKind) {
+        match kind {
+            NodeKind::DatumComment => self.ignored += 1,
+            _ if self.ignored > 0 => self.ignored += 1,
+            // 顶层表达式直接加入program
+            NodeKind::Program => (),
+            kind => self.stack.push(PartialDatum { kind, index: None, items: Vec::new(), dotted: false }),
+        }
+    }
+
+    fn token(&mut self, token_unit: &TokenUnit, table_item: &TableItem) -> Result<(), ParseError> {
+        if self.ignored > 0 || token_unit.token_type.is_trivia() {
+            return Ok(());
+        }
+        if let Some(partial) = self.stack.last_mut() && partial.index.is_none() {
+            partial.index = Some(table_item.index);
+            return Ok(());
+        }
+
+        match token_unit.token_type {
+            RParen => (),
+            Dot => {
+                if let Some(partial) = self.stack.last_mut() {
+                    partial.dotted = true;
                 }
-            }
-        },
-        None => Err(UnexpectedEndOfInput)
+            },
+            _ => self.push(build_atom(token_unit, table_item)?),
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if self.ignored > 0 {
+            self.ignored -= 1;
+            return;
+        }
+        let Some(PartialDatum { kind, index, mut items, dotted }) = self.stack.pop() else {
+            return;
+        };
+
+        let kind = match kind {
+            NodeKind::List if dotted && let Some(tail) = items.pop() => DatumKind::DottedList(items, Box::new(tail)),
+            NodeKind::List => DatumKind::List(items),
+            NodeKind::Vector => DatumKind::Vector(items),
+            prefix => {
+                let Some(datum) = items.pop().map(Box::new) else {
+                    return;
+                };
+                match prefix {
+                    NodeKind::Quasiquote => DatumKind::Quasiquote(datum),
+                    NodeKind::Unquote => DatumKind::Unquote(datum),
+                    NodeKind::UnquoteSplicing => DatumKind::UnquoteSplicing(datum),
+                    _ => DatumKind::Quote(datum),
+                }
+            },
+        };
+        self.push(Datum { kind, index: index.unwrap_or_default() });
     }
 }
 
 
-// 试图匹配1个指定的终结符
-fn expect_ts<'a>(tokens: &'a [TokenUnit], token_table: &Vec<TableItem>, ts: TokenType) -> Result<&'a [TokenUnit], ParseError> {
-    if let Some(token_unit) = tokens.get(0) {
-        if token_unit.token_type == ts {
-            Ok(&tokens[1..])
-        } else {
-            match token_table.get(token_unit.table_ptr) {
-                Some(table_item) => Err(UnexpectedToken(table_item.index)),
-                None => Err(UnknownScanError)
-            }
+// 错误恢复：求出从start起出错的顶层表达式之后的同步点，返回同步点及其中未闭合的`(`的位置
+// (1) 括号在输入结束前配平：同步于配平的`)`之后
+// (2) 否则：同步于下一个位于行首的`(`（视为新的顶层表达式），其前尚未闭合的`(`即为缺少`)`之处
+// 表达式（除去前缀记号与`#;`）不以`(`开头时，出错的只是这一个记号（如多余的`.`或`)`），同步于其后
+pub fn resync(tokens: &[TokenUnit], start: usize, token_table: &[TableItem]) -> (usize, Vec<(usize, usize)>) {
+    let index_of = |i: usize| token_table.get(tokens[i].table_ptr).map(|item| item.index);
+
+    let head = tokens[start..].iter().position(|token_unit| !token_unit.token_type.is_prefix() && token_unit.token_type != DatumComment).map_or(tokens.len(), |offset| start + offset);
+    if head < tokens.len() && !tokens[head].token_type.is_open() {
+        return (head + 1, Vec::new());
+    }
+
+    let mut depth = 0;
+    for (i, token_unit) in tokens.iter().enumerate().skip(start) {
+        match token_unit.token_type {
+            LParen | VectorParen => depth += 1,
+            RParen => {
+                // 多余的`)`或配平的`)`
+                if depth <= 1 {
+                    return (i + 1, Vec::new());
+                }
+                depth -= 1;
+            },
+            _ => (),
+        }
+    }
+
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    for (i, token_unit) in tokens.iter().enumerate().skip(start) {
+        match token_unit.token_type {
+            LParen | VectorParen => {
+                let index = index_of(i);
+                if !open.is_empty() && matches!(index, Some((_, 0))) {
+                    return (i, open);
+                }
+                open.extend(index);
+            },
+            RParen => {
+                open.pop();
+            },
+            _ => (),
         }
-    } else {
-        Err(UnexpectedEndOfInput)
     }
+
+    (tokens.len(), open)
 }
 
 
-// 检验token是否为终结符atom
-fn is_atom(token_type: TokenType) -> bool {
-    token_type != LParen && token_type != RParen && token_type != QuoteMark
+// 由终结符atom构造叶节点
+fn build_atom(token_unit: &TokenUnit, table_item: &TableItem) -> Result<Datum, ParseError> {
+    let kind = match (token_unit.token_type, &table_item.value) {
+        (Const, Some(value)) => DatumKind::Const(value.clone()),
+        (Id, Some(ValueType::Str(name))) => DatumKind::Symbol(name.clone()),
+        (token_type, _) => match token_type.lexeme() {
+            Some(lexeme) => DatumKind::Symbol(String::from(lexeme)),
+            None => return Err(UnknownScanError),
+        },
+    };
+
+    Ok(Datum { kind, index: table_item.index })
 }
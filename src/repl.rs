@@ -0,0 +1,182 @@
+use std::{fs, io::{self, BufRead, Write}};
+
+use crate::{ParseError, ScanError, scanner::scan, parser::parse, eval::{Env, Value}, expand::{MacroEnv, expand_and_eval}, diagnostics::Diagnostic};
+#[cfg(test)]
+mod tests;
+
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = ". ";
+
+
+// 交互式解释器：各次输入共享同一全局环境及宏定义
+pub struct Repl {
+    env: Env,
+    macros: MacroEnv,
+    // 尚未闭合的多行输入
+    buffer: String,
+    history: Vec<String>,
+    // 错误信息是否使用ANSI颜色
+    color: bool,
+}
+
+
+// 一段输入的处理结果
+enum Outcome {
+    // 括号尚未闭合，需继续读入
+    Incomplete,
+    Done,
+    Quit,
+}
+
+
+impl Repl {
+    pub fn new(color: bool) -> Repl {
+        let env = Env::global();
+        Repl { macros: MacroEnv::global(&env), env, buffer: String::new(), history: Vec::new(), color }
+    }
+
+    // 逐行读入并求值，直至输入结束或`:quit`；求值结果写入output，错误信息写入errors
+    pub fn run<R: BufRead, W: Write, E: Write>(&mut self, mut input: R, output: &mut W, errors: &mut E) -> io::Result<()> {
+        loop {
+            write!(output, "{}", if self.buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT })?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                return Ok(());
+            }
+
+            if let Outcome::Quit = self.feed(line.trim_end_matches(['\n', '\r']), output, errors)? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn feed<W: Write, E: Write>(&mut self, line: &str, output: &mut W, errors: &mut E) -> io::Result<Outcome> {
+        // 元命令只在新输入的开头识别
+        if self.buffer.is_empty() && line.trim_start().starts_with(':') {
+            // 重新执行历史输入的命令本身不记入历史，记入的是被重新执行的输入
+            if !line.trim().starts_with(":history ") {
+                self.history.push(String::from(line.trim()));
+            }
+            return self.meta_command(line.trim(), output, errors);
+        }
+
+        if self.buffer.is_empty() && line.trim().is_empty() {
+            return Ok(Outcome::Done);
+        }
+
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+
+        let source = self.buffer.clone();
+        match self.eval_source(&source, "<repl>", true, output, errors)? {
+            Outcome::Incomplete => Ok(Outcome::Incomplete),
+            outcome => {
+                self.history.push(String::from(source.trim_end()));
+                self.buffer.clear();
+                Ok(outcome)
+            },
+        }
+    }
+
+    // origin为源码的来源，用于错误信息；interactive为真时未闭合的输入等待后续行
+    fn eval_source<W: Write, E: Write>(&mut self, source: &str, origin: &str, interactive: bool, output: &mut W, errors: &mut E) -> io::Result<Outcome> {
+        let (tokens, token_table) = match scan(source) {
+            Ok(result) => result,
+            // 块注释或字符串未闭合：继续读入后续行
+            Err(ScanError::UnterminatedComment(_) | ScanError::UnterminatedString(_)) if interactive => return Ok(Outcome::Incomplete),
+            Err(e) => {
+                write!(errors, "{}", Diagnostic::from(&e).render(source, origin, self.color))?;
+                return Ok(Outcome::Done);
+            },
+        };
+
+        let program = match parse(&tokens, &token_table) {
+            Ok(program) => program,
+            // 括号未闭合：继续读入后续行
+            Err(ParseError::UnexpectedEndOfInput | ParseError::UnclosedList(_)) if interactive => return Ok(Outcome::Incomplete),
+            Err(e) => {
+                write!(errors, "{}", Diagnostic::from(&e).render(source, origin, self.color))?;
+                return Ok(Outcome::Done);
+            },
+        };
+
+        let result = expand_and_eval(&program, &self.macros);
+        io::stdout().flush()?;
+        match result {
+            Ok(Value::Void) => (),
+            Ok(value) => writeln!(output, "{}", value)?,
+            Err(e) => write!(errors, "{}", Diagnostic::from(&e).render(source, origin, self.color))?,
+        }
+
+        Ok(Outcome::Done)
+    }
+
+    fn meta_command<W: Write, E: Write>(&mut self, command: &str, output: &mut W, errors: &mut E) -> io::Result<Outcome> {
+        let mut parts = command.splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        let arg = parts.next().map(str::trim).unwrap_or("");
+
+        match name {
+            ":quit" | ":q" => return Ok(Outcome::Quit),
+
+            ":load" => {
+                if arg.is_empty() {
+                    writeln!(errors, "usage: :load <file>")?;
+                } else {
+                    match fs::read_to_string(arg) {
+                        Ok(source) => {
+                            self.eval_source(&source, arg, false, output, errors)?;
+                        },
+                        Err(e) => writeln!(errors, "cannot read `{}`: {}", arg, e)?,
+                    }
+                }
+            },
+
+            ":env" => {
+                // 只列出用户定义的绑定
+                for (name, value) in self.env.bindings() {
+                    if !matches!(value, Value::Builtin(_)) {
+                        writeln!(output, "{} = {}", name, value)?;
+                    }
+                }
+            },
+
+            ":history" if arg.is_empty() => {
+                for (i, entry) in self.history.iter().enumerate() {
+                    writeln!(output, "{:>4}  {}", i + 1, entry)?;
+                }
+            },
+
+            // 回显并重新执行第n条历史输入
+            ":history" => {
+                let entry = arg.parse::<usize>().ok().and_then(|n| self.history.get(n.checked_sub(1)?)).cloned();
+                match entry {
+                    Some(entry) => {
+                        writeln!(output, "{}", entry)?;
+                        let mut outcome = Outcome::Done;
+                        for line in entry.lines() {
+                            outcome = self.feed(line, output, errors)?;
+                        }
+                        return Ok(outcome);
+                    },
+                    None => writeln!(errors, "no history entry `{}`", arg)?,
+                }
+            },
+
+            ":help" => {
+                writeln!(output, ":load <file>  evaluate a file in the current environment")?;
+                writeln!(output, ":env          list user-defined bindings")?;
+                writeln!(output, ":history [n]  list previous inputs, or re-run input n")?;
+                writeln!(output, ":quit         leave the REPL")?;
+            },
+
+            other => writeln!(errors, "unknown command `{}`, try :help", other)?,
+        }
+
+        Ok(Outcome::Done)
+    }
+}
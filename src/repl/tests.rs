@@ -0,0 +1,91 @@
+use std::io::Cursor;
+
+use super::Repl;
+
+
+// 把input逐行交给解释器，返回其全部输出
+fn session(input: &str) -> String {
+    session_with_errors(input).0
+}
+
+
+// 同session，另返回写入错误输出的内容
+fn session_with_errors(input: &str) -> (String, String) {
+    let (mut output, mut errors) = (Vec::new(), Vec::new());
+    Repl::new(false).run(Cursor::new(input), &mut output, &mut errors).expect("repl failed");
+    (String::from_utf8(output).expect("output is not UTF-8"), String::from_utf8(errors).expect("errors are not UTF-8"))
+}
+
+
+#[test]
+fn definitions_persist_across_inputs() {
+    assert_eq!(session("(define x 40)\n(+ x 2)\n"), "> > 42\n> \n");
+}
+
+
+#[test]
+fn unclosed_input_continues_on_next_line() {
+    assert_eq!(session("(+ 1\n   2)\n"), "> . 3\n> \n");
+}
+
+
+#[test]
+fn meta_commands() {
+    let output = session("(define (f) 1)\n(f)\n:env\n:history\n:quit\n(f)\n");
+    assert_eq!(output, "> > 1\n> f = #<procedure f>\n>    1  (define (f) 1)\n   2  (f)\n   3  :env\n   4  :history\n> ");
+}
+
+
+#[test]
+fn errors_do_not_end_the_session() {
+    let (output, errors) = session_with_errors("(car '())\n)\n:nope\n1\n");
+    assert_eq!(output, "> > > > 1\n> \n");
+    let expected = concat!(
+        "error: type mismatch: `car` expected a pair, got ()\n",
+        " --> <repl>:1:1\n",
+        "  |\n",
+        "1 | (car '())\n",
+        "  | ^^^^^^^^^\n",
+        "error: unexpected token\n",
+        " --> <repl>:1:1\n",
+        "  |\n",
+        "1 | )\n",
+        "  | ^ expected an expression\n",
+        "unknown command `:nope`, try :help\n",
+    );
+    assert_eq!(errors, expected);
+}
+
+
+// `:history n`回显并重新执行第n条输入，记入历史的是被重新执行的输入
+#[test]
+fn history_re_runs_an_entry() {
+    let (output, errors) = session_with_errors("(define n 1)\n(set! n (+ n 1))\n:history 2\nn\n:history\n:history 9\n");
+    let expected = concat!(
+        "> > > (set! n (+ n 1))\n",
+        "> 3\n",
+        ">    1  (define n 1)\n",
+        "   2  (set! n (+ n 1))\n",
+        "   3  (set! n (+ n 1))\n",
+        "   4  n\n",
+        "   5  :history\n",
+        "> > \n",
+    );
+    assert_eq!(output, expected);
+    assert_eq!(errors, "no history entry `9`\n");
+
+    assert_eq!(session("(+ 1\n   2)\n:history 1\n"), "> . 3\n> (+ 1\n   2)\n3\n> \n");
+}
+
+
+#[test]
+fn unterminated_string_continues_on_next_line() {
+    assert_eq!(session("(car (list 1 \"a\nb\"))\n"), "> . 1\n> \n");
+}
+
+
+#[test]
+fn macros_persist_across_inputs() {
+    let output = session("(define-syntax twice (syntax-rules () ((_ e) (begin e e))))\n(define n 0)\n(twice (set! n (+ n 1)))\nn\n");
+    assert_eq!(output, "> > > > 2\n> \n");
+}
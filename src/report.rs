@@ -0,0 +1,187 @@
+// 词法、语法分析结果的结构化输出（`--format json` / `--format sexpr`）
+//
+// 模式（version 1）。行号、列号均从1开始，列号按字符计数；
+// 新增字段只会追加在对象末尾，已有字段的名称与含义保持不变。
+//
+// document := { "version": 1,
+//               "tokens": [token],
+//               "table": [entry],
+//               "tree": [node] | null,        （仅parse输出；存在词法错误时为null）
+//               "errors": [error] }
+// token    := { "type": TokenType变体名, "lexeme": 源码文本, "value": value | null,
+//               "row": int, "column": int, "table_index": int }
+// entry    := { "row": int, "column": int, "length": int, "value": value | null }
+// value    := { "type": "int" | "float" | "string" | "bool", "value": 对应的JSON值 }
+//           | { "type": "float", "value": "+inf.0" | "-inf.0" | "+nan.0" }   （JSON不能表示的浮点数，按Scheme写法输出）
+//           | { "type": "bignum" | "rational", "value": string }   （超出i64的整数、精确分数，按十进制文本输出）
+//           | { "type": "char", "value": string }                 （仅含该字符的字符串）
+//               （Id的value为其名称，type为"string"）
+// node     := { "kind": "const", "row", "column", "value": value }
+//           | { "kind": "symbol", "row", "column", "name": string }
+//           | { "kind": "list", "row", "column", "items": [node] }
+//           | { "kind": "dotted_list", "row", "column", "items": [node], "tail": node }
+//           | { "kind": "vector", "row", "column", "items": [node] }
+//           | { "kind": "quote" | "quasiquote" | "unquote" | "unquote_splicing", "row", "column", "datum": node }
+// error    := { "stage": "scan" | "parse", "kind": 错误变体名, "message": string,
+//               "row": int | null, "column": int | null }
+//
+// sexpr格式输出同一结构：对象输出为属性表`(key value ...)`，数组输出为列表，null输出为`()`。
+
+use crate::{Datum, DatumKind, ParseError, ScanError, TableItem, TokenUnit, ValueType, json::Json, number::Number};
+#[cfg(test)]
+mod tests;
+
+
+const SCHEMA_VERSION: i64 = 1;
+
+
+// scan命令的输出
+pub fn scan_document(source: &str, tokens: &[TokenUnit], token_table: &[TableItem], errors: &[ScanError]) -> Json {
+    Json::object([
+        ("version", Json::Int(SCHEMA_VERSION)),
+        ("tokens", tokens_json(source, tokens, token_table)),
+        ("table", table_json(token_table)),
+        ("errors", Json::Array(errors.iter().map(scan_error_json).collect())),
+    ])
+}
+
+
+// parse命令的输出；program为None表示因词法错误未做语法分析
+pub fn parse_document(
+    source: &str,
+    tokens: &[TokenUnit],
+    token_table: &[TableItem],
+    program: Option<&[Datum]>,
+    scan_errors: &[ScanError],
+    parse_errors: &[ParseError],
+) -> Json {
+    let errors = scan_errors.iter().map(scan_error_json)
+        .chain(parse_errors.iter().map(parse_error_json))
+        .collect();
+
+    Json::object([
+        ("version", Json::Int(SCHEMA_VERSION)),
+        ("tokens", tokens_json(source, tokens, token_table)),
+        ("table", table_json(token_table)),
+        ("tree", match program {
+            Some(program) => Json::Array(program.iter().map(datum_json).collect()),
+            None => Json::Null,
+        }),
+        ("errors", Json::Array(errors)),
+    ])
+}
+
+
+fn tokens_json(source: &str, tokens: &[TokenUnit], token_table: &[TableItem]) -> Json {
+    let chars: Vec<char> = source.chars().collect();
+    // 各行首字符的字符偏移
+    let mut line_starts = vec![0];
+    line_starts.extend(chars.iter().enumerate().filter(|(_, ch)| **ch == '\n').map(|(i, _)| i + 1));
+
+    let tokens = tokens.iter().filter_map(|token| {
+        let item = token_table.get(token.table_ptr)?;
+        let start = line_starts.get(item.index.0).map_or(chars.len(), |line_start| line_start + item.index.1);
+        let lexeme: String = chars.iter().skip(start).take(item.len).collect();
+
+        Some(Json::object([
+            ("type", Json::str(format!("{:?}", token.token_type))),
+            ("lexeme", Json::Str(lexeme)),
+            ("value", item.value.as_ref().map_or(Json::Null, value_json)),
+            ("row", Json::Int(item.index.0 as i64 + 1)),
+            ("column", Json::Int(item.index.1 as i64 + 1)),
+            ("table_index", Json::Int(token.table_ptr as i64)),
+        ]))
+    });
+
+    Json::Array(tokens.collect())
+}
+
+
+fn table_json(token_table: &[TableItem]) -> Json {
+    Json::Array(token_table.iter().map(|item| Json::object([
+        ("row", Json::Int(item.index.0 as i64 + 1)),
+        ("column", Json::Int(item.index.1 as i64 + 1)),
+        ("length", Json::Int(item.len as i64)),
+        ("value", item.value.as_ref().map_or(Json::Null, value_json)),
+    ])).collect())
+}
+
+
+fn value_json(value: &ValueType) -> Json {
+    let (value_type, value) = match value {
+        ValueType::Number(Number::Int(v)) => ("int", Json::Int(*v as i64)),
+        ValueType::Number(Number::Big(v)) => ("bignum", Json::str(v.to_string())),
+        ValueType::Number(v @ Number::Rational(..)) => ("rational", Json::str(v.to_string())),
+        ValueType::Number(v @ Number::Float(f)) if !f.is_finite() => ("float", Json::str(v.to_string())),
+        ValueType::Number(Number::Float(v)) => ("float", Json::Float(*v)),
+        ValueType::Str(v) => ("string", Json::str(v.as_str())),
+        ValueType::Bool(v) => ("bool", Json::Bool(*v)),
+        ValueType::Char(v) => ("char", Json::str(v.to_string())),
+    };
+    Json::object([("type", Json::str(value_type)), ("value", value)])
+}
+
+
+fn datum_json(datum: &Datum) -> Json {
+    let row = ("row", Json::Int(datum.index.0 as i64 + 1));
+    let column = ("column", Json::Int(datum.index.1 as i64 + 1));
+
+    match &datum.kind {
+        DatumKind::Const(value) => Json::object([("kind", Json::str("const")), row, column, ("value", value_json(value))]),
+        DatumKind::Symbol(name) => Json::object([("kind", Json::str("symbol")), row, column, ("name", Json::str(name.as_str()))]),
+        DatumKind::List(items) => Json::object([
+            ("kind", Json::str("list")), row, column,
+            ("items", Json::Array(items.iter().map(datum_json).collect())),
+        ]),
+        DatumKind::Vector(items) => Json::object([
+            ("kind", Json::str("vector")), row, column,
+            ("items", Json::Array(items.iter().map(datum_json).collect())),
+        ]),
+        DatumKind::DottedList(items, tail) => Json::object([
+            ("kind", Json::str("dotted_list")), row, column,
+            ("items", Json::Array(items.iter().map(datum_json).collect())),
+            ("tail", datum_json(tail)),
+        ]),
+        DatumKind::Quote(quoted) => Json::object([("kind", Json::str("quote")), row, column, ("datum", datum_json(quoted))]),
+        DatumKind::Quasiquote(quoted) => Json::object([("kind", Json::str("quasiquote")), row, column, ("datum", datum_json(quoted))]),
+        DatumKind::Unquote(quoted) => Json::object([("kind", Json::str("unquote")), row, column, ("datum", datum_json(quoted))]),
+        DatumKind::UnquoteSplicing(quoted) => {
+            Json::object([("kind", Json::str("unquote_splicing")), row, column, ("datum", datum_json(quoted))])
+        },
+    }
+}
+
+
+fn scan_error_json(e: &ScanError) -> Json {
+    let kind = match e {
+        ScanError::InvalidCharacter(_) => "InvalidCharacter",
+        ScanError::InvalidToken(_) => "InvalidToken",
+        ScanError::UnterminatedComment(_) => "UnterminatedComment",
+        ScanError::InvalidEscape(_) => "InvalidEscape",
+        ScanError::UnterminatedString(_) => "UnterminatedString",
+    };
+    error_json("scan", kind, e.message(), Some(e.index()))
+}
+
+
+fn parse_error_json(e: &ParseError) -> Json {
+    let kind = match e {
+        ParseError::UnexpectedToken(_) => "UnexpectedToken",
+        ParseError::UnexpectedEndOfInput => "UnexpectedEndOfInput",
+        ParseError::UnclosedList(_) => "UnclosedList",
+        ParseError::MisplacedDot(_) => "MisplacedDot",
+        ParseError::UnknownScanError => "UnknownScanError",
+    };
+    error_json("parse", kind, e.message(), e.index())
+}
+
+
+fn error_json(stage: &str, kind: &str, message: String, index: Option<(usize, usize)>) -> Json {
+    Json::object([
+        ("stage", Json::str(stage)),
+        ("kind", Json::str(kind)),
+        ("message", Json::Str(message)),
+        ("row", index.map_or(Json::Null, |(row, _)| Json::Int(row as i64 + 1))),
+        ("column", index.map_or(Json::Null, |(_, column)| Json::Int(column as i64 + 1))),
+    ])
+}
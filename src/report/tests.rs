@@ -0,0 +1,89 @@
+use crate::{json::Json, scanner::{scan, scan_all}, parser::parse_all};
+use super::{parse_document, scan_document};
+
+
+#[test]
+fn scan_document_lists_tokens_table_and_errors() {
+    let source = "(f \"é\")\n{";
+    let (tokens, token_table, errors) = scan_all(source);
+    let expected = concat!(
+        r#"{"version":1,"tokens":["#,
+        r#"{"type":"LParen","lexeme":"(","value":null,"row":1,"column":1,"table_index":0},"#,
+        r#"{"type":"Id","lexeme":"f","value":{"type":"string","value":"f"},"row":1,"column":2,"table_index":1},"#,
+        r#"{"type":"Const","lexeme":"\"é\"","value":{"type":"string","value":"é"},"row":1,"column":4,"table_index":2},"#,
+        r#"{"type":"RParen","lexeme":")","value":null,"row":1,"column":7,"table_index":3}],"#,
+        r#""table":["#,
+        r#"{"row":1,"column":1,"length":1,"value":null},"#,
+        r#"{"row":1,"column":2,"length":1,"value":{"type":"string","value":"f"}},"#,
+        r#"{"row":1,"column":4,"length":3,"value":{"type":"string","value":"é"}},"#,
+        r#"{"row":1,"column":7,"length":1,"value":null}],"#,
+        r#""errors":[{"stage":"scan","kind":"InvalidCharacter","message":"invalid character","row":2,"column":1}]}"#,
+    );
+    assert_eq!(scan_document(source, &tokens, &token_table, &errors).to_string(), expected);
+}
+
+
+#[test]
+fn parse_document_includes_the_tree() {
+    let source = "'(1 #t) x";
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed");
+    };
+    let (program, errors) = parse_all(&tokens, &token_table);
+    let document = parse_document(source, &tokens, &token_table, Some(&program), &[], &errors);
+    let expected = concat!(
+        r#"(version 1 tokens ("#,
+        r#"(type "QuoteMark" lexeme "'" value () row 1 column 1 table_index 0) "#,
+        r#"(type "LParen" lexeme "(" value () row 1 column 2 table_index 1) "#,
+        r#"(type "Const" lexeme "1" value (type "int" value 1) row 1 column 3 table_index 2) "#,
+        r##"(type "Const" lexeme "#t" value (type "bool" value #t) row 1 column 5 table_index 3) "##,
+        r#"(type "RParen" lexeme ")" value () row 1 column 7 table_index 4) "#,
+        r#"(type "Id" lexeme "x" value (type "string" value "x") row 1 column 9 table_index 5)) "#,
+        r#"table ("#,
+        r#"(row 1 column 1 length 1 value ()) "#,
+        r#"(row 1 column 2 length 1 value ()) "#,
+        r#"(row 1 column 3 length 1 value (type "int" value 1)) "#,
+        r#"(row 1 column 5 length 2 value (type "bool" value #t)) "#,
+        r#"(row 1 column 7 length 1 value ()) "#,
+        r#"(row 1 column 9 length 1 value (type "string" value "x"))) "#,
+        r#"tree ("#,
+        r#"(kind "quote" row 1 column 1 datum (kind "list" row 1 column 2 items ("#,
+        r#"(kind "const" row 1 column 3 value (type "int" value 1)) "#,
+        r#"(kind "const" row 1 column 5 value (type "bool" value #t))))) "#,
+        r#"(kind "symbol" row 1 column 9 name "x")) "#,
+        r#"errors ())"#,
+    );
+    assert_eq!(document.to_sexpr(), expected);
+}
+
+
+#[test]
+fn parse_document_reports_errors_of_both_stages() {
+    let source = "(a\n(b)";
+    let (tokens, token_table, _) = scan_all(source);
+    let (program, errors) = parse_all(&tokens, &token_table);
+    let document = parse_document(source, &tokens, &token_table, Some(&program), &[], &errors).to_string();
+    assert!(document.ends_with(r#""errors":[{"stage":"parse","kind":"UnclosedList","message":"expected `)` to close `(` opened at 1:1","row":1,"column":1}]}"#));
+
+    let (tokens, token_table, scan_errors) = scan_all("\"open");
+    let document = parse_document("\"open", &tokens, &token_table, None, &scan_errors, &[]).to_string();
+    assert!(document.contains(r#""tree":null,"#));
+    assert!(document.ends_with(r#""errors":[{"stage":"scan","kind":"UnterminatedString","message":"unterminated string literal","row":1,"column":1}]}"#));
+}
+
+
+// inf与NaN的值标记为float，以Scheme写法的字符串给出
+#[test]
+fn non_finite_floats_are_tagged_strings() {
+    let source = "+inf.0 -inf.0 +nan.0";
+    let (tokens, token_table, errors) = scan_all(source);
+    let document = scan_document(source, &tokens, &token_table, &errors);
+    let values: Vec<String> = document.get("table").and_then(Json::as_array).expect("no table").iter()
+        .map(|entry| entry.get("value").expect("no value").to_string())
+        .collect();
+    assert_eq!(values, [
+        r#"{"type":"float","value":"+inf.0"}"#,
+        r#"{"type":"float","value":"-inf.0"}"#,
+        r#"{"type":"float","value":"+nan.0"}"#,
+    ]);
+}
@@ -1,53 +1,210 @@
 mod utils;
+#[cfg(test)]
+mod tests;
 use utils::{tokenize, chars2bytes};
-use crate::{TokenUnit, TableItem, ScanError};
+use crate::{TokenUnit, TokenType, TableItem, ScanError};
 
 
 pub fn scan(input: &str) -> Result<(Vec<TokenUnit>, Vec<TableItem>), ScanError> {
+    let (tokens, token_table, mut errors) = scan_impl(input, false, false);
+    match errors.pop() {
+        Some(e) => Err(e),
+        None => Ok((tokens, token_table)),
+    }
+}
+
+
+// 带错误恢复的词法分析：记录错误后跳至下一个空白符或界定符继续扫描，
+// 返回完整的token序列、符号表及全部词法错误
+pub fn scan_all(input: &str) -> (Vec<TokenUnit>, Vec<TableItem>, Vec<ScanError>) {
+    scan_impl(input, true, false)
+}
+
+
+// 无损词法分析：空白符、换行符与注释也作为词法单元（trivia）输出，
+// 全部词法单元依次首尾相接，恰好覆盖整个输入
+pub fn scan_lossless(input: &str) -> Result<(Vec<TokenUnit>, Vec<TableItem>), ScanError> {
+    let (tokens, token_table, mut errors) = scan_impl(input, false, true);
+    match errors.pop() {
+        Some(e) => Err(e),
+        None => Ok((tokens, token_table)),
+    }
+}
+
+
+// recover为假时遇到第一个错误即停止；lossless为真时保留trivia
+fn scan_impl(input: &str, recover: bool, lossless: bool) -> (Vec<TokenUnit>, Vec<TableItem>, Vec<ScanError>) {
     let mut token_table: Vec<TableItem> = Vec::new();
     let mut tokens: Vec<TokenUnit> = Vec::new();
+    let mut errors: Vec<ScanError> = Vec::new();
+    let mut cursor = Cursor { rest: input, row: 0, column: 0 };
 
-    for (row, mut line) in input.lines().enumerate() {
-        let mut column = whitespace_cnt(line);
-        line = &line[column..];
+    loop {
+        // 识别前导空白符及注释（未闭合的块注释延伸至输入末尾）
+        match cursor.trivia() {
+            Ok(Some((token_type, bytes))) => {
+                if lossless {
+                    tokens.push(TokenUnit { token_type, table_ptr: token_table.len() });
+                    token_table.push(TableItem {
+                        index: (cursor.row, cursor.column),
+                        len: cursor.rest[..bytes].chars().count(),
+                        value: None,
+                    });
+                }
+                cursor.advance(bytes);
+                continue;
+            },
+            Ok(None) => (),
+            Err(e) => {
+                errors.push(e);
+                break;
+            },
+        }
+        if cursor.rest.is_empty() {
+            break;
+        }
+
+        // 字符串常量可跨行，故将剩余的全部输入交给tokenize()
+        let (mut token, table_item) = match tokenize(cursor.rest, cursor.row, cursor.column) {
+            Ok(result) => result,
+            Err(e) => {
+                errors.push(e);
+                if !recover {
+                    break;
+                }
+                cursor.advance(invalid_bytes(cursor.rest));
+                continue;
+            },
+        };
 
-        while !line.is_empty() {
-            let (mut token, table_item) = tokenize(line, row, column)?;
+        // 计算切片索引并移动游标（游标据跨过的换行符更新行列号）
+        let token_bytes = chars2bytes(cursor.rest, token.table_ptr);
+        cursor.advance(token_bytes);
 
-            // 更新column
-            column += token.table_ptr;
+        // 添加token序列
+        token.table_ptr = token_table.len();
+        tokens.push(token);
 
-            // 计算切片索引
-            let token_bytes = chars2bytes(line, token.table_ptr);
+        // 添加符号表条目
+        token_table.push(table_item);
+    }
 
-            // 添加token序列
-            token.table_ptr = token_table.len();
-            tokens.push(token);
+    (tokens, token_table, errors)
+}
 
-            // 添加符号表条目
-            token_table.push(table_item);
 
-            // 切片并去除前导空白符
-            line = &line[token_bytes..];
-            let ws_cnt = whitespace_cnt(line);
-            column += ws_cnt;
-            line = &line[ws_cnt..];
+// 扫描游标：rest为尚未扫描的输入，(row, column)为其首字符的位置
+struct Cursor<'a> {
+    rest: &'a str,
+    row: usize,
+    column: usize,
+}
+
+
+impl Cursor<'_> {
+    // 前移若干字节，并据跨过的字符更新行列号
+    fn advance(&mut self, bytes: usize) {
+        for ch in self.rest[..bytes].chars() {
+            if ch == '\n' {
+                self.row += 1;
+                self.column = 0;
+            } else {
+                self.column += 1;
+            }
         }
+        self.rest = &self.rest[bytes..];
     }
 
-    Ok((tokens, token_table))
+    // 识别位于开头的一段trivia，返回其类型及字节数：
+    // 换行符、（不含换行符的）空白符、行注释`; ...`或可嵌套的块注释`#| ... |#`
+    fn trivia(&self) -> Result<Option<(TokenType, usize)>, ScanError> {
+        let rest = self.rest;
+
+        if rest.starts_with('\n') {
+            return Ok(Some((TokenType::Newline, 1)));
+        }
+        if rest.starts_with("\r\n") {
+            return Ok(Some((TokenType::Newline, 2)));
+        }
+
+        let ws_bytes = whitespace_bytes(rest);
+        if ws_bytes > 0 {
+            return Ok(Some((TokenType::Whitespace, ws_bytes)));
+        }
+
+        if rest.starts_with(';') {
+            // 行注释不含行末的换行符
+            let comment_bytes = rest.find('\n').unwrap_or(rest.len());
+            let comment_bytes = if rest[..comment_bytes].ends_with('\r') { comment_bytes - 1 } else { comment_bytes };
+            return Ok(Some((TokenType::LineComment, comment_bytes)));
+        }
+
+        if rest.starts_with("#|") {
+            return match block_comment_bytes(rest) {
+                Some(comment_bytes) => Ok(Some((TokenType::BlockComment, comment_bytes))),
+                None => Err(ScanError::UnterminatedComment((self.row, self.column))),
+            };
+        }
+
+        Ok(None)
+    }
 }
 
 
-fn whitespace_cnt(line: &str) -> usize {
-    let mut ws_cnt = 0;
+// 开头的空白符的字节数（不含换行符）
+fn whitespace_bytes(line: &str) -> usize {
+    let mut ws_bytes = 0;
 
     for ch in line.chars() {
-        if !ch.is_whitespace() {
-            return ws_cnt;
+        if !ch.is_whitespace() || ch == '\n' || line[ws_bytes..].starts_with("\r\n") {
+            return ws_bytes;
+        }
+        ws_bytes += ch.len_utf8();
+    }
+
+    ws_bytes
+}
+
+
+// 开头的块注释的字节数；块注释未闭合时返回None
+fn block_comment_bytes(rest: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < rest.len() {
+        if rest[i..].starts_with("#|") {
+            depth += 1;
+            i += 2;
+        } else if rest[i..].starts_with("|#") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += rest[i..].chars().next().map_or(1, char::len_utf8);
         }
-        ws_cnt += 1;
     }
 
-    ws_cnt
+    None
+}
+
+
+// 错误恢复时跳过的字节数：字符串跳至闭合的双引号之后，其余跳至下一个空白符或界定符
+fn invalid_bytes(rest: &str) -> usize {
+    if rest.starts_with('"') {
+        let mut pre_escape = false;
+        for (i, ch) in rest.char_indices().skip(1) {
+            if ch == '"' && !pre_escape {
+                return i + 1;
+            }
+            pre_escape = !pre_escape && ch == '\\';
+        }
+        return rest.len();
+    }
+
+    match rest.char_indices().skip(1).find(|(_, ch)| ch.is_whitespace() || "()\";".contains(*ch)) {
+        Some((i, _)) => i,
+        None => rest.len(),
+    }
 }
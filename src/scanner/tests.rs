@@ -0,0 +1,177 @@
+use crate::{ScanError, TokenType, ValueType};
+use super::{scan, scan_all, scan_lossless};
+
+
+// 各词法单元的类型、位置及值
+fn tokens(source: &str) -> Vec<(TokenType, (usize, usize), Option<ValueType>)> {
+    let Ok((tokens, token_table)) = scan(source) else {
+        panic!("scan failed: {}", source);
+    };
+    tokens.iter()
+        .map(|token| {
+            let item = &token_table[token.table_ptr];
+            (token.token_type, item.index, item.value.clone())
+        })
+        .collect()
+}
+
+
+#[test]
+fn skips_line_and_block_comments() {
+    let source = "; header\n(a #| inline |# b) ; trailing\n#| multi\nline |# c";
+    let positions: Vec<(usize, usize)> = tokens(source).into_iter().map(|(_, index, _)| index).collect();
+    assert_eq!(positions, [(1, 0), (1, 1), (1, 16), (1, 17), (3, 8)]);
+}
+
+
+#[test]
+fn block_comments_nest() {
+    assert_eq!(tokens("#| a #| b |# c |# x").len(), 1);
+}
+
+
+#[test]
+fn reports_unterminated_block_comment_at_its_start() {
+    assert!(matches!(scan("x\n  #| a #| b |#"), Err(ScanError::UnterminatedComment((1, 2)))));
+}
+
+
+fn string_value(source: &str) -> String {
+    match tokens(source).as_slice() {
+        [(TokenType::Const, _, Some(ValueType::Str(value)))] => value.clone(),
+        other => panic!("expected one string constant, got {:?}", other),
+    }
+}
+
+
+#[test]
+fn decodes_string_escapes() {
+    assert_eq!(string_value(r#""a\n\t\r\0\\\"b""#), "a\n\t\r\0\\\"b");
+    assert_eq!(string_value(r#""\x41;\x3bb;\u{1F600}""#), "A\u{3bb}\u{1F600}");
+}
+
+
+#[test]
+fn non_ascii_strings_keep_their_characters() {
+    assert_eq!(string_value("\"héllo wörld\""), "héllo wörld");
+    assert_eq!(tokens("(\"日本\" x)")[2].1, (0, 6));
+}
+
+
+#[test]
+fn reports_invalid_escape_at_the_backslash() {
+    assert!(matches!(scan(r#"(f "ab\q")"#), Err(ScanError::InvalidEscape((0, 6)))));
+    assert!(matches!(scan("x\n \"é\\x110000;\""), Err(ScanError::InvalidEscape((1, 3)))));
+    assert!(matches!(scan(r#""\u41""#), Err(ScanError::InvalidEscape((0, 1)))));
+    assert!(matches!(scan(r#""\x41""#), Err(ScanError::InvalidEscape((0, 1)))));
+}
+
+
+#[test]
+fn strings_span_lines() {
+    assert_eq!(string_value("\"first\n  second\""), "first\n  second");
+    let positions: Vec<(usize, usize)> = tokens("(\"a\nbc\" x)").into_iter().map(|(_, index, _)| index).collect();
+    assert_eq!(positions, [(0, 0), (0, 1), (1, 4), (1, 5)]);
+}
+
+
+#[test]
+fn reports_unterminated_string_at_its_start() {
+    assert!(matches!(scan("(a\n  \"open\nstill open"), Err(ScanError::UnterminatedString((1, 2)))));
+}
+
+
+#[test]
+fn scan_all_skips_bad_tokens_and_reports_each() {
+    let (tokens, token_table, errors) = scan_all("(a {b} \"x\\q\" c)\n[d");
+    let positions: Vec<(usize, usize)> = tokens.iter().map(|token| token_table[token.table_ptr].index).collect();
+    assert_eq!(positions, [(0, 0), (0, 1), (0, 13), (0, 14)]);
+    assert_eq!(errors, [ScanError::InvalidCharacter((0, 3)), ScanError::InvalidEscape((0, 9)), ScanError::InvalidCharacter((1, 0))]);
+}
+
+
+#[test]
+fn scan_all_stops_at_an_unterminated_block_comment() {
+    let (tokens, _, errors) = scan_all("x #| open\n y");
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(errors, [ScanError::UnterminatedComment((0, 2))]);
+}
+
+
+#[test]
+fn scan_reports_the_first_error_only() {
+    assert_eq!(scan("{ }").err(), Some(ScanError::InvalidCharacter((0, 0))));
+    let (_, _, errors) = scan_all("{ }");
+    assert_eq!(errors.len(), 2);
+}
+
+
+#[test]
+fn lossless_scan_emits_trivia_that_tiles_the_input() {
+    let source = "; c\n(a  #| b |#\t\"é\")\n";
+    let Ok((tokens, token_table)) = scan_lossless(source) else {
+        panic!("scan failed");
+    };
+    let kinds: Vec<TokenType> = tokens.iter().map(|token| token.token_type).collect();
+    assert_eq!(kinds, [
+        TokenType::LineComment, TokenType::Newline, TokenType::LParen, TokenType::Id, TokenType::Whitespace,
+        TokenType::BlockComment, TokenType::Whitespace, TokenType::Const, TokenType::RParen, TokenType::Newline,
+    ]);
+    let lengths: usize = token_table.iter().map(|item| item.len).sum();
+    assert_eq!(lengths, source.chars().count());
+    assert_eq!(token_table[5].index, (1, 4));
+
+    // 非无损扫描不含trivia
+    let Ok((tokens, _)) = scan(source) else {
+        panic!("scan failed");
+    };
+    assert!(tokens.iter().all(|token| !token.token_type.is_trivia()));
+}
+
+
+#[test]
+fn numeric_literals_with_prefixes() {
+    let values: Vec<String> = tokens("#xff #e0.5 -inf.0 1_000 .5 x1")
+        .into_iter()
+        .map(|(token_type, _, value)| format!("{:?} {}", token_type, value.map(|value| value.to_string()).unwrap_or_default()))
+        .collect();
+    assert_eq!(values, ["Const 255", "Const 1/2", "Const -inf.0", "Const 1000", "Const 0.5", "Id \"x1\""]);
+}
+
+
+#[test]
+fn reports_malformed_numbers_at_the_offending_character() {
+    assert_eq!(scan("(f #x1g)").err(), Some(ScanError::InvalidToken((0, 6))));
+    assert_eq!(scan("\n  1/0").err(), Some(ScanError::InvalidToken((1, 4))));
+    assert_eq!(scan("1.2.3").err(), Some(ScanError::InvalidToken((0, 3))));
+}
+
+
+#[test]
+fn char_literals() {
+    let values: Vec<(String, (usize, usize))> = tokens("(#\\a #\\space #\\x41 #\\( #\\) #\\λ #\\x)")
+        .into_iter()
+        .filter(|(token_type, ..)| *token_type == TokenType::Const)
+        .map(|(_, index, value)| (value.map(|value| value.to_string()).unwrap_or_default(), index))
+        .collect();
+    assert_eq!(values, [
+        (String::from("#\\a"), (0, 1)),
+        (String::from("#\\space"), (0, 5)),
+        (String::from("#\\A"), (0, 13)),
+        (String::from("#\\("), (0, 19)),
+        (String::from("#\\)"), (0, 23)),
+        (String::from("#\\λ"), (0, 27)),
+        (String::from("#\\x"), (0, 31)),
+    ]);
+    assert_eq!(tokens("#\\ ")[0].2, Some(ValueType::Char(' ')));
+    assert_eq!(tokens("#\\x7f")[0].2.as_ref().map(ValueType::to_string).as_deref(), Some("#\\delete"));
+    assert_eq!(tokens("#\\x1")[0].2.as_ref().map(ValueType::to_string).as_deref(), Some("#\\x1"));
+}
+
+
+#[test]
+fn reports_malformed_char_literals() {
+    assert_eq!(scan("(a #\\bogus)").err(), Some(ScanError::InvalidToken((0, 5))));
+    assert_eq!(scan("#\\xD800").err(), Some(ScanError::InvalidToken((0, 2))));
+    assert_eq!(scan("#\\").err(), Some(ScanError::InvalidToken((0, 0))));
+}
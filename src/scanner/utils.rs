@@ -1,12 +1,12 @@
-use crate::{ScanError, TokenUnit, TokenType, TableItem, ValueType};
+use crate::{CHAR_NAMES, ScanError, TokenUnit, TokenType, TableItem, ValueType, number::Number};
 
 
 // 功能：
-// 传入一个以非空白字符开头的字符串，识别出第一个词法单元，
+// 传入一个以非空白字符开头的字符串（可含多行，仅字符串常量会跨行），识别出第一个词法单元，
 // 返回词法单元和符号表条目。（词法单元字符数暂存于TokenUnit.table_ptr中）
 
 // 识别顺序：
-// (1) 左右括号（即除双引号外的界定符）
+// (1) 左右括号（即除双引号外的界定符）、点对记号、准引用与反引用记号及数据注释
 // (2) 常量（含字符串）
 // (3) 特殊形式关键字
 // (4) 用户自定义标识符
@@ -17,7 +17,7 @@ pub fn tokenize(line: &str, row: usize, column: usize) -> Result<(TokenUnit, Tab
         return Ok(result);
     }
 
-    if let Some(result) = recog_const(line, row, column) {
+    if let Some(result) = recog_const(line, row, column)? {
         return Ok(result);
     }
 
@@ -83,72 +83,237 @@ fn generate_valid_chars() -> Vec<char> {
     // 添加单、双引号
     valid_chars.extend(['\'', '"']);
 
+    // 添加注释及布尔常量所用符号
+    valid_chars.extend(['#', ';', '|']);
+
+    // 添加点对记号及小数点
+    valid_chars.push('.');
+
+    // 添加准引用及反引用记号
+    valid_chars.extend(['`', ',', '@']);
+
     valid_chars
 }
 
 
 fn recog_mark(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableItem)> {
+    // 拼接反引用`,@`
+    if line.starts_with(",@") {
+        return Some((TokenUnit { token_type: TokenType::UnquoteSplicingMark, table_ptr: 2 }, TableItem {
+            index: (row, column),
+            len: 2,
+            value: None,
+        }));
+    }
+
+    // 数据注释`#;`
+    if line.starts_with("#;") {
+        return Some((TokenUnit { token_type: TokenType::DatumComment, table_ptr: 2 }, TableItem {
+            index: (row, column),
+            len: 2,
+            value: None,
+        }));
+    }
+
+    // 向量的左界定符`#(`
+    if line.starts_with("#(") {
+        return Some((TokenUnit { token_type: TokenType::VectorParen, table_ptr: 2 }, TableItem {
+            index: (row, column),
+            len: 2,
+            value: None,
+        }));
+    }
+
     let token_unit = if let Some(ch) = line.chars().next() {
         match ch {
             '(' => Some(TokenUnit { token_type: TokenType::LParen, table_ptr: 1 }),
             ')' => Some(TokenUnit { token_type: TokenType::RParen, table_ptr: 1 }),
+            '`' => Some(TokenUnit { token_type: TokenType::QuasiquoteMark, table_ptr: 1 }),
+            ',' => Some(TokenUnit { token_type: TokenType::UnquoteMark, table_ptr: 1 }),
+            // 单独的`.`（其后为空白符、界定符或输入末尾）；`.5`等留待常量识别
+            '.' if line[1..].chars().next().is_none_or(|next| next.is_whitespace() || "()\";".contains(next)) => {
+                Some(TokenUnit { token_type: TokenType::Dot, table_ptr: 1 })
+            },
             _ => None,
         }
     } else {
         None
     };
 
-    match token_unit {
-        Some(token_unit) => Some((token_unit, TableItem {
+    token_unit.map(|token_unit| {
+        let len = token_unit.table_ptr;
+        (token_unit, TableItem {
             index: (row, column),
+            len,
             value: None,
-        })),
-        _ => None,
-    }
+        })
+    })
 }
 
 
-fn recog_const(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableItem)> {
+fn recog_const(line: &str, row: usize, column: usize) -> Result<Option<(TokenUnit, TableItem)>, ScanError> {
     // 字符串识别（双引号的特性使得字符串须单独写识别逻辑）
     if line.starts_with("\"") {
-        let mut pre_escape = false;
-        for (i, ch) in line.chars().enumerate() {
-            if i != 0 && ch == '\"' && !pre_escape {
-                return Some((TokenUnit {
-                    token_type: TokenType::Const,
-                    table_ptr: i + 1
-                }, TableItem {
-                    index: (row, column),
-                    value: Some(ValueType::Str(String::from(&line[1..i])))
-                }))
-            }
-            pre_escape = ch == '\\';
-        }
-        return None;
+        return recog_str(line, row, column);
+    }
+
+    // 字符常量识别（`#\(`等字符本身可为界定符）
+    if line.starts_with("#\\") {
+        return recog_char(line, row, column);
     }
 
-    // 整型、浮点型、布尔型常量识别
+    // 数值、布尔型常量识别
     if let Some(first) = line.split_whitespace().next() {
-        if let Some(first) = first.split(|c| c == '(' || c == ')' || c == '\"').next() {
-            match parse_const(first) {
-                Some((value_type, token_len)) => Some((TokenUnit {
+        if let Some(first) = first.split(['(', ')', '\"', ';']).next() {
+            let parsed = parse_const(first).map_err(|offset| {
+                // 非法字符优先报告为InvalidCharacter，否则指出字面量中首个出错的字符
+                detect_invalid_char(first, row, column).err().unwrap_or(ScanError::InvalidToken((row, column + offset)))
+            })?;
+            match parsed {
+                Some((value_type, token_len)) => Ok(Some((TokenUnit {
                     token_type: TokenType::Const,
                     table_ptr: token_len,
                 }, TableItem {
                     index: (row, column),
+                    len: token_len,
                     value: Some(value_type),
-                })),
-                _ => None,
+                }))),
+                _ => Ok(None),
             }
-        } else { None }
-    } else { None }
+        } else { Ok(None) }
+    } else { Ok(None) }
+}
+
+
+// 识别字符常量：`#\a`、具名字符`#\space`或十六进制码点`#\x41`
+fn recog_char(line: &str, row: usize, column: usize) -> Result<Option<(TokenUnit, TableItem)>, ScanError> {
+    let mut chars = line.chars().skip(2);
+    let Some(first) = chars.next() else {
+        return Err(ScanError::InvalidToken((row, column)));
+    };
+
+    // 名字延续至下一个空白符或界定符；首字符为空白符或界定符时即为该字符本身
+    let name_len = if first.is_whitespace() || "()\";".contains(first) {
+        1
+    } else {
+        1 + chars.take_while(|ch| !ch.is_whitespace() && !"()\";".contains(*ch)).count()
+    };
+    let name: String = line.chars().skip(2).take(name_len).collect();
+
+    let value = if name_len == 1 {
+        Some(first)
+    } else if let Some((_, named)) = CHAR_NAMES.iter().find(|(named, _)| *named == name) {
+        Some(*named)
+    } else {
+        name.strip_prefix('x').and_then(hex_to_char)
+    };
+
+    match value {
+        Some(ch) => Ok(Some((TokenUnit {
+            token_type: TokenType::Const,
+            table_ptr: 2 + name_len,
+        }, TableItem {
+            index: (row, column),
+            len: 2 + name_len,
+            value: Some(ValueType::Char(ch)),
+        }))),
+        None => Err(ScanError::InvalidToken((row, column + 2))),
+    }
+}
+
+
+// 识别字符串常量并解码其中的转义序列，字符串可跨行
+fn recog_str(line: &str, row: usize, column: usize) -> Result<Option<(TokenUnit, TableItem)>, ScanError> {
+    let mut value = String::new();
+    // 下一字符在源码中的位置
+    let mut next_index = (row, column + 1);
+    // (字符序号, 字符位置, 字符)，跳过开头的双引号
+    let mut chars = line.chars().enumerate().skip(1).map(|(i, ch)| {
+        let index = next_index;
+        next_index = if ch == '\n' { (index.0 + 1, 0) } else { (index.0, index.1 + 1) };
+        (i, index, ch)
+    });
+
+    while let Some((i, index, ch)) = chars.next() {
+        match ch {
+            '"' => return Ok(Some((TokenUnit {
+                token_type: TokenType::Const,
+                table_ptr: i + 1,
+            }, TableItem {
+                index: (row, column),
+                len: i + 1,
+                value: Some(ValueType::Str(value)),
+            }))),
+
+            '\\' => {
+                // 转义序列的错误位置为反斜杠所在处
+                let escape_error = ScanError::InvalidEscape(index);
+                let Some((_, _, escaped)) = chars.next() else {
+                    break;
+                };
+
+                match escaped {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    '0' => value.push('\0'),
+                    '\\' => value.push('\\'),
+                    '"' => value.push('"'),
+
+                    // `\x41;`：以分号结尾的十六进制码点
+                    'x' => {
+                        let mut digits = String::new();
+                        loop {
+                            match chars.next() {
+                                Some((_, _, ';')) => break,
+                                Some((_, _, d)) if d.is_ascii_hexdigit() => digits.push(d),
+                                _ => return Err(escape_error),
+                            }
+                        }
+                        value.push(hex_to_char(&digits).ok_or(escape_error)?);
+                    },
+
+                    // `\u{41}`：花括号包围的十六进制码点
+                    'u' => {
+                        if !matches!(chars.next(), Some((_, _, '{'))) {
+                            return Err(escape_error);
+                        }
+                        let mut digits = String::new();
+                        loop {
+                            match chars.next() {
+                                Some((_, _, '}')) => break,
+                                Some((_, _, d)) if d.is_ascii_hexdigit() => digits.push(d),
+                                _ => return Err(escape_error),
+                            }
+                        }
+                        value.push(hex_to_char(&digits).ok_or(escape_error)?);
+                    },
+
+                    _ => return Err(escape_error),
+                }
+            },
+
+            _ => value.push(ch),
+        }
+    }
+
+    Err(ScanError::UnterminatedString((row, column)))
+}
+
+
+// 将十六进制码点转换为字符（码点须为合法的Unicode标量值）
+fn hex_to_char(digits: &str) -> Option<char> {
+    if digits.is_empty() || digits.len() > 6 {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok().and_then(char::from_u32)
 }
 
 
 // 先于recog_id()调用
 fn recog_reserved(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableItem)> {
     let token_unit = if let Some(first) = line.split_whitespace().next() {
-        match first.split(|c| c == '(' || c == ')' || c == '\"').next() {
+        match first.split(['(', ')', '\"', ';']).next() {
             Some("define") => Some(TokenUnit { token_type: TokenType::Define, table_ptr: "define".len() }),
             Some("if") => Some(TokenUnit { token_type: TokenType::If, table_ptr: "if".len() }),
             Some("list") => Some(TokenUnit { token_type: TokenType::List, table_ptr: "list".len() }),
@@ -177,32 +342,36 @@ fn recog_reserved(line: &str, row: usize, column: usize) -> Option<(TokenUnit, T
         }
     } else { None };
 
-    match token_unit {
-        Some(token_unit) => Some((token_unit, TableItem { index: (row, column), value: None })),
-        _ => None,
-    }
+    token_unit.map(|token_unit| {
+        let len = token_unit.table_ptr;
+        (token_unit, TableItem { index: (row, column), len, value: None })
+    })
 }
 
 
 fn recog_id(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableItem)> {
     if let Some(first) = line.split_whitespace().next() {
-        if let Some(first) = first.split(|c| c == '(' || c == ')' || c == '\"').next() {
-            if first == "" {
+        if let Some(first) = first.split(['(', ')', '\"', ';']).next() {
+            // 运算符留待recog_op()、recog_cmp()识别
+            if first.is_empty() || ["*", "/", "<", ">", "=", "<=", ">="].contains(&first) {
                 None
             } else {
-                for (i, ch) in first.chars().enumerate() {
-                    if i == 0 && ch.is_digit(10) {
+                // 省略号`...`是标识符（用于syntax-rules的模式与模板）
+                for (i, ch) in first.chars().enumerate().filter(|_| first != "...") {
+                    if i == 0 && ch.is_ascii_digit() {
                         return None;
                     }
-                    if !ch.is_alphabetic() && !ch.is_numeric() && "-_?!".find(ch) == None {
+                    // `+`只能出现在首字符之外；`*/<=>`可作首字符（如`=>`、`<tag>`）
+                    if !ch.is_alphabetic() && !ch.is_numeric() && !"-_?!*/<=>".contains(ch) && (i == 0 || ch != '+') {
                         return None;
                     }
                 }
                 Some((TokenUnit {
                     token_type: TokenType::Id,
-                    table_ptr: first.len(),
+                    table_ptr: first.chars().count(),
                 }, TableItem {
                     index: (row, column),
+                    len: first.chars().count(),
                     value: Some(ValueType::Str(String::from(first))),
                 }))
             }
@@ -213,7 +382,7 @@ fn recog_id(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableIt
 
 fn recog_op(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableItem)> {
     let token_unit = if let Some(first) = line.split_whitespace().next() {
-        if let Some(first) = first.split(|c| c == '(' || c == ')' || c == '\"').next() {
+        if let Some(first) = first.split(['(', ')', '\"', ';']).next() {
             match first {
                 "+" => Some(TokenUnit { token_type: TokenType::PlusOp, table_ptr: "+".len(), }),
                 "-" => Some(TokenUnit { token_type: TokenType::MinusOp, table_ptr: "-".len(), }),
@@ -225,20 +394,20 @@ fn recog_op(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableIt
         } else { None }
     } else { None };
 
-    match token_unit {
-        Some(token_unit) => Some((token_unit, TableItem {
+    token_unit.map(|token_unit| {
+        let len = token_unit.table_ptr;
+        (token_unit, TableItem {
             index: (row, column),
+            len,
             value: None,
-        })),
-
-        _ => None,
-    }
+        })
+    })
 }
 
 
 fn recog_cmp(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableItem)> {
     let token_unit = if let Some(first) = line.split_whitespace().next() {
-        if let Some(first) = first.split(|c| c == '(' || c == ')' || c == '\"').next() {
+        if let Some(first) = first.split(['(', ')', '\"', ';']).next() {
             match first {
                 "<=" => Some(TokenUnit { token_type: TokenType::LessEq, table_ptr: "<=".len(), }),
                 ">=" => Some(TokenUnit { token_type: TokenType::GreaterEq, table_ptr: ">=".len(), }),
@@ -251,14 +420,14 @@ fn recog_cmp(line: &str, row: usize, column: usize) -> Option<(TokenUnit, TableI
         } else { None }
     } else { None };
 
-    match token_unit {
-        Some(token_unit) => Some((token_unit, TableItem {
+    token_unit.map(|token_unit| {
+        let len = token_unit.table_ptr;
+        (token_unit, TableItem {
             index: (row, column),
+            len,
             value: None,
-        })),
-
-        _ => None,
-    }
+        })
+    })
 }
 
 
@@ -276,21 +445,35 @@ pub fn chars2bytes(input: &str, charcnt: usize) -> usize {
 }
 
 
-// 将字符串转换为整型、浮点型或布尔型常量
-fn parse_const(input: &str) -> Option<(ValueType, usize)> {
+// 将字符串转换为数值或布尔型常量；形似数值却不合文法时返回首个出错字符的偏移
+fn parse_const(input: &str) -> Result<Option<(ValueType, usize)>, usize> {
     let token_len= input.len();
 
-    if let Ok(int_val) = input.parse::<isize>() {
-        return Some((ValueType::Int(int_val), token_len));
+    match input {
+        "#t" => return Ok(Some((ValueType::Bool(true), token_len))),
+        "#f" => return Ok(Some((ValueType::Bool(false), token_len))),
+        _ => (),
     }
 
-    if let Ok(float_val) = input.parse::<f64>() {
-        return Some((ValueType::Float(float_val), token_len));
+    if !is_numeric(input) {
+        return Ok(None);
     }
+    Number::parse(input).map(|number| Some((ValueType::Number(number), token_len)))
+}
 
-    match input {
-        "#t" => Some((ValueType::Bool(true), token_len)),
-        "#f" => Some((ValueType::Bool(false), token_len)),
-        _ => None,
+
+// 形似数值的字面量：以数位、`.数位`、带符号的数位或特殊浮点数、或者进制与精确性前缀开头
+fn is_numeric(input: &str) -> bool {
+    let unsigned = input.strip_prefix(['+', '-']);
+    let body = unsigned.unwrap_or(input);
+    let special = unsigned.is_some_and(|body| ["inf.0", "nan.0"].iter().any(|name| body.eq_ignore_ascii_case(name)));
+
+    let mut chars = body.chars();
+    match (chars.next(), chars.next()) {
+        _ if special => true,
+        (Some('#'), Some(prefix)) => unsigned.is_none() && "xXbBoOdDeEiI".contains(prefix),
+        (Some('.'), Some(next)) => next.is_ascii_digit(),
+        (Some(first), _) => first.is_ascii_digit(),
+        _ => false,
     }
 }
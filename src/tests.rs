@@ -0,0 +1,32 @@
+use std::error::Error as _;
+
+use crate::{Error, EvalError, ParseError, ScanError};
+
+
+#[test]
+fn errors_display_one_based_positions() {
+    assert_eq!(ScanError::InvalidCharacter((0, 4)).to_string(), "invalid character at 1:5");
+    assert_eq!(ParseError::UnexpectedToken((2, 0)).to_string(), "unexpected token at 3:1");
+    assert_eq!(ParseError::UnexpectedEndOfInput.to_string(), "unexpected end of input");
+    assert_eq!(ParseError::UnclosedList((1, 2)).to_string(), "expected `)` to close `(` opened at 2:3");
+    assert_eq!(EvalError::UnboundVariable(String::from("y"), (0, 5)).to_string(), "unbound variable `y` at 1:6");
+    assert_eq!(EvalError::DivisionByZero((3, 1)).to_string(), "division by zero at 4:2");
+}
+
+
+#[test]
+fn unified_error_wraps_each_stage() {
+    let error = Error::from(ScanError::UnterminatedString((1, 2)));
+    assert_eq!(error, Error::Scan(ScanError::UnterminatedString((1, 2))));
+    assert_eq!(error.index(), Some((1, 2)));
+    assert_eq!(error.to_string(), "scan error: unterminated string literal at 2:3");
+    assert!(error.source().is_none());
+
+    let error = Error::from(ParseError::UnexpectedEndOfInput);
+    assert_eq!(error.index(), None);
+    assert_eq!(error.to_string(), "parse error: unexpected end of input");
+
+    let error = Error::from(EvalError::DivisionByZero((0, 0)));
+    assert_eq!(error.index(), Some((0, 0)));
+    assert_eq!(error.to_string(), "eval error: division by zero at 1:1");
+}